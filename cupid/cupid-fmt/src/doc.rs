@@ -0,0 +1,221 @@
+use std::ops::Add;
+
+/// A document to lay out within a line width: the lines of a group are either all kept flat, when
+/// the group fits on the rest of the line, or all broken.
+#[derive(Debug, Clone)]
+pub enum Doc {
+    Text(String),
+    /// A space, or a line break when its group is broken.
+    Line,
+    /// Nothing, or a line break when its group is broken.
+    SoftLine,
+    /// Always a line break, which breaks all the groups around it.
+    HardLine,
+    Nest(usize, Box<Doc>),
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+impl Doc {
+    pub fn nil() -> Doc {
+        Doc::Concat(vec![])
+    }
+
+    pub fn text(text: impl Into<String>) -> Doc {
+        Doc::Text(text.into())
+    }
+
+    pub fn line() -> Doc {
+        Doc::Line
+    }
+
+    pub fn softline() -> Doc {
+        Doc::SoftLine
+    }
+
+    pub fn hardline() -> Doc {
+        Doc::HardLine
+    }
+
+    pub fn concat(docs: impl IntoIterator<Item = Doc>) -> Doc {
+        docs.into_iter().fold(Doc::nil(), Add::add)
+    }
+
+    /// Joins the documents, with `separator` between each of them.
+    pub fn join(docs: impl IntoIterator<Item = Doc>, separator: Doc) -> Doc {
+        let mut joined = Doc::nil();
+        for (i, doc) in docs.into_iter().enumerate() {
+            if i > 0 {
+                joined = joined + separator.clone();
+            }
+            joined = joined + doc;
+        }
+        joined
+    }
+
+    /// Indents the lines broken within the document by `indent` more columns.
+    pub fn nest(self, indent: usize) -> Doc {
+        Doc::Nest(indent, Box::new(self))
+    }
+
+    pub fn group(self) -> Doc {
+        Doc::Group(Box::new(self))
+    }
+
+    /// The first text of the document, ignoring the empty ones.
+    pub fn first_text(&self) -> Option<&str> {
+        match self {
+            Doc::Text(text) if !text.is_empty() => Some(text),
+            Doc::Nest(_, doc) | Doc::Group(doc) => doc.first_text(),
+            Doc::Concat(docs) => docs.iter().find_map(Doc::first_text),
+            _ => None,
+        }
+    }
+
+    /// Whether the document always spans several lines.
+    pub fn is_multiline(&self) -> bool {
+        match self {
+            Doc::HardLine => true,
+            Doc::Nest(_, doc) | Doc::Group(doc) => doc.is_multiline(),
+            Doc::Concat(docs) => docs.iter().any(Doc::is_multiline),
+            _ => false,
+        }
+    }
+
+    /// Lays out the document to fit in `width` columns where it can. Lines never end with spaces.
+    pub fn render(&self, width: usize) -> String {
+        let mut out = String::new();
+        let mut column = 0;
+        let mut stack: Vec<(usize, Mode, &Doc)> = vec![(0, Mode::Break, self)];
+        while let Some((indent, mode, doc)) = stack.pop() {
+            match doc {
+                Doc::Text(text) => {
+                    out.push_str(text);
+                    column = match text.rfind('\n') {
+                        Some(newline) => text[newline + 1..].chars().count(),
+                        None => column + text.chars().count(),
+                    };
+                }
+                Doc::Line | Doc::SoftLine if mode == Mode::Flat => {
+                    if let Doc::Line = doc {
+                        out.push(' ');
+                        column += 1;
+                    }
+                }
+                Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                    out.truncate(out.trim_end_matches(' ').len());
+                    out.push('\n');
+                    out.push_str(&" ".repeat(indent));
+                    column = indent;
+                }
+                Doc::Nest(more, doc) => stack.push((indent + more, mode, doc)),
+                Doc::Group(doc) => {
+                    let flat = (indent, Mode::Flat, &**doc);
+                    let mode = match fits(width.saturating_sub(column), flat, &stack) {
+                        true => Mode::Flat,
+                        false => Mode::Break,
+                    };
+                    stack.push((indent, mode, doc));
+                }
+                Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc))),
+            }
+        }
+        out.truncate(out.trim_end_matches(' ').len());
+        out
+    }
+}
+
+/// Whether `next` fits in the `remaining` columns, along with what follows it up to the next line
+/// break.
+fn fits(remaining: usize, next: (usize, Mode, &Doc), rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut remaining = remaining as isize;
+    let mut rest = rest.iter().rev();
+    let mut stack = vec![next];
+    loop {
+        let (indent, mode, doc) = match stack.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some(next) => *next,
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Text(text) => {
+                remaining -= text.split('\n').next().unwrap_or_default().chars().count() as isize;
+                if remaining < 0 {
+                    return false;
+                }
+            }
+            Doc::Line if mode == Mode::Flat => remaining -= 1,
+            Doc::SoftLine if mode == Mode::Flat => (),
+            Doc::HardLine if mode == Mode::Flat => return false,
+            Doc::Line | Doc::SoftLine | Doc::HardLine => return true,
+            Doc::Nest(more, doc) => stack.push((indent + more, mode, doc)),
+            Doc::Group(doc) => stack.push((indent, mode, doc)),
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc))),
+        }
+        if remaining < 0 {
+            return false;
+        }
+    }
+}
+
+impl Add for Doc {
+    type Output = Doc;
+
+    fn add(self, other: Doc) -> Doc {
+        match (self, other) {
+            (Doc::Concat(mut docs), Doc::Concat(others)) => {
+                docs.extend(others);
+                Doc::Concat(docs)
+            }
+            (Doc::Concat(mut docs), other) => {
+                docs.push(other);
+                Doc::Concat(docs)
+            }
+            (doc, Doc::Concat(mut others)) => {
+                others.insert(0, doc);
+                Doc::Concat(others)
+            }
+            (doc, other) => Doc::Concat(vec![doc, other]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Doc;
+
+    fn call(name: &str, args: &[&str]) -> Doc {
+        let args = Doc::join(args.iter().map(|arg| Doc::text(*arg)), Doc::text(",") + Doc::line());
+        let args = (Doc::softline() + args).nest(4) + Doc::softline();
+        (Doc::text(name) + Doc::text("(") + args + Doc::text(")")).group()
+    }
+
+    #[test]
+    fn breaks_groups_that_do_not_fit() {
+        let doc = call("add", &["one", "two"]);
+        assert_eq!(doc.render(20), "add(one, two)");
+        assert_eq!(doc.render(10), "add(\n    one,\n    two\n)");
+    }
+
+    #[test]
+    fn keeps_inner_groups_flat_when_they_fit() {
+        let inner = call("pair", &["a", "b"]);
+        let args = Doc::join([Doc::text("first"), inner], Doc::text(",") + Doc::line());
+        let doc = (Doc::text("f(") + (Doc::softline() + args).nest(4) + Doc::softline())
+            + Doc::text(")");
+        assert_eq!(doc.group().render(14), "f(\n    first,\n    pair(a, b)\n)");
+
+        let doc = (Doc::text("{") + (Doc::hardline() + call("f", &["a"])).nest(4))
+            + Doc::hardline()
+            + Doc::text("}");
+        assert_eq!(doc.group().render(80), "{\n    f(a)\n}");
+    }
+}
@@ -0,0 +1,165 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use cupid::{
+    analyze::{
+        complete::{complete, Completion},
+        index::{index_source, SymbolIndex},
+        lint::{lint_source, LintConfig},
+        rename::{rename, TextEdit},
+    },
+    error::{CupidError, Severity},
+    span::Span,
+};
+use lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Position, Range};
+
+/// An open file, along with everything the server knows about it.
+pub struct Document {
+    pub text: String,
+    pub lines: LineIndex,
+    /// The index of the document as of its last change, if it checked without errors.
+    pub index: Option<SymbolIndex>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Document {
+    pub fn new(text: String) -> Self {
+        let lines = LineIndex::new(&text);
+        let (index, diagnostics) = match catch_panic(|| index_source(&text)) {
+            Ok(index) => {
+                let lints = catch_panic(|| lint_source(&text, &LintConfig::default()));
+                let lints = lints.unwrap_or_default();
+                (Some(index), lints.iter().map(|lint| diagnostic(&text, &lines, lint)).collect())
+            }
+            Err(error) => (None, vec![diagnostic(&text, &lines, &error)]),
+        };
+        Self {
+            text,
+            lines,
+            index,
+            diagnostics,
+        }
+    }
+
+    pub fn range(&self, span: Span) -> Range {
+        self.lines.range(&self.text, span)
+    }
+
+    pub fn offset(&self, position: Position) -> usize {
+        self.lines.offset(&self.text, position)
+    }
+
+    pub fn complete(&self, position: Position) -> Result<Vec<Completion>, CupidError> {
+        let offset = self.offset(position);
+        catch_panic(|| complete(&self.text, offset))
+    }
+
+    pub fn rename(&self, position: Position, new_name: &str) -> Result<Vec<TextEdit>, CupidError> {
+        let offset = self.offset(position);
+        catch_panic(|| rename(&self.text, offset, new_name))
+    }
+}
+
+/// The parser still panics on some malformed input, which must not take the server down.
+fn catch_panic<T>(f: impl FnOnce() -> Result<T, CupidError>) -> Result<T, CupidError> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(panic) => {
+            let message = match panic.downcast_ref::<String>() {
+                Some(message) => message.clone(),
+                None => panic.downcast_ref::<&str>().unwrap_or(&"unknown error").to_string(),
+            };
+            Err(CupidError::parse_error(message, None))
+        }
+    }
+}
+
+fn diagnostic(text: &str, lines: &LineIndex, error: &CupidError) -> Diagnostic {
+    let range = match error.span {
+        Some(span) => lines.range(text, span),
+        None => Range::default(),
+    };
+    let severity = match error.severity {
+        Severity::Error => DiagnosticSeverity::ERROR,
+        Severity::Warning => DiagnosticSeverity::WARNING,
+        Severity::Lint => DiagnosticSeverity::HINT,
+    };
+    Diagnostic {
+        range,
+        severity: Some(severity),
+        code: Some(NumberOrString::String(error.code.to_string())),
+        source: Some("cupid".to_string()),
+        message: match &error.help {
+            Some(help) => {
+                format!("{} {}: {}\nhelp: {}", error.kind, error.severity, error.message, help)
+            }
+            None => format!("{} {}: {}", error.kind, error.severity, error.message),
+        },
+        ..Default::default()
+    }
+}
+
+/// Converts between byte offsets, which spans use, and the line/UTF-16 column positions of LSP.
+pub struct LineIndex {
+    starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        let mut starts = vec![0];
+        starts.extend(text.match_indices('\n').map(|(index, _)| index + 1));
+        Self { starts }
+    }
+
+    pub fn position(&self, text: &str, offset: usize) -> Position {
+        let offset = offset.min(text.len());
+        let line = self.starts.partition_point(|start| *start <= offset) - 1;
+        let line_start = self.starts[line];
+        let character = text[line_start..offset].encode_utf16().count();
+        Position::new(line as u32, character as u32)
+    }
+
+    pub fn range(&self, text: &str, span: Span) -> Range {
+        Range::new(self.position(text, span.start.index), self.position(text, span.end.index))
+    }
+
+    pub fn offset(&self, text: &str, position: Position) -> usize {
+        let line_start = match self.starts.get(position.line as usize) {
+            Some(start) => *start,
+            None => return text.len(),
+        };
+        let mut units = 0;
+        for (index, char) in text[line_start..].char_indices() {
+            if units >= position.character as usize || char == '\n' {
+                return line_start + index;
+            }
+            units += char.len_utf16();
+        }
+        text.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::Position;
+
+    use super::{Document, LineIndex};
+
+    #[test]
+    fn positions_round_trip() {
+        let text = "let a = 'é'\nlog(a)\n";
+        let lines = LineIndex::new(text);
+        for offset in [0, 4, 12, 16, text.len()] {
+            assert_eq!(lines.offset(text, lines.position(text, offset)), offset);
+        }
+        assert_eq!(lines.position(text, 17), Position::new(1, 4));
+        assert_eq!(lines.offset(text, Position::new(0, 11)), "let a = 'é'".len());
+    }
+
+    #[test]
+    fn diagnostics_point_at_the_error() {
+        let document = Document::new("let a = 1\nlog(b)\n".to_string());
+        assert!(document.index.is_none());
+        assert_eq!(document.diagnostics.len(), 1);
+        assert_eq!(document.diagnostics[0].range.start, Position::new(1, 4));
+    }
+}
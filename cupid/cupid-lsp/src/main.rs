@@ -0,0 +1,13 @@
+//! A language server for Cupid, speaking LSP over stdio.
+
+mod document;
+mod server;
+
+use lsp_server::Connection;
+
+fn main() -> server::Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    server::run(connection)?;
+    io_threads.join()?;
+    Ok(())
+}
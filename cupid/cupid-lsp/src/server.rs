@@ -0,0 +1,374 @@
+use std::{collections::HashMap, error::Error};
+
+use cupid::analyze::{
+    complete::{Completion, CompletionKind},
+    index::{Outline, SymbolKind},
+};
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        Notification as NotificationTrait, PublishDiagnostics,
+    },
+    request::{
+        Completion as CompletionRequest, DocumentSymbolRequest, GotoDefinition, HoverRequest,
+        References, Rename, Request as _,
+    },
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, GotoDefinitionParams,
+    GotoDefinitionResponse, Hover, HoverContents, HoverParams, HoverProviderCapability, Location,
+    MarkupContent, MarkupKind, OneOf, PublishDiagnosticsParams, ReferenceParams,
+    RenameParams, ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit,
+    Uri, WorkspaceEdit,
+};
+use serde_json::Value;
+
+use crate::document::Document;
+
+pub type Result<T> = std::result::Result<T, Box<dyn Error + Sync + Send>>;
+
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        rename_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![".".to_string()]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Answers requests on `connection` until the client shuts the server down.
+pub fn run(connection: Connection) -> Result<()> {
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+    let mut server = Server {
+        connection,
+        documents: HashMap::new(),
+    };
+    server.main_loop()
+}
+
+struct Server {
+    connection: Connection,
+    documents: HashMap<Uri, Document>,
+}
+
+impl Server {
+    fn main_loop(&mut self) -> Result<()> {
+        while let Ok(message) = self.connection.receiver.recv() {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    let response = self.handle_request(request);
+                    self.connection.sender.send(response.into())?;
+                }
+                Message::Notification(notification) => self.handle_notification(notification)?,
+                Message::Response(_) => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request(&self, request: Request) -> Response {
+        let id = request.id.clone();
+        let result = match request.method.as_str() {
+            HoverRequest::METHOD => params(request).and_then(|params| json(self.hover(params))),
+            GotoDefinition::METHOD => {
+                params(request).and_then(|params| json(self.definition(params)))
+            }
+            References::METHOD => params(request).and_then(|params| json(self.references(params))),
+            Rename::METHOD => params(request).and_then(|params| json(self.rename(params)?)),
+            DocumentSymbolRequest::METHOD => {
+                params(request).and_then(|params| json(self.symbols(params)))
+            }
+            CompletionRequest::METHOD => {
+                params(request).and_then(|params| json(self.completions(params)))
+            }
+            method => {
+                let message = format!("Unsupported request: {method}");
+                return Response::new_err(
+                    id,
+                    lsp_server::ErrorCode::MethodNotFound as i32,
+                    message,
+                );
+            }
+        };
+        match result {
+            Ok(value) => Response::new_ok(id, value),
+            Err(error) => error_response(id, error),
+        }
+    }
+
+    fn handle_notification(&mut self, notification: Notification) -> Result<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: <DidOpenTextDocument as NotificationTrait>::Params =
+                    serde_json::from_value(notification.params)?;
+                self.update(params.text_document.uri, params.text_document.text)
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: <DidChangeTextDocument as NotificationTrait>::Params =
+                    serde_json::from_value(notification.params)?;
+                // Only full synchronization is offered, so the last change holds the whole text.
+                match params.content_changes.into_iter().last() {
+                    Some(change) => self.update(params.text_document.uri, change.text),
+                    None => Ok(()),
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: <DidCloseTextDocument as NotificationTrait>::Params =
+                    serde_json::from_value(notification.params)?;
+                self.documents.remove(&params.text_document.uri);
+                self.publish(params.text_document.uri, vec![])
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn update(&mut self, uri: Uri, text: String) -> Result<()> {
+        let document = Document::new(text);
+        let diagnostics = document.diagnostics.clone();
+        self.documents.insert(uri.clone(), document);
+        self.publish(uri, diagnostics)
+    }
+
+    fn publish(&self, uri: Uri, diagnostics: Vec<lsp_types::Diagnostic>) -> Result<()> {
+        let params = PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version: None,
+        };
+        let notification = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+        self.connection.sender.send(notification.into())?;
+        Ok(())
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let position = params.text_document_position_params;
+        let document = self.documents.get(&position.text_document.uri)?;
+        let index = document.index.as_ref()?;
+        let offset = document.offset(position.position);
+        let (span, ty) = index.type_at(offset)?;
+        let value = match index.symbol_at(offset) {
+            Some(id) => format!("```cupid\n{}: {}\n```", index.symbol(id).name, ty),
+            None => format!("```cupid\n{}\n```", ty),
+        };
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(document.range(span)),
+        })
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let position = params.text_document_position_params;
+        let uri = position.text_document.uri;
+        let document = self.documents.get(&uri)?;
+        let index = document.index.as_ref()?;
+        let id = index.symbol_at(document.offset(position.position))?;
+        let span = index.symbol(id).span?;
+        Some(GotoDefinitionResponse::Scalar(Location::new(uri, document.range(span))))
+    }
+
+    fn references(&self, params: ReferenceParams) -> Option<Vec<Location>> {
+        let position = params.text_document_position;
+        let uri = position.text_document.uri;
+        let document = self.documents.get(&uri)?;
+        let index = document.index.as_ref()?;
+        let id = index.symbol_at(document.offset(position.position))?;
+        let declaration = match params.context.include_declaration {
+            true => index.symbol(id).span,
+            false => None,
+        };
+        let locations = declaration
+            .into_iter()
+            .chain(index.references_to(id))
+            .map(|span| Location::new(uri.clone(), document.range(span)))
+            .collect();
+        Some(locations)
+    }
+
+    fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let position = params.text_document_position;
+        let uri = position.text_document.uri;
+        let document = match self.documents.get(&uri) {
+            Some(document) => document,
+            None => return Ok(None),
+        };
+        let edits = document
+            .rename(position.position, &params.new_name)
+            .map_err(|error| error.message.clone())?
+            .into_iter()
+            .map(|edit| TextEdit::new(document.range(edit.span), edit.new_text))
+            .collect();
+        Ok(Some(WorkspaceEdit::new(HashMap::from([(uri, edits)]))))
+    }
+
+    fn symbols(&self, params: DocumentSymbolParams) -> Option<DocumentSymbolResponse> {
+        let document = self.documents.get(&params.text_document.uri)?;
+        let index = document.index.as_ref()?;
+        let symbols = index.outline.iter().map(|item| document_symbol(document, item)).collect();
+        Some(DocumentSymbolResponse::Nested(symbols))
+    }
+
+    fn completions(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let position = params.text_document_position;
+        let document = self.documents.get(&position.text_document.uri)?;
+        // Code that does not check yet has nothing to offer.
+        let completions = document.complete(position.position).ok()?;
+        Some(CompletionResponse::Array(completions.into_iter().map(completion_item).collect()))
+    }
+}
+
+fn completion_item(completion: Completion) -> CompletionItem {
+    let kind = match completion.kind {
+        CompletionKind::Keyword => CompletionItemKind::KEYWORD,
+        CompletionKind::Symbol(SymbolKind::Class) => CompletionItemKind::CLASS,
+        CompletionKind::Symbol(SymbolKind::Method) => CompletionItemKind::METHOD,
+        CompletionKind::Symbol(SymbolKind::Field) => CompletionItemKind::FIELD,
+        CompletionKind::Symbol(SymbolKind::Variable | SymbolKind::Parameter) => {
+            CompletionItemKind::VARIABLE
+        }
+        CompletionKind::Symbol(SymbolKind::Function | SymbolKind::Native) => {
+            CompletionItemKind::FUNCTION
+        }
+    };
+    CompletionItem {
+        label: completion.label,
+        kind: Some(kind),
+        detail: completion.detail,
+        ..Default::default()
+    }
+}
+
+#[allow(deprecated)]
+fn document_symbol(document: &Document, outline: &Outline) -> DocumentSymbol {
+    DocumentSymbol {
+        name: outline.name.clone(),
+        detail: None,
+        kind: match outline.kind {
+            SymbolKind::Class => lsp_types::SymbolKind::CLASS,
+            SymbolKind::Method => lsp_types::SymbolKind::METHOD,
+            SymbolKind::Field => lsp_types::SymbolKind::FIELD,
+            SymbolKind::Variable | SymbolKind::Parameter => lsp_types::SymbolKind::VARIABLE,
+            SymbolKind::Function | SymbolKind::Native => lsp_types::SymbolKind::FUNCTION,
+        },
+        tags: None,
+        deprecated: None,
+        range: document.range(outline.span),
+        selection_range: document.range(outline.name_span),
+        children: Some(
+            outline.children.iter().map(|child| document_symbol(document, child)).collect(),
+        ),
+    }
+}
+
+fn params<P: serde::de::DeserializeOwned>(request: Request) -> Result<P> {
+    Ok(serde_json::from_value(request.params)?)
+}
+
+fn json(value: impl serde::Serialize) -> Result<Value> {
+    Ok(serde_json::to_value(value)?)
+}
+
+fn error_response(id: RequestId, error: Box<dyn Error + Sync + Send>) -> Response {
+    let code = lsp_server::ErrorCode::InvalidParams as i32;
+    Response::new_err(id, code, error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_server::{Connection, Message, Notification, Request, RequestId};
+    use lsp_types::{
+        notification::{DidOpenTextDocument, Exit, Initialized, Notification as _},
+        request::{
+            Completion, GotoDefinition, HoverRequest, Initialize, Request as _, Shutdown,
+        },
+        CompletionResponse, DidOpenTextDocumentParams, GotoDefinitionResponse, Hover,
+        HoverContents, Position,
+        TextDocumentItem, Uri,
+    };
+    use serde_json::{json, Value};
+
+    fn request(client: &Connection, id: i32, method: &str, params: Value) -> Value {
+        let request = Request::new(RequestId::from(id), method.to_string(), params);
+        client.sender.send(request.into()).unwrap();
+        loop {
+            match client.receiver.recv().unwrap() {
+                Message::Response(response) => return response.result.unwrap_or(Value::Null),
+                _ => continue,
+            }
+        }
+    }
+
+    fn notify(client: &Connection, method: &str, params: impl serde::Serialize) {
+        let notification = Notification::new(method.to_string(), params);
+        client.sender.send(notification.into()).unwrap();
+    }
+
+    #[test]
+    fn hover_definition_and_completion() {
+        let (server, client) = Connection::memory();
+        let thread = std::thread::spawn(move || super::run(server).unwrap());
+        request(&client, 1, Initialize::METHOD, json!({ "capabilities": {} }));
+        notify(&client, Initialized::METHOD, json!({}));
+
+        let uri: Uri = "file:///test.cupid".parse().unwrap();
+        let text = "let greeting = 'hi'\nlog(greeting)\n";
+        notify(
+            &client,
+            DidOpenTextDocument::METHOD,
+            DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(uri, "cupid".into(), 0, text.into()),
+            },
+        );
+        let position = json!({
+            "textDocument": { "uri": "file:///test.cupid" },
+            "position": Position::new(1, 6),
+        });
+
+        let hover: Hover =
+            serde_json::from_value(request(&client, 2, HoverRequest::METHOD, position.clone()))
+                .unwrap();
+        match hover.contents {
+            HoverContents::Markup(content) => assert!(content.value.contains("greeting: String")),
+            contents => panic!("Unexpected hover contents: {:?}", contents),
+        }
+
+        let definition: GotoDefinitionResponse =
+            serde_json::from_value(request(&client, 3, GotoDefinition::METHOD, position)).unwrap();
+        match definition {
+            GotoDefinitionResponse::Scalar(location) => {
+                assert_eq!(location.range.start, Position::new(0, 4))
+            }
+            definition => panic!("Unexpected definition: {:?}", definition),
+        }
+
+        let position = json!({
+            "textDocument": { "uri": "file:///test.cupid" },
+            "position": Position::new(1, 5),
+        });
+        let completions: CompletionResponse =
+            serde_json::from_value(request(&client, 4, Completion::METHOD, position)).unwrap();
+        match completions {
+            CompletionResponse::Array(items) => {
+                let labels: Vec<&str> = items.iter().map(|item| item.label.as_str()).collect();
+                assert_eq!(labels, vec!["get", "greeting"]);
+            }
+            completions => panic!("Unexpected completions: {:?}", completions),
+        }
+
+        request(&client, 5, Shutdown::METHOD, Value::Null);
+        notify(&client, Exit::METHOD, Value::Null);
+        thread.join().unwrap();
+    }
+}
@@ -0,0 +1,44 @@
+// Loads the interpreter built for the browser with
+//   cargo build -p cupid-wasm --release --target wasm32-unknown-unknown
+// and wraps its exports, which pass strings through the module's memory. Both `compile` and `run`
+// resolve to `{ success, output, diagnostics, ast, bytecode }`, where `ast` and `bytecode` are
+// only set when asked for with `{ ast: true, bytecode: true }`. What a program logs is also passed to
+// the `onOutput` callback given to `init` as it is written, before `run` resolves.
+
+const AST = 1;
+const BYTECODE = 2;
+
+let exports;
+
+export default async function init(
+	input = new URL('../target/wasm32-unknown-unknown/release/cupid_wasm.wasm', import.meta.url),
+	{ onOutput = () => {} } = {},
+) {
+	const decoder = new TextDecoder();
+	const imports = {
+		env: {
+			// `clock()` has no clock inside the module, so it reads the page's in seconds
+			cupid_now: () => performance.now() / 1000,
+			cupid_write: (ptr, len) => {
+				const bytes = new Uint8Array(exports.memory.buffer, ptr, len);
+				onOutput(decoder.decode(bytes, { stream: true }));
+			},
+		},
+	};
+	const { instance } = await WebAssembly.instantiateStreaming(fetch(input), imports);
+	exports = instance.exports;
+}
+
+function call(name, source, { ast = false, bytecode = false } = {}) {
+	const bytes = new TextEncoder().encode(source);
+	const ptr = exports.cupid_alloc(bytes.length);
+	new Uint8Array(exports.memory.buffer, ptr, bytes.length).set(bytes);
+	const len = exports[name](ptr, bytes.length, (ast ? AST : 0) | (bytecode ? BYTECODE : 0));
+	exports.cupid_free(ptr, bytes.length);
+	const result = new Uint8Array(exports.memory.buffer, exports.cupid_result_ptr(), len);
+	return JSON.parse(new TextDecoder().decode(result));
+}
+
+export const compile = (source, dump) => call('cupid_compile', source, dump);
+
+export const run = (source, dump) => call('cupid_run', source, dump);
@@ -0,0 +1,64 @@
+//! The functions exported by the wasm module. JavaScript writes the source into memory it gets from
+//! `cupid_alloc`, calls `cupid_compile` or `cupid_run` with it, and reads the JSON of the [`Output`]
+//! from `cupid_result_ptr`, with the length they return. The JSON stays there until the next call.
+
+use std::{cell::RefCell, mem, slice};
+
+use crate::{compile, run, Dump, Output};
+
+thread_local! {
+    static RESULT: RefCell<String> = RefCell::default();
+}
+
+/// Reserves `len` bytes for the caller to write a source into.
+#[no_mangle]
+pub extern "C" fn cupid_alloc(len: usize) -> *mut u8 {
+    let mut buffer = Vec::<u8>::with_capacity(len);
+    let ptr = buffer.as_mut_ptr();
+    mem::forget(buffer);
+    ptr
+}
+
+/// # Safety
+///
+/// `ptr` must have been returned by `cupid_alloc` for the same `len`, and not freed since.
+#[no_mangle]
+pub unsafe extern "C" fn cupid_free(ptr: *mut u8, len: usize) {
+    drop(Vec::from_raw_parts(ptr, 0, len));
+}
+
+/// Compiles the source and returns the length of the result. `dump` holds the bits of
+/// [`Dump::AST`] and [`Dump::BYTECODE`].
+///
+/// # Safety
+///
+/// `ptr` must point to `len` initialized bytes.
+#[no_mangle]
+pub unsafe extern "C" fn cupid_compile(ptr: *const u8, len: usize, dump: u32) -> usize {
+    let source = String::from_utf8_lossy(slice::from_raw_parts(ptr, len));
+    store(compile(&source, Dump::from_bits(dump)))
+}
+
+/// Runs the source and returns the length of the result, like `cupid_compile`.
+///
+/// # Safety
+///
+/// `ptr` must point to `len` initialized bytes.
+#[no_mangle]
+pub unsafe extern "C" fn cupid_run(ptr: *const u8, len: usize, dump: u32) -> usize {
+    let source = String::from_utf8_lossy(slice::from_raw_parts(ptr, len));
+    store(run(&source, Dump::from_bits(dump)))
+}
+
+/// The JSON of the last result.
+#[no_mangle]
+pub extern "C" fn cupid_result_ptr() -> *const u8 {
+    RESULT.with(|result| result.borrow().as_ptr())
+}
+
+fn store(output: Output) -> usize {
+    RESULT.with(|result| {
+        *result.borrow_mut() = output.to_json();
+        result.borrow().len()
+    })
+}
@@ -0,0 +1,235 @@
+//! The interpreter built for the browser playground:
+//!
+//! ```text
+//! cargo build -p cupid-wasm --release --target wasm32-unknown-unknown
+//! ```
+//!
+//! [`compile`] checks a program and [`run`] runs it. Both return an [`Output`] holding everything
+//! the program would have printed, so nothing is written to stdout or stderr and the process is
+//! never exited. The module exports them through [`ffi`], and `cupid.js` loads it and wraps them
+//! for JavaScript. In the browser, what the program logs also reaches the page as it is written.
+
+pub mod ffi;
+
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+};
+
+use cupid::{
+    diagnostic::Diagnostic,
+    emit,
+    gc::{GcConfig, GcRef},
+    objects::Function,
+    parse::{bytecode::BytecodeCompiler, parser::Parser},
+    run::do_passes,
+    vm::{Buffer, Clock, Vm, VmConfig},
+};
+use serde::Serialize;
+
+/// The dumps to add to an [`Output`].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Dump {
+    pub ast: bool,
+    pub bytecode: bool,
+}
+
+impl Dump {
+    pub const AST: u32 = 1;
+    pub const BYTECODE: u32 = 2;
+
+    pub fn from_bits(bits: u32) -> Self {
+        Self {
+            ast: bits & Self::AST != 0,
+            bytecode: bits & Self::BYTECODE != 0,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Output {
+    /// Whether the program compiled and, if it was run, ended without a runtime error.
+    pub success: bool,
+    /// Everything the program logged, a line per `log`.
+    pub output: String,
+    /// The compile error or the runtime error, as printed with `--error-format=json`.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The typed AST, as printed with `--emit=ast-json`.
+    pub ast: Option<serde_json::Value>,
+    /// The instructions of the script, followed by those of every function in it.
+    pub bytecode: Option<String>,
+}
+
+impl Output {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Outputs always serialize")
+    }
+}
+
+/// Parses and checks `source` and compiles it to bytecode, without running it.
+pub fn compile(source: &str, dump: Dump) -> Output {
+    let mut vm = Vm::default();
+    let mut output = Output::default();
+    output.success = compile_in(&mut vm, source, dump, &mut output).is_some();
+    output
+}
+
+/// The instructions a program may run in the playground, which can't interrupt the module, so
+/// that an endless loop ends with an error rather than freezing the page.
+pub const FUEL: u64 = 100_000_000;
+
+/// The most memory the live objects of a program may take up in the playground.
+pub const MAX_HEAP: usize = 64 * 1024 * 1024;
+
+/// Compiles `source` and, if it compiles, runs it, capturing what it logs.
+pub fn run(source: &str, dump: Dump) -> Output {
+    let config = VmConfig {
+        gc: GcConfig {
+            max_heap: Some(MAX_HEAP),
+            ..GcConfig::default()
+        },
+        fuel: Some(FUEL),
+        ..VmConfig::default()
+    };
+    let mut vm = Vm::with_config(config);
+    vm.initialize();
+    vm.clock = clock();
+    let logged = Buffer::new();
+    vm.set_stdout(Streamed(logged.clone()));
+    vm.set_stdin(io::empty());
+    vm.diagnostics = Some(RefCell::default());
+    let mut output = Output::default();
+    if let Some(function) = compile_in(&mut vm, source, dump, &mut output) {
+        output.success = vm.interpret_function(function).is_ok();
+        output.output = logged.take();
+        output
+            .diagnostics
+            .extend(vm.diagnostics.take().unwrap_or_default().into_inner());
+    }
+    output
+}
+
+fn compile_in(
+    vm: &mut Vm,
+    source: &str,
+    dump: Dump,
+    output: &mut Output,
+) -> Option<GcRef<Function>> {
+    let mut parser = Parser::new(source);
+    let exprs = match parser.parse(&mut vm.gc) {
+        Ok(exprs) => do_passes(exprs, &mut parser.arena).map_err(|err| err.locate(&parser.arena)),
+        Err(error) => Err(error),
+    };
+    let exprs = match exprs {
+        Ok(exprs) => exprs,
+        Err(error) => {
+            output.diagnostics.push(Diagnostic::from_error(&error, None));
+            return None;
+        }
+    };
+    if dump.ast {
+        output.ast = Some(emit::exprs_json(&exprs, &parser.arena));
+    }
+    let function = match BytecodeCompiler::new(exprs, parser.arena, &mut vm.gc).compile() {
+        Ok(function) => function,
+        Err(error) => {
+            output.diagnostics.push(Diagnostic::from_error(&error, None));
+            return None;
+        }
+    };
+    if dump.bytecode {
+        output.bytecode = Some(function.disassemble());
+    }
+    Some(function)
+}
+
+/// What the program logs, collected for its [`Output`] and, in the browser, also handed to the
+/// page as it is written, through `env.cupid_write`.
+struct Streamed(Buffer);
+
+impl Write for Streamed {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        #[cfg(target_arch = "wasm32")]
+        {
+            extern "C" {
+                fn cupid_write(ptr: *const u8, len: usize);
+            }
+            unsafe { cupid_write(buf.as_ptr(), buf.len()) }
+        }
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The time read by `clock`, which the page provides as `env.cupid_now` since the module has no
+/// clock of its own.
+#[cfg(target_arch = "wasm32")]
+fn clock() -> Clock {
+    extern "C" {
+        fn cupid_now() -> f64;
+    }
+    Clock::Host(|| unsafe { cupid_now() })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn clock() -> Clock {
+    Clock::default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compile, run, Dump};
+
+    #[test]
+    fn captures_logs() {
+        let output = run("log('hello')\nlog(1 + 2)\n", Dump::default());
+        assert!(output.success);
+        assert_eq!(output.output, "'hello'\n3\n");
+        assert!(output.diagnostics.is_empty());
+        assert_eq!((output.ast, output.bytecode), (None, None));
+    }
+
+    #[test]
+    fn captures_runtime_errors() {
+        let output = run("log('before')\nfun f () { f() }\nf()\n", Dump::default());
+        assert!(!output.success);
+        assert_eq!(output.output, "'before'\n");
+        let error = &output.diagnostics[0];
+        assert_eq!(error.code, "E0302");
+        assert_eq!(error.message, "Stack overflow.");
+        assert_eq!(error.spans[0].label.as_deref(), Some("in f() (repeated 1023 times)"));
+    }
+
+    #[test]
+    fn reports_compile_errors_without_running() {
+        let output = run("log('never')\nlog(nope)\n", Dump::default());
+        assert!(!output.success);
+        assert_eq!(output.output, "");
+        assert_eq!(output.diagnostics[0].code, "E0102");
+    }
+
+    #[test]
+    fn reports_errors_found_while_compiling() {
+        let output = run("log('never')\nreturn\n", Dump::default());
+        assert!(!output.success);
+        assert_eq!(output.output, "");
+        assert_eq!(output.diagnostics[0].code, "E0003");
+        assert_eq!(output.diagnostics[0].message, "Can't return from top-level code.");
+        assert_eq!(output.diagnostics[0].spans[0].line_start, 2);
+    }
+
+    #[test]
+    fn dumps_the_ast_and_bytecode() {
+        let output =
+            compile("fun f () { log(1) }\nf()\n", Dump::from_bits(Dump::AST | Dump::BYTECODE));
+        assert!(output.success);
+        assert_eq!(output.ast.unwrap()[0]["Fun"]["name"], "f");
+        let bytecode = output.bytecode.unwrap();
+        assert!(bytecode.starts_with("== <script> ==\n"));
+        assert!(bytecode.contains("== <fun f> ==\n"));
+        assert!(bytecode.contains("Log"));
+    }
+}
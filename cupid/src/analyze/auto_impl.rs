@@ -32,7 +32,10 @@ macro_rules! auto_impl {
 
         impl<'src> $name<'src> for Expr<'src> {
             fn $fn_name(self $(, $param: $param_ty)*) -> Result<Self, CupidError> {
-                for_expr_variant!(self => |inner| Ok(inner.$fn_name($($param),*)?.into()))
+                let source = self.header().source;
+                let result: Result<Self, CupidError> =
+                    for_expr_variant!(self => |inner| inner.$fn_name($($param),*).map(Into::into));
+                result.map_err(|err| err.at_source(source))
             }
         }
     };
@@ -89,6 +92,9 @@ macro_rules! pass {
     ( Method::$fn_name:ident($self:ident, $ctx:expr) ) => {
         $self.fun = $self.fun.$fn_name($ctx)?;
     };
+    ( Propagate::$fn_name:ident($self:ident, $ctx:expr) ) => {
+        $self.value = $self.value.$fn_name($ctx)?;
+    };
     ( Return::$fn_name:ident($self:ident, $ctx:expr) ) => {
         $self.value = $self.value.$fn_name($ctx)?;
     };
@@ -99,6 +105,15 @@ macro_rules! pass {
         $self.receiver = $self.receiver.$fn_name($ctx)?;
         $self.value = $self.value.$fn_name($ctx)?;
     };
+    ( Throw::$fn_name:ident($self:ident, $ctx:expr) ) => {
+        $self.value = $self.value.$fn_name($ctx)?;
+    };
+    ( Try::$fn_name:ident($self:ident, $ctx:expr) ) => {
+        $self.body = $self.body.$fn_name($ctx)?;
+        $self.catch_param = $self.catch_param.$fn_name($ctx)?;
+        $self.catch_body = $self.catch_body.$fn_name($ctx)?;
+        $self.finally_body = $self.finally_body.$fn_name($ctx)?;
+    };
     ( UnOp::$fn_name:ident($self:ident, $ctx:expr) ) => {
         $self.expr = $self.expr.$fn_name($ctx)?;
     };
@@ -244,6 +259,14 @@ macro_rules! base_pass {
             }
         }
     };
+    ( impl $name:ident::$fn_name:ident($param:ident: $param_ty:ty) for Propagate) => {
+        impl<'src> $name<'src> for Propagate<'src> {
+            fn $fn_name(mut self , $param: $param_ty) -> Result<Self, CupidError> {
+                pass!(Propagate::$fn_name(self, $param));
+                Ok(self)
+            }
+        }
+    };
     ( impl $name:ident::$fn_name:ident($param:ident: $param_ty:ty) for Return) => {
         impl<'src> $name<'src> for Return<'src> {
             fn $fn_name(mut self , $param: $param_ty) -> Result<Self, CupidError> {
@@ -268,6 +291,22 @@ macro_rules! base_pass {
             }
         }
     };
+    ( impl $name:ident::$fn_name:ident($param:ident: $param_ty:ty) for Throw) => {
+        impl<'src> $name<'src> for Throw<'src> {
+            fn $fn_name(mut self , $param: $param_ty) -> Result<Self, CupidError> {
+                pass!(Throw::$fn_name(self, $param));
+                Ok(self)
+            }
+        }
+    };
+    ( impl $name:ident::$fn_name:ident($param:ident: $param_ty:ty) for Try) => {
+        impl<'src> $name<'src> for Try<'src> {
+            fn $fn_name(mut self , $param: $param_ty) -> Result<Self, CupidError> {
+                pass!(Try::$fn_name(self, $param));
+                Ok(self)
+            }
+        }
+    };
     ( impl $name:ident::$fn_name:ident($param:ident: $param_ty:ty) for UnOp) => {
         impl<'src> $name<'src> for UnOp<'src> {
             fn $fn_name(mut self , $param: $param_ty) -> Result<Self, CupidError> {
@@ -0,0 +1,126 @@
+use crate::{
+    analyze::index::{index_source, SymbolKind},
+    error::CupidError,
+    scanner::Scanner,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CompletionKind {
+    Symbol(SymbolKind),
+    Keyword,
+}
+
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    /// The type of the symbol, or `None` for keywords.
+    pub detail: Option<String>,
+}
+
+/// Lists what can be written at the given byte offset of `code`: after a `.`, the fields and
+/// methods of the receiver, and otherwise the names in scope and the keywords. Only the ones
+/// starting with the part of the name already typed are returned, sorted by name.
+pub fn complete(code: &str, offset: usize) -> Result<Vec<Completion>, CupidError> {
+    let mut offset = offset.min(code.len());
+    while !code.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let is_name = |c: char| c.is_alphanumeric() || c == '_';
+    let start = code[..offset].trim_end_matches(is_name).len();
+    let end = code.len() - code[offset..].trim_start_matches(is_name).len();
+    let prefix = &code[start..offset];
+    let before = code[..start].trim_end();
+    let receiver_end = before.strip_suffix('.').map(|receiver| receiver.trim_end().len());
+
+    // Take out the unfinished name so that the rest of the code checks: after a `.` the receiver is
+    // left on its own, and otherwise a `none` takes the place of the name.
+    let mut code = code.to_string();
+    match receiver_end {
+        Some(_) => code.replace_range(before.len() - 1..end, &" ".repeat(end - before.len() + 1)),
+        None => code.replace_range(start..end, "none"),
+    }
+    let index = index_source(&code)?;
+
+    let symbols = match receiver_end {
+        Some(end) => match index.instance_ending_at(end) {
+            Some(class) => index.members(class),
+            None => vec![],
+        },
+        None => index.visible_at(start),
+    };
+    let mut completions: Vec<Completion> = symbols
+        .into_iter()
+        .map(|id| index.symbol(id))
+        .map(|symbol| Completion {
+            label: symbol.name.clone(),
+            kind: CompletionKind::Symbol(symbol.kind),
+            detail: Some(symbol.ty.clone()),
+        })
+        .collect();
+    if receiver_end.is_none() {
+        let scanner = Scanner::new("");
+        let keywords: Vec<Completion> = scanner
+            .keywords()
+            .filter(|keyword| !completions.iter().any(|c| c.label == *keyword))
+            .map(|keyword| Completion {
+                label: keyword.to_string(),
+                kind: CompletionKind::Keyword,
+                detail: None,
+            })
+            .collect();
+        completions.extend(keywords);
+    }
+    completions.retain(|completion| completion.label.starts_with(prefix));
+    completions.sort_by(|a, b| a.label.cmp(&b.label));
+    Ok(completions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{complete, CompletionKind};
+    use crate::analyze::index::SymbolKind;
+
+    fn labels(code: &str) -> Vec<String> {
+        let offset = code.find('|').unwrap();
+        let code = code.replace('|', "");
+        complete(&code, offset).unwrap().into_iter().map(|c| c.label).collect()
+    }
+
+    #[test]
+    fn names_in_scope() {
+        let code = "let total = 1\nfun add (amount) {\n    let next = t|\n}\nlet tail = 2\n";
+        assert_eq!(labels(code), vec!["throw", "total", "trait", "true", "try"]);
+
+        let code = "let total = 1\nfun add (amount) {\n    log(a|)\n}\n";
+        assert_eq!(
+            labels(code),
+            vec!["add", "amount", "and", "assert", "assert_eq", "assert_err"]
+        );
+    }
+
+    #[test]
+    fn members_after_a_dot() {
+        let code = "class Animal {\n    let name = 'animal'\n    speak () { return self.name }\n}\n\
+            class Dog < Animal {\n    speak () { return 'woof' }\n    fetch () {}\n}\n\
+            let dog = Dog()\ndog.|\n";
+        assert_eq!(labels(code), vec!["fetch", "name", "speak"]);
+
+        let code = "class Dog {\n    fetch () {}\n    feed () {}\n    sit () {}\n}\n\
+            let dog = Dog()\nlog(dog . f|)\n";
+        assert_eq!(labels(code), vec!["feed", "fetch"]);
+    }
+
+    #[test]
+    fn kinds_and_types() {
+        let code = "fun double (x) => x * 2\nl|";
+        let completions = complete(&code.replace('|', ""), code.find('|').unwrap()).unwrap();
+        let len = completions.iter().find(|c| c.label == "len").unwrap();
+        assert_eq!(len.kind, CompletionKind::Symbol(SymbolKind::Native));
+        assert_eq!(len.detail.as_deref(), Some("Function<Int>"));
+        let log = completions.iter().find(|c| c.label == "log").unwrap();
+        assert_eq!(log.kind, CompletionKind::Symbol(SymbolKind::Native));
+        let let_kw = completions.iter().find(|c| c.label == "let").unwrap();
+        assert_eq!(let_kw.kind, CompletionKind::Keyword);
+    }
+}
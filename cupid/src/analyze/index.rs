@@ -0,0 +1,718 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use crate::{
+    analyze::pretty::PrettyPrint,
+    arena::{EntryId, ExprArena, UseArena},
+    ast::{self, Expr, GetSource, GetTy, Header},
+    cst::{
+        block::BlockSource,
+        class::ClassSource,
+        define::DefineSource,
+        expr::{ExprSource, UnwrapEnum},
+        fun::FunSource,
+        get::GetSource as GetSourceToken,
+        get_property::GetPropertySource,
+        invoke::InvokeSource,
+        set::SetSource,
+        set_property::SetPropertySource,
+        r#try::TrySource,
+        SourceId,
+    },
+    error::CupidError,
+    for_expr_variant,
+    gc::Gc,
+    parse::parser::Parser,
+    pointer::Pointer,
+    run::do_passes,
+    scope::{symbol::Symbol, Scope},
+    span::Span,
+    token::Token,
+    ty::Type,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SymbolId(pub usize);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SymbolKind {
+    Class,
+    Method,
+    Function,
+    Field,
+    Parameter,
+    Variable,
+    Native,
+}
+
+#[derive(Debug, Clone)]
+pub struct SymbolInfo {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub ty: String,
+    /// Where the symbol is defined, or `None` for natives.
+    pub span: Option<Span>,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Reference {
+    pub span: Span,
+    pub symbol: SymbolId,
+}
+
+/// A class, method or function, with the definitions nested inside of it.
+#[derive(Debug, Clone)]
+pub struct Outline {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub span: Span,
+    pub name_span: Span,
+    pub children: Vec<Outline>,
+}
+
+/// A scope of the program, and the symbols defined directly inside of it.
+#[derive(Debug, Clone)]
+pub struct ScopeInfo {
+    /// The source the scope covers, or `None` for the global scope.
+    pub span: Option<Span>,
+    pub parent: Option<usize>,
+    pub symbols: Vec<SymbolId>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClassInfo {
+    pub name: String,
+    pub super_class: Option<String>,
+    /// The scope holding the fields and methods of the class.
+    pub scope: usize,
+}
+
+/// Everything editor tooling needs to know about a checked program, detached from its AST so that
+/// it can outlive the source it was built from.
+#[derive(Debug, Default)]
+pub struct SymbolIndex {
+    pub symbols: Vec<SymbolInfo>,
+    pub references: Vec<Reference>,
+    pub types: Vec<(Span, String)>,
+    pub outline: Vec<Outline>,
+    pub scopes: Vec<ScopeInfo>,
+    pub classes: Vec<ClassInfo>,
+    /// The end offsets of expressions typed as instances of a class, with the name of the class.
+    pub instances: Vec<(usize, String)>,
+}
+
+impl SymbolIndex {
+    pub fn new<'src>(exprs: &[Expr<'src>], arena: &ExprArena<'src>) -> Self {
+        let mut indexer = Indexer {
+            arena,
+            ids: HashMap::new(),
+            scope_ids: HashMap::new(),
+            index: SymbolIndex::default(),
+            outline: vec![vec![]],
+        };
+        // The global scope goes first, so that it can be found at index 0.
+        if let Some(expr) = exprs.first() {
+            indexer.scope(&expr.header().scope, None);
+        }
+        for expr in exprs {
+            expr.index(&mut indexer);
+        }
+        let mut index = indexer.index;
+        index.outline = indexer.outline.pop().unwrap_or_default();
+        index
+    }
+
+    pub fn symbol(&self, id: SymbolId) -> &SymbolInfo {
+        &self.symbols[id.0]
+    }
+
+    /// Finds the symbol defined or referenced by the token at the given byte offset.
+    pub fn symbol_at(&self, offset: usize) -> Option<SymbolId> {
+        let definition = self.symbols.iter().position(|symbol| match symbol.span {
+            Some(span) => span.contains(offset),
+            None => false,
+        });
+        match definition {
+            Some(id) => Some(SymbolId(id)),
+            None => self
+                .references
+                .iter()
+                .find(|reference| reference.span.contains(offset))
+                .map(|reference| reference.symbol),
+        }
+    }
+
+    pub fn references_to(&self, id: SymbolId) -> impl Iterator<Item = Span> + '_ {
+        self.references.iter().filter(move |r| r.symbol == id).map(|r| r.span)
+    }
+
+    /// The inferred type of the expression at the given byte offset.
+    pub fn type_at(&self, offset: usize) -> Option<(Span, &str)> {
+        self.types
+            .iter()
+            .find(|(span, _)| span.contains(offset))
+            .map(|(span, ty)| (*span, ty.as_str()))
+    }
+
+    /// The symbols that can be named at the given byte offset, leaving out shadowed ones and
+    /// variables that are only defined further on.
+    pub fn visible_at(&self, offset: usize) -> Vec<SymbolId> {
+        let innermost = self
+            .scopes
+            .iter()
+            .enumerate()
+            .filter_map(|(id, scope)| scope.span.map(|span| (id, span)))
+            .filter(|(_, span)| span.contains(offset))
+            .max_by_key(|(_, span)| span.start.index)
+            .map(|(id, _)| id);
+        let scopes = innermost.or(if self.scopes.is_empty() { None } else { Some(0) });
+        let is_visible = |id: &SymbolId| {
+            let symbol = self.symbol(*id);
+            match (symbol.kind, symbol.span) {
+                (SymbolKind::Variable, Some(span)) => span.start.index < offset,
+                _ => true,
+            }
+        };
+        self.collect(std::iter::successors(scopes, |id| self.scopes[*id].parent), is_visible)
+    }
+
+    /// The fields and methods of `class`, including the ones it inherits and does not override.
+    pub fn members(&self, class: &str) -> Vec<SymbolId> {
+        let mut classes = vec![];
+        let mut next = Some(class);
+        while let Some(name) = next {
+            match self.classes.iter().find(|class| class.name == name) {
+                // A class cannot inherit from itself, but guard against it rather than loop.
+                Some(class) if !classes.iter().any(|c: &&ClassInfo| c.name == name) => {
+                    classes.push(class);
+                    next = class.super_class.as_deref();
+                }
+                _ => break,
+            }
+        }
+        self.collect(classes.iter().map(|class| class.scope), |_| true)
+    }
+
+    /// The class of the instance expression that ends at the given byte offset, if any.
+    pub fn instance_ending_at(&self, end: usize) -> Option<&str> {
+        self.instances
+            .iter()
+            .rev()
+            .find(|(instance_end, _)| *instance_end == end)
+            .map(|(_, class)| class.as_str())
+    }
+
+    /// The symbols of `scopes`, of which the first ones shadow the others.
+    fn collect(
+        &self,
+        scopes: impl Iterator<Item = usize>,
+        filter: impl Fn(&SymbolId) -> bool,
+    ) -> Vec<SymbolId> {
+        let mut names = HashSet::new();
+        scopes
+            .flat_map(|scope| self.scopes[scope].symbols.iter().copied())
+            .filter(filter)
+            .filter(|id| names.insert(self.symbol(*id).name.as_str()))
+            .collect()
+    }
+}
+
+/// Parses and checks `code`, returning the index of its symbols or the first error found.
+pub fn index_source(code: &str) -> Result<SymbolIndex, CupidError> {
+    let mut gc = Gc::default();
+    let mut parser = Parser::new(code);
+    let exprs = parser.parse(&mut gc)?;
+    let exprs = do_passes(exprs, &mut parser.arena).map_err(|err| err.locate(&parser.arena))?;
+    Ok(SymbolIndex::new(&exprs, &parser.arena))
+}
+
+pub struct Indexer<'a, 'src> {
+    arena: &'a ExprArena<'src>,
+    ids: HashMap<*const RefCell<Symbol<'src>>, SymbolId>,
+    scope_ids: HashMap<*const RefCell<Scope<'src>>, usize>,
+    index: SymbolIndex,
+    outline: Vec<Vec<Outline>>,
+}
+
+impl<'a, 'src> Indexer<'a, 'src> {
+    fn id(&mut self, symbol: &Pointer<Symbol<'src>>, name: &str) -> SymbolId {
+        let key = Rc::as_ptr(&symbol.0);
+        if let Some(id) = self.ids.get(&key) {
+            return *id;
+        }
+        let id = SymbolId(self.index.symbols.len());
+        self.index.symbols.push(SymbolInfo {
+            name: name.to_string(),
+            kind: SymbolKind::Native,
+            ty: symbol.borrow().ty.pretty_print(self.arena),
+            span: None,
+        });
+        self.ids.insert(key, id);
+        id
+    }
+
+    /// Registers `scope` along with its parents, and the source it covers if that is known.
+    fn scope(&mut self, scope: &Pointer<Scope<'src>>, span: Option<Span>) -> usize {
+        let key = Rc::as_ptr(&scope.0);
+        let id = match self.scope_ids.get(&key) {
+            Some(id) => *id,
+            None => {
+                let parent = scope.parent().map(|parent| self.scope(&parent, None));
+                // `self` and `super` are keywords rather than names to look up.
+                let symbols: Vec<(&'src str, Pointer<Symbol<'src>>)> = scope
+                    .borrow()
+                    .symbols
+                    .iter()
+                    .filter(|(name, _)| !matches!(**name, "self" | "super"))
+                    .map(|(name, symbol)| (*name, symbol.clone()))
+                    .collect();
+                let symbols = symbols.iter().map(|(name, symbol)| self.id(symbol, name)).collect();
+                let id = self.index.scopes.len();
+                self.index.scopes.push(ScopeInfo {
+                    span: None,
+                    parent,
+                    symbols,
+                });
+                self.scope_ids.insert(key, id);
+                id
+            }
+        };
+        if let Some(span) = span.filter(|span| !span.is_synthetic()) {
+            self.index.scopes[id].span = Some(span);
+        }
+        id
+    }
+
+    fn define(
+        &mut self,
+        symbol: Option<&Pointer<Symbol<'src>>>,
+        name: Token<'src>,
+        kind: SymbolKind,
+    ) {
+        let symbol = match symbol {
+            Some(symbol) => symbol,
+            None => return,
+        };
+        let id = self.id(symbol, name.lexeme);
+        let info = &mut self.index.symbols[id.0];
+        info.kind = kind;
+        if !name.span.is_synthetic() {
+            info.span = Some(name.span);
+            self.index.types.push((name.span, info.ty.clone()));
+        }
+    }
+
+    fn reference(&mut self, symbol: Option<&Pointer<Symbol<'src>>>, name: Token<'src>) {
+        if let Some(symbol) = symbol {
+            let symbol = self.id(symbol, name.lexeme);
+            if !name.span.is_synthetic() {
+                self.index.references.push(Reference {
+                    span: name.span,
+                    symbol,
+                });
+            }
+        }
+    }
+
+    fn ty(&mut self, source: SourceId, ty: &Type<'src>) {
+        if let Some(span) = self.source(source).span() {
+            let ty = ty.pretty_print(self.arena);
+            self.index.types.push((span, ty));
+        }
+        if let Type::Instance(class) = ty {
+            let end = self.source(source).last_token(self.arena).span;
+            if !end.is_synthetic() {
+                self.index.instances.push((end.end.index, class.0.to_string()));
+            }
+        }
+    }
+
+    fn source(&self, source: SourceId) -> &'a ExprSource<'src> {
+        self.arena.expect_source(source)
+    }
+
+    fn begin_outline(&mut self) {
+        self.outline.push(vec![]);
+    }
+
+    fn end_outline(&mut self, name: Token<'src>, kind: SymbolKind, span: Span) {
+        let children = self.outline.pop().unwrap_or_default();
+        if name.span.is_synthetic() {
+            return;
+        }
+        if let Some(parent) = self.outline.last_mut() {
+            parent.push(Outline {
+                name: name.lexeme.to_string(),
+                kind,
+                span,
+                name_span: name.span,
+                children,
+            })
+        }
+    }
+
+    /// Registers the scope of a function's parameters, returning the span from the start of the
+    /// function to the end of its body.
+    fn fun_scope(&mut self, fun: &ast::Fun<'src>, start: Token<'src>) -> Span {
+        let source: &FunSource = fun.source(self.arena).unwrapped();
+        let span = start.span.to(self.source(source.body_src).last_token(self.arena).span);
+        // The body is parsed inside of the function's scope, so its header holds that scope.
+        let scope = self.arena.expect_expr(fun.body).header().scope.clone();
+        self.scope(&scope, Some(span));
+        span
+    }
+}
+
+pub trait IndexSymbols<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>);
+}
+
+impl<'src, T: IndexSymbols<'src>> IndexSymbols<'src> for Vec<T> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        self.iter().for_each(|item| item.index(indexer))
+    }
+}
+
+impl<'src, T: IndexSymbols<'src>> IndexSymbols<'src> for Option<T> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        if let Some(item) = self {
+            item.index(indexer)
+        }
+    }
+}
+
+impl<'src> IndexSymbols<'src> for EntryId {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        let expr: &Expr<'src> = indexer.arena.expect(*self);
+        expr.index(indexer)
+    }
+}
+
+impl<'src> IndexSymbols<'src> for Expr<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        for_expr_variant!(self => |inner| inner.index(indexer))
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::Array<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        indexer.ty(self.header.source, &self.ty());
+        self.items.index(indexer);
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::BinOp<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        indexer.ty(self.header.source, &self.ty());
+        self.left.index(indexer);
+        self.right.index(indexer);
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::Block<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        if let Some(first) = self.body.first() {
+            let span = match self.source(indexer.arena) {
+                ExprSource::Block(BlockSource::BraceBlock(block)) => {
+                    block.open_brace.span.to(block.close_brace.span)
+                }
+                source => source.token().span.to(source.last_token(indexer.arena).span),
+            };
+            let scope = indexer.arena.expect_expr(*first).header().scope.clone();
+            indexer.scope(&scope, Some(span));
+        }
+        self.body.index(indexer);
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::Break<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        self.value.index(indexer);
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::Call<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        indexer.ty(self.header.source, &self.ty());
+        self.callee.index(indexer);
+        self.args.index(indexer);
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::Class<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        let source: &ClassSource = self.source(indexer.arena).unwrapped();
+        indexer.define(self.symbol.as_ref(), source.name, SymbolKind::Class);
+        let span = source.class_kw.span.to(source.close_brace.span);
+        let scope = indexer.scope(&self.class_scope, Some(span));
+        indexer.index.classes.push(ClassInfo {
+            name: self.name.to_string(),
+            super_class: self.super_class.map(str::to_string),
+            scope,
+        });
+        indexer.begin_outline();
+        for field in &self.fields {
+            let field_source: &DefineSource = field.source(indexer.arena).unwrapped();
+            indexer.define(field.symbol.as_ref(), field_source.name, SymbolKind::Field);
+            field.value.index(indexer);
+        }
+        self.methods.index(indexer);
+        indexer.end_outline(source.name, SymbolKind::Class, span);
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::Constant<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        indexer.ty(self.header.source, &self.ty());
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::Define<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        let source: &DefineSource = self.source(indexer.arena).unwrapped();
+        indexer.define(self.symbol.as_ref(), source.name, SymbolKind::Variable);
+        self.value.index(indexer);
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::Fun<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        let source: &FunSource = self.source(indexer.arena).unwrapped();
+        let start = source.name.unwrap_or(source.fun_kw);
+        if let Some(name) = source.name {
+            indexer.define(self.symbol.as_ref(), name, SymbolKind::Function);
+        }
+        let span = indexer.fun_scope(self, start);
+        indexer.begin_outline();
+        for param in &self.params {
+            let param_source: &DefineSource = param.source(indexer.arena).unwrapped();
+            indexer.define(param.symbol.as_ref(), param_source.name, SymbolKind::Parameter);
+        }
+        self.body.index(indexer);
+        match source.name {
+            Some(name) => indexer.end_outline(name, SymbolKind::Function, span),
+            // Anonymous functions have no entry of their own, so their definitions belong to
+            // the enclosing one.
+            None => {
+                let mut children = indexer.outline.pop().unwrap_or_default();
+                if let Some(parent) = indexer.outline.last_mut() {
+                    parent.append(&mut children);
+                }
+            }
+        }
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::Get<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        let source: &GetSourceToken = self.source(indexer.arena).unwrapped();
+        indexer.reference(self.symbol.as_ref(), source.name);
+        indexer.ty(self.header.source, &self.ty());
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::GetProperty<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        let source: &GetPropertySource = self.source(indexer.arena).unwrapped();
+        self.receiver.index(indexer);
+        indexer.reference(self.symbol.as_ref(), source.property);
+        indexer.ty(self.header.source, &self.ty());
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::GetSuper<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        indexer.ty(self.header.source, &self.ty());
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::If<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        self.condition.index(indexer);
+        self.body.index(indexer);
+        self.else_body.index(indexer);
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::Invoke<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        let source: &InvokeSource = self.source(indexer.arena).unwrapped();
+        self.receiver.index(indexer);
+        indexer.reference(self.symbol.as_ref(), source.callee);
+        indexer.ty(self.header.source, &self.ty());
+        self.args.index(indexer);
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::InvokeSuper<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        self.args.index(indexer);
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::Loop<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        self.body.index(indexer);
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::Method<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        let source: &FunSource = self.fun.source(indexer.arena).unwrapped();
+        let name = match source.name {
+            Some(name) => name,
+            None => return,
+        };
+        indexer.define(self.symbol.as_ref(), name, SymbolKind::Method);
+        let span = indexer.fun_scope(&self.fun, name);
+        indexer.begin_outline();
+        for param in &self.fun.params {
+            let param_source: &DefineSource = param.source(indexer.arena).unwrapped();
+            indexer.define(param.symbol.as_ref(), param_source.name, SymbolKind::Parameter);
+        }
+        self.fun.body.index(indexer);
+        indexer.end_outline(name, SymbolKind::Method, span);
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::Propagate<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        self.value.index(indexer);
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::Return<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        self.value.index(indexer);
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::Set<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        let source: &SetSource = self.source(indexer.arena).unwrapped();
+        indexer.reference(self.symbol.as_ref(), source.name);
+        self.value.index(indexer);
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::SetProperty<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        let source: &SetPropertySource = self.source(indexer.arena).unwrapped();
+        self.receiver.index(indexer);
+        indexer.reference(self.symbol.as_ref(), source.property);
+        self.value.index(indexer);
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::Throw<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        self.value.index(indexer);
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::Try<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        let source: &TrySource = self.source(indexer.arena).unwrapped();
+        self.body.index(indexer);
+        // The error is in scope from its name to the end of the catch body
+        let end = indexer.arena.expect_source(source.catch_body_src).last_token(indexer.arena);
+        let span = source.open_paren.span.to(end.span);
+        indexer.scope(&self.catch_param.header.scope, Some(span));
+        let param_source: &DefineSource = self.catch_param.source(indexer.arena).unwrapped();
+        indexer.define(self.catch_param.symbol.as_ref(), param_source.name, SymbolKind::Variable);
+        self.catch_body.index(indexer);
+        self.finally_body.index(indexer);
+    }
+}
+
+impl<'src> IndexSymbols<'src> for ast::UnOp<'src> {
+    fn index(&self, indexer: &mut Indexer<'_, 'src>) {
+        indexer.ty(self.header.source, &self.ty());
+        self.expr.index(indexer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{index_source, SymbolKind};
+
+    fn offset(code: &str, needle: &str, nth: usize) -> usize {
+        code.match_indices(needle).nth(nth).unwrap().0
+    }
+
+    #[test]
+    fn definition_and_references() {
+        let code = "let count = 1\ncount = count + 1\nlog(count)\n";
+        let index = index_source(code).unwrap();
+        let id = index.symbol_at(offset(code, "count", 3)).unwrap();
+        let symbol = index.symbol(id);
+        assert_eq!(symbol.name, "count");
+        assert_eq!(symbol.kind, SymbolKind::Variable);
+        assert_eq!(symbol.span.unwrap().start.index, 4);
+        assert_eq!(index.references_to(id).count(), 3);
+        assert_eq!(index.symbol_at(offset(code, "count", 0)), Some(id));
+    }
+
+    #[test]
+    fn natives_have_no_definition() {
+        let code = "log(1)";
+        let index = index_source(code).unwrap();
+        let id = index.symbol_at(1).unwrap();
+        assert_eq!(index.symbol(id).kind, SymbolKind::Native);
+        assert!(index.symbol(id).span.is_none());
+    }
+
+    #[test]
+    fn methods_resolve_through_instances() {
+        let code = "class Foo {\n    bar (a) { return a }\n}\nlet foo = Foo()\nfoo.bar(1)\n";
+        let index = index_source(code).unwrap();
+        let id = index.symbol_at(offset(code, "bar", 1)).unwrap();
+        assert_eq!(index.symbol(id).kind, SymbolKind::Method);
+        assert_eq!(index.symbol(id).span.unwrap().start.index, offset(code, "bar", 0));
+        assert_eq!(index.type_at(offset(code, "foo", 1)).unwrap().1, "Instance('Foo')");
+    }
+
+    #[test]
+    fn outline() {
+        let code =
+            "class Foo {\n    bar () {}\n    baz () {}\n}\nfun qux () {\n    fun quux () {}\n}\n";
+        let index = index_source(code).unwrap();
+        let names: Vec<(&str, SymbolKind, usize)> = index
+            .outline
+            .iter()
+            .map(|item| (item.name.as_str(), item.kind, item.children.len()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("Foo", SymbolKind::Class, 2),
+                ("qux", SymbolKind::Function, 1)
+            ]
+        );
+        assert_eq!(index.outline[0].children[1].name, "baz");
+        assert_eq!(index.outline[0].span.end.index, offset(code, "}\nfun", 0) + 1);
+    }
+
+    #[test]
+    fn errors_are_located() {
+        let code = "let a = 1\nlog(b)\n";
+        let error = index_source(code).unwrap_err();
+        assert_eq!(error.span.unwrap().start.index, offset(code, "b", 0));
+    }
+
+    #[test]
+    fn undefined_names_suggest_close_ones() {
+        let code = "let counter = 1\nfun f (amount) {\n    log(amont + countr)\n}\n";
+        let error = index_source(code).unwrap_err();
+        assert_eq!(error.help.as_deref(), Some("did you mean `amount`?"));
+
+        let error = index_source("retrun 1\n").unwrap_err();
+        assert_eq!(error.help.as_deref(), Some("did you mean `return`?"));
+
+        let error = index_source("log(xyz)\n").unwrap_err();
+        assert_eq!(error.help, None);
+    }
+}
@@ -2,12 +2,15 @@ use crate::{
     arena::{EntryId, ExprArena, UseArena},
     ast::{
         Array, BinOp, Block, Break, Call, Class, Constant, Define, Expr, Fun, Get, GetProperty,
-        GetSuper, GetTy, HasSymbol, Header, If, Invoke, InvokeSuper, Loop, Method, Return, Set,
-        SetProperty, UnOp,
+        GetSuper, GetTy, HasSymbol, Header, If, Invoke, InvokeSuper, Loop, Method, Propagate,
+        Return, Set, SetProperty, Throw, Try, UnOp,
     },
     auto_impl, base_pass,
+    code::Code,
+    compiler::FunctionType,
     error::CupidError,
     for_expr_variant, pass,
+    scope::ScopeContext,
     ty::Type,
     value::Value,
 };
@@ -24,6 +27,7 @@ auto_impl! {
 base_pass! {
     impl Infer::infer(arena: &mut ExprArena<'src>) for {
         Block,
+        Throw,
     }
 }
 
@@ -45,6 +49,16 @@ fn unwrapped_entry_ty<'src>(id: Option<EntryId>, arena: &mut ExprArena<'src>) ->
     }
 }
 
+/// Whether `?` can return `propagated` early from a function that also returns `returned`.
+fn propagates_to(propagated: Type, returned: Type) -> bool {
+    matches!(
+        (propagated, returned),
+        (_, Type::Unknown)
+            | (Type::Option(_), Type::Option(_) | Type::Nil)
+            | (Type::Result { .. }, Type::Result { .. })
+    )
+}
+
 impl<'src> Infer<'src> for EntryId {
     fn infer(self, arena: &mut ExprArena<'src>) -> Result<Self, CupidError> {
         let expr: Expr<'src> = arena.take(self);
@@ -92,7 +106,29 @@ impl<'src> Infer<'src> for Call<'src> {
             Type::Function { returns } => self.set_ty(*arena.expect_ty(returns)),
             Type::Class(class) => self.set_ty(Type::Instance(class)),
             Type::Unknown => (),
-            _ => return Err(CupidError::type_error("Not a function", "")),
+            _ => return Err(CupidError::type_error(Code::NotCallable, "Not a function", "")),
+        }
+
+        // The builtin constructors wrap the type of their argument
+        let name = match UseArena::<Expr>::expect(arena, self.callee) {
+            Expr::Get(get) => get.name,
+            _ => return Ok(self),
+        };
+        let wrapped = match self.args[..] {
+            [arg] => entry_ty(arg, arena),
+            _ => return Ok(self),
+        };
+        let wrapped = arena.insert(wrapped);
+        let unknown = |id| *arena.expect_ty(id) == Type::Unknown;
+        match (name, self.ty()) {
+            ("some", Type::Option(some)) if unknown(some) => self.set_ty(Type::Option(wrapped)),
+            ("ok", Type::Result { ok, err }) if unknown(ok) && unknown(err) => {
+                self.set_ty(Type::Result { ok: wrapped, err })
+            }
+            ("err", Type::Result { ok, err }) if unknown(ok) && unknown(err) => {
+                self.set_ty(Type::Result { ok, err: wrapped })
+            }
+            _ => (),
         }
         Ok(self)
     }
@@ -142,7 +178,44 @@ impl<'src> Infer<'src> for Define<'src> {
 impl<'src> Infer<'src> for Fun<'src> {
     fn infer(mut self, arena: &mut ExprArena<'src>) -> Result<Self, CupidError> {
         pass!(Fun::infer(self, arena));
-        let body_ty = entry_ty(self.body, arena);
+
+        let scope = UseArena::<Expr>::expect(arena, self.body).header().scope.clone();
+        let (returns, propagates) = {
+            let mut scope = scope.borrow_mut();
+            (std::mem::take(&mut scope.returns), std::mem::take(&mut scope.propagates))
+        };
+        if !propagates.is_empty() && matches!(self.kind, FunctionType::Initializer) {
+            return Err(CupidError::type_error(
+                Code::InvalidPropagation,
+                "`?` can't be used in an initializer.",
+                "",
+            ));
+        }
+        for &propagated in &propagates {
+            let mut returned = returns.iter().chain(&propagates);
+            if returned.any(|&ty| !propagates_to(propagated, ty)) {
+                let kind = match propagated {
+                    Type::Option(_) => "an option",
+                    _ => "a result",
+                };
+                return Err(CupidError::type_error(
+                    Code::InvalidPropagation,
+                    format!("`?` can only be used in a function that returns {kind}."),
+                    "",
+                ));
+            }
+        }
+
+        // A function that returns options or results is typed by them
+        let wrapper = returns
+            .iter()
+            .chain(&propagates)
+            .copied()
+            .find(|ty| matches!(ty, Type::Option(_) | Type::Result { .. }));
+        let body_ty = match (entry_ty(self.body, arena), wrapper) {
+            (Type::Unknown, Some(ty)) => ty,
+            (ty, _) => ty,
+        };
         self.header.ty = Type::Function {
             returns: arena.insert(body_ty),
         };
@@ -189,7 +262,10 @@ impl<'src> Infer<'src> for If<'src> {
 impl<'src> Infer<'src> for Invoke<'src> {
     fn infer(mut self, arena: &mut ExprArena<'src>) -> Result<Self, CupidError> {
         pass!(Invoke::infer(self, arena));
-        self.set_ty(unwrapped_ty(&self.symbol));
+        match unwrapped_ty(&self.symbol) {
+            Type::Function { returns } => self.set_ty(*arena.expect_ty(returns)),
+            _ => self.set_ty(Type::Unknown),
+        }
         Ok(self)
     }
 }
@@ -221,10 +297,51 @@ impl<'src> Infer<'src> for Method<'src> {
     }
 }
 
+impl<'src> Infer<'src> for Propagate<'src> {
+    fn infer(mut self, arena: &mut ExprArena<'src>) -> Result<Self, CupidError> {
+        pass!(Propagate::infer(self, arena));
+        let ty = match entry_ty(self.value, arena) {
+            Type::Nil => Type::Option(arena.insert(Type::Unknown)),
+            ty => ty,
+        };
+        let unwrapped = match ty {
+            Type::Option(some) => *arena.expect_ty(some),
+            Type::Result { ok, .. } => *arena.expect_ty(ok),
+            Type::Unknown => Type::Unknown,
+            _ => {
+                return Err(CupidError::type_error(
+                    Code::InvalidOperands,
+                    "`?` can only be used on an option or a result.",
+                    "",
+                ))
+            }
+        };
+        let fun_scope = match self.header.scope.enclosing(ScopeContext::Fun) {
+            Some(scope) => scope,
+            None => {
+                return Err(CupidError::type_error(
+                    Code::InvalidPropagation,
+                    "`?` can only be used inside a function.",
+                    "",
+                ))
+            }
+        };
+        if ty != Type::Unknown {
+            fun_scope.borrow_mut().propagates.push(ty);
+        }
+        self.set_ty(unwrapped);
+        Ok(self)
+    }
+}
+
 impl<'src> Infer<'src> for Return<'src> {
     fn infer(mut self, arena: &mut ExprArena<'src>) -> Result<Self, CupidError> {
         pass!(Return::infer(self, arena));
-        self.set_ty(unwrapped_entry_ty(self.value, arena));
+        let ty = unwrapped_entry_ty(self.value, arena);
+        if let Some(fun_scope) = self.header.scope.enclosing(ScopeContext::Fun) {
+            fun_scope.borrow_mut().returns.push(ty);
+        }
+        self.set_ty(ty);
         Ok(self)
     }
 }
@@ -267,6 +384,14 @@ impl<'src> Infer<'src> for SetProperty<'src> {
     }
 }
 
+impl<'src> Infer<'src> for Try<'src> {
+    fn infer(mut self, arena: &mut ExprArena<'src>) -> Result<Self, CupidError> {
+        pass!(Try::infer(self, arena));
+        self.set_ty(entry_ty(self.body, arena));
+        Ok(self)
+    }
+}
+
 impl<'src> Infer<'src> for UnOp<'src> {
     fn infer(mut self, arena: &mut ExprArena<'src>) -> Result<Self, CupidError> {
         pass!(UnOp::infer(self, arena));
@@ -0,0 +1,805 @@
+//! Lints over the checked AST. Every rule has an ID that can be set to `allow`, `warn` or `deny`,
+//! either on the command line or in a comment directive:
+//!
+//! ```text
+//! -- lint: allow(unused_parameter) deny(shadowed_local)
+//! ```
+//!
+//! A directive before the first line of code applies to the whole file. Any other directive
+//! applies to the line it is on, or to the next line when it is on a line of its own.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt, fs, process,
+    rc::Rc,
+};
+
+use crate::{
+    arena::{EntryId, ExprArena, UseArena},
+    ast::{self, Expr, GetSource, GetTy, Header},
+    code::Code,
+    cst::{
+        block::BlockSource,
+        define::DefineSource,
+        expr::{ExprSource, UnwrapEnum},
+        SourceId,
+    },
+    diagnostic::{self, ErrorFormat},
+    error::{CupidError, ErrorDetails, Kind, Severity},
+    for_expr_variant,
+    format::comments,
+    gc::Gc,
+    parse::parser::Parser,
+    pointer::Pointer,
+    run::do_passes,
+    scanner::Scanner,
+    scope::{symbol::Symbol, Scope, ScopeContext},
+    span::Span,
+    token::{Token, TokenType},
+    ty::Type,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Rule {
+    UnusedVariable,
+    UnusedParameter,
+    ShadowedLocal,
+    UnreachableCode,
+    UnreadField,
+    EmptyBlock,
+    ConstantComparison,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 7] = [
+        Rule::UnusedVariable,
+        Rule::UnusedParameter,
+        Rule::ShadowedLocal,
+        Rule::UnreachableCode,
+        Rule::UnreadField,
+        Rule::EmptyBlock,
+        Rule::ConstantComparison,
+    ];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Rule::UnusedVariable => "unused_variable",
+            Rule::UnusedParameter => "unused_parameter",
+            Rule::ShadowedLocal => "shadowed_local",
+            Rule::UnreachableCode => "unreachable_code",
+            Rule::UnreadField => "unread_field",
+            Rule::EmptyBlock => "empty_block",
+            Rule::ConstantComparison => "constant_comparison",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Rule> {
+        Rule::ALL.iter().copied().find(|rule| rule.id() == id)
+    }
+
+    pub fn code(self) -> Code {
+        match self {
+            Rule::UnusedVariable => Code::UnusedVariable,
+            Rule::UnusedParameter => Code::UnusedParameter,
+            Rule::ShadowedLocal => Code::ShadowedLocal,
+            Rule::UnreachableCode => Code::UnreachableCode,
+            Rule::UnreadField => Code::UnreadField,
+            Rule::EmptyBlock => Code::EmptyBlock,
+            Rule::ConstantComparison => Code::ConstantComparison,
+        }
+    }
+
+    /// How a warned rule is reported: matters of style are only hints.
+    fn severity(self) -> Severity {
+        match self {
+            Rule::ShadowedLocal | Rule::EmptyBlock => Severity::Lint,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.id())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl Level {
+    fn from_name(name: &str) -> Option<Level> {
+        match name {
+            "allow" => Some(Level::Allow),
+            "warn" => Some(Level::Warn),
+            "deny" => Some(Level::Deny),
+            _ => None,
+        }
+    }
+}
+
+/// The levels of the rules, all of which warn unless set otherwise.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LintConfig {
+    levels: HashMap<Rule, Level>,
+}
+
+impl LintConfig {
+    /// Sets the level of the comma separated rules in `ids`, where `all` stands for every rule.
+    pub fn set(&mut self, ids: &str, level: Level) -> Result<(), String> {
+        for id in ids.split(',') {
+            match (id, Rule::from_id(id)) {
+                ("all", _) => Rule::ALL.iter().for_each(|rule| {
+                    self.levels.insert(*rule, level);
+                }),
+                (_, Some(rule)) => {
+                    self.levels.insert(rule, level);
+                }
+                (_, None) => return Err(format!("Unknown lint '{id}'.")),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn level(&self, rule: Rule) -> Level {
+        self.levels.get(&rule).copied().unwrap_or(Level::Warn)
+    }
+}
+
+/// A `-- lint:` comment, setting the level of rules on the lines from `first` to `last`.
+#[derive(Debug, Clone)]
+struct Directive {
+    level: Level,
+    rules: Vec<Rule>,
+    first: usize,
+    last: usize,
+}
+
+/// Finds the lint directives in the comments of `code`.
+fn directives(code: &str) -> Result<Vec<Directive>, CupidError> {
+    let first_line = Scanner::new(code).scan_token().span.start.line;
+    let mut directives = vec![];
+    for comment in comments(code) {
+        let text = match comment.text.strip_prefix("--") {
+            Some(text) => text.trim(),
+            None => continue,
+        };
+        let text = match text.strip_prefix("lint:") {
+            Some(text) => text.trim(),
+            None => continue,
+        };
+        let line = code[..comment.start].matches('\n').count() + 1;
+        let line_start = code[..comment.start].rfind('\n').map_or(0, |newline| newline + 1);
+        let (first, last) = match code[line_start..comment.start].trim().is_empty() {
+            true if line < first_line => (0, usize::MAX),
+            true => (line, line + 1),
+            false => (line, line),
+        };
+        let invalid = || {
+            let message = format!("Invalid lint directive '{}'.", comment.text);
+            CupidError::parse_error(message, None).with_code(Code::InvalidLintDirective)
+        };
+        for group in text.split(')').map(str::trim).filter(|group| !group.is_empty()) {
+            let (level, ids) = group.split_once('(').ok_or_else(invalid)?;
+            let level = Level::from_name(level.trim()).ok_or_else(invalid)?;
+            let rules = ids
+                .split(',')
+                .map(|id| match Rule::from_id(id.trim()) {
+                    Some(rule) => Ok(rule),
+                    None => {
+                        let message = format!("Unknown lint '{}'.", id.trim());
+                        Err(CupidError::parse_error(message, None)
+                            .with_code(Code::InvalidLintDirective))
+                    }
+                })
+                .collect::<Result<_, _>>()?;
+            directives.push(Directive {
+                level,
+                rules,
+                first,
+                last,
+            });
+        }
+    }
+    Ok(directives)
+}
+
+/// Parses, checks and lints `code`, returning the lints that are not allowed sorted by where they
+/// are found, or the first error that kept the code from checking.
+pub fn lint_source(code: &str, config: &LintConfig) -> Result<Vec<CupidError>, CupidError> {
+    let directives = directives(code)?;
+    let mut gc = Gc::default();
+    let mut parser = Parser::new(code);
+    let exprs = parser.parse(&mut gc)?;
+    let exprs = do_passes(exprs, &mut parser.arena).map_err(|err| err.locate(&parser.arena))?;
+    let mut linter = Linter {
+        arena: &parser.arena,
+        variables: vec![],
+        variable_ids: HashMap::new(),
+        definitions: HashMap::new(),
+        field_writes: vec![],
+        field_reads: HashSet::new(),
+        lints: vec![],
+    };
+    exprs.lint(&mut linter);
+    let mut lints = linter.finish();
+    lints.sort_by_key(|(_, span, _)| *span);
+
+    let level = |rule: Rule, line: usize| {
+        // Directives on the line win over the ones for the whole file, which win over the config.
+        let directive = directives
+            .iter()
+            .filter(|directive| directive.rules.contains(&rule))
+            .filter(|directive| directive.first <= line && line <= directive.last)
+            .max_by_key(|directive| directive.first);
+        directive.map_or(config.level(rule), |directive| directive.level)
+    };
+    let lints = lints.into_iter().filter_map(|(rule, span, message)| {
+        let severity = match level(rule, span.start.line) {
+            Level::Allow => return None,
+            Level::Warn => rule.severity(),
+            Level::Deny => Severity::Error,
+        };
+        Some(CupidError::new(ErrorDetails {
+            code: rule.code(),
+            kind: Kind::Lint(rule.id()),
+            severity,
+            message,
+            data: vec![],
+            span: Some(span),
+            source: None,
+            help: None,
+            secondary: vec![],
+        }))
+    });
+    Ok(lints.collect())
+}
+
+/// Prints the lints of the file at `path`, exiting with an error if any of them is denied.
+pub fn lint_file(path: &str, config: &LintConfig, format: ErrorFormat) {
+    let code = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) => {
+            eprintln!("Unable to read file {}: {}", path, error);
+            process::exit(74);
+        }
+    };
+    let lints = match lint_source(&code, config) {
+        Ok(lints) => lints,
+        Err(error) => {
+            diagnostic::report(&error, format, path);
+            process::exit(65);
+        }
+    };
+    for lint in &lints {
+        diagnostic::report(lint, format, path);
+    }
+    let denied = lints.iter().filter(|lint| matches!(lint.severity, Severity::Error)).count();
+    // JSON output is only the diagnostics themselves.
+    let summarize = format == ErrorFormat::Human;
+    match (lints.len(), denied) {
+        (0, _) => (),
+        (found, 0) if summarize => eprintln!("{found} lints found."),
+        (_, 0) => (),
+        (found, denied) => {
+            if summarize {
+                eprintln!("{found} lints found, {denied} of which are denied.");
+            }
+            process::exit(1);
+        }
+    }
+}
+
+/// A local variable or parameter, and how it is used.
+struct Variable<'src> {
+    name: &'src str,
+    span: Span,
+    rule: Rule,
+    reads: usize,
+    writes: usize,
+}
+
+pub struct Linter<'a, 'src> {
+    arena: &'a ExprArena<'src>,
+    variables: Vec<Variable<'src>>,
+    variable_ids: HashMap<*const RefCell<Symbol<'src>>, usize>,
+    /// The names defined so far in each local scope, where they are defined.
+    definitions: HashMap<*const RefCell<Scope<'src>>, Vec<(&'src str, Span)>>,
+    /// The fields declared in classes or assigned through `self`, and where.
+    field_writes: Vec<(&'src str, Span)>,
+    /// The names of the properties read anywhere, whatever their receiver.
+    field_reads: HashSet<&'src str>,
+    lints: Vec<(Rule, Span, String)>,
+}
+
+impl<'a, 'src> Linter<'a, 'src> {
+    fn lint(&mut self, rule: Rule, span: Span, message: impl ToString) {
+        self.lints.push((rule, span, message.to_string()));
+    }
+
+    fn source(&self, source: SourceId) -> &'a ExprSource<'src> {
+        self.arena.expect_source(source)
+    }
+
+    fn span(&self, source: SourceId) -> Span {
+        let source = self.source(source);
+        source.first_token(self.arena).span.to(source.last_token(self.arena).span)
+    }
+
+    /// Tracks the uses of a local variable or parameter, and reports whether it shadows another.
+    fn define(&mut self, define: &ast::Define<'src>, rule: Rule) {
+        let scope = &define.header.scope;
+        let name: Token<'src> = {
+            let source: &DefineSource = define.source(self.arena).unwrapped();
+            source.name
+        };
+        if scope.borrow().context == ScopeContext::Global || name.span.is_synthetic() {
+            return;
+        }
+        if rule == Rule::UnusedVariable {
+            if let Some(span) = self.lookup_local(scope, name.lexeme) {
+                let message = format!(
+                    "'{}' shadows the local defined on line {}.",
+                    name.lexeme, span.start.line
+                );
+                self.lint(Rule::ShadowedLocal, name.span, message);
+            }
+        }
+        let definitions = self.definitions.entry(Rc::as_ptr(&scope.0)).or_default();
+        definitions.push((name.lexeme, name.span));
+        if let Some(symbol) = &define.symbol {
+            self.variable_ids.insert(Rc::as_ptr(&symbol.0), self.variables.len());
+            self.variables.push(Variable {
+                name: name.lexeme,
+                span: name.span,
+                rule,
+                reads: 0,
+                writes: 0,
+            });
+        }
+    }
+
+    /// Where `name` was last defined in `scope` or the local scopes around it.
+    fn lookup_local(&self, scope: &Pointer<Scope<'src>>, name: &str) -> Option<Span> {
+        let mut scope = Some(scope.clone());
+        while let Some(current) = scope {
+            if matches!(current.borrow().context, ScopeContext::Global | ScopeContext::Class) {
+                return None;
+            }
+            let definitions = self.definitions.get(&Rc::as_ptr(&current.0));
+            let definition = definitions.and_then(|definitions| {
+                definitions.iter().rev().find(|(defined, _)| *defined == name)
+            });
+            if let Some((_, span)) = definition {
+                return Some(*span);
+            }
+            scope = current.parent();
+        }
+        None
+    }
+
+    fn variable(&mut self, symbol: Option<&Pointer<Symbol<'src>>>) -> Option<&mut Variable<'src>> {
+        let id = self.variable_ids.get(&Rc::as_ptr(&symbol?.0))?;
+        self.variables.get_mut(*id)
+    }
+
+    fn empty_block(&mut self, source: SourceId) {
+        if let ExprSource::Block(BlockSource::BraceBlock(block)) = self.source(source) {
+            let commented =
+                ["--", "##"].iter().any(|marker| block.close_brace.leading.contains(marker));
+            if block.body_src.is_empty() && !commented && !block.open_brace.span.is_synthetic() {
+                let span = block.open_brace.span.to(block.close_brace.span);
+                self.lint(Rule::EmptyBlock, span, "Empty block.");
+            }
+        }
+    }
+
+    /// Reports the variables that are never read, and the fields that are never read.
+    fn finish(mut self) -> Vec<(Rule, Span, String)> {
+        for variable in &self.variables {
+            if variable.reads > 0 || variable.name.starts_with('_') {
+                continue;
+            }
+            let message = match (variable.rule, variable.writes) {
+                (Rule::UnusedParameter, _) => format!("Unused parameter '{}'.", variable.name),
+                (_, 0) => format!("Unused variable '{}'.", variable.name),
+                _ => format!("Variable '{}' is assigned but never read.", variable.name),
+            };
+            self.lints.push((variable.rule, variable.span, message));
+        }
+        let mut reported = HashSet::new();
+        for (name, span) in &self.field_writes {
+            if !self.field_reads.contains(name) && reported.insert(*name) {
+                let message = format!("Field '{name}' is assigned but never read.");
+                self.lints.push((Rule::UnreadField, *span, message));
+            }
+        }
+        self.lints
+    }
+}
+
+pub trait Lint<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>);
+}
+
+impl<'src, T: Lint<'src>> Lint<'src> for Vec<T> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        self.iter().for_each(|item| item.lint(linter))
+    }
+}
+
+impl<'src, T: Lint<'src>> Lint<'src> for Option<T> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        if let Some(item) = self {
+            item.lint(linter)
+        }
+    }
+}
+
+impl<'src> Lint<'src> for EntryId {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        let expr: &Expr<'src> = linter.arena.expect(*self);
+        expr.lint(linter)
+    }
+}
+
+impl<'src> Lint<'src> for Expr<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        for_expr_variant!(self => |inner| inner.lint(linter))
+    }
+}
+
+impl<'src> Lint<'src> for ast::Array<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        self.items.lint(linter);
+    }
+}
+
+/// Whether values of the two types can ever be equal. Values of different kinds never are, and
+/// neither are ints and floats.
+fn can_be_equal(left: Type, right: Type) -> bool {
+    let known = |ty: Type| !matches!(ty, Type::Unknown | Type::Unit | Type::Type);
+    !known(left) || !known(right) || std::mem::discriminant(&left) == std::mem::discriminant(&right)
+}
+
+impl<'src> Lint<'src> for ast::BinOp<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        self.left.lint(linter);
+        self.right.lint(linter);
+        let outcome = match self.op {
+            TokenType::EqualEqual => "false",
+            TokenType::BangEqual => "true",
+            _ => return,
+        };
+        let left = UseArena::<Expr>::expect(linter.arena, self.left).ty();
+        let right = UseArena::<Expr>::expect(linter.arena, self.right).ty();
+        if !can_be_equal(left, right) {
+            let message = format!(
+                "Comparison is always {}: {} and {} values are never equal.",
+                outcome, left, right
+            );
+            linter.lint(Rule::ConstantComparison, linter.span(self.header.source), message);
+        }
+    }
+}
+
+impl<'src> Lint<'src> for ast::Block<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        let mut terminator: Option<Span> = None;
+        let mut unreachable: Option<Span> = None;
+        for id in &self.body {
+            let expr: &Expr<'src> = linter.arena.expect(*id);
+            let span = linter.span(expr.header().source);
+            match terminator {
+                // Desugared loops append their increment after the body, though it comes first.
+                Some(end) if !span.is_synthetic() && span.start > end.end => {
+                    unreachable = Some(match unreachable {
+                        Some(unreachable) => unreachable.to(span),
+                        None => span,
+                    });
+                }
+                Some(_) => (),
+                None if matches!(expr, Expr::Return(_) | Expr::Break(_) | Expr::Throw(_)) => {
+                    terminator = Some(span).filter(|span| !span.is_synthetic());
+                }
+                None => (),
+            }
+            if let Expr::Block(_) = expr {
+                linter.empty_block(expr.header().source);
+            }
+            expr.lint(linter);
+        }
+        if let Some(span) = unreachable {
+            linter.lint(Rule::UnreachableCode, span, "Unreachable code.");
+        }
+    }
+}
+
+impl<'src> Lint<'src> for ast::Break<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        self.value.lint(linter);
+    }
+}
+
+impl<'src> Lint<'src> for ast::Call<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        self.callee.lint(linter);
+        self.args.lint(linter);
+    }
+}
+
+impl<'src> Lint<'src> for ast::Class<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        for field in &self.fields {
+            let source: &DefineSource = field.source(linter.arena).unwrapped();
+            linter.field_writes.push((field.name, source.name.span));
+            field.value.lint(linter);
+        }
+        self.methods.lint(linter);
+    }
+}
+
+impl<'src> Lint<'src> for ast::Constant<'src> {
+    fn lint(&self, _linter: &mut Linter<'_, 'src>) {}
+}
+
+impl<'src> Lint<'src> for ast::Define<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        linter.define(self, Rule::UnusedVariable);
+        self.value.lint(linter);
+    }
+}
+
+impl<'src> Lint<'src> for ast::Fun<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        for param in &self.params {
+            linter.define(param, Rule::UnusedParameter);
+        }
+        self.body.lint(linter);
+    }
+}
+
+impl<'src> Lint<'src> for ast::Get<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        if let Some(variable) = linter.variable(self.symbol.as_ref()) {
+            variable.reads += 1;
+        }
+    }
+}
+
+impl<'src> Lint<'src> for ast::GetProperty<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        self.receiver.lint(linter);
+        linter.field_reads.insert(self.property);
+    }
+}
+
+impl<'src> Lint<'src> for ast::GetSuper<'src> {
+    fn lint(&self, _linter: &mut Linter<'_, 'src>) {}
+}
+
+impl<'src> Lint<'src> for ast::If<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        self.condition.lint(linter);
+        let source = match linter.source(self.header.source) {
+            ExprSource::If(source) => Some(source),
+            _ => None,
+        };
+        // The `if` of a desugared loop has no body of its own.
+        if let Some(source) = source.filter(|source| !source.if_kw.span.is_synthetic()) {
+            linter.empty_block(source.body_src);
+            if let Some(else_body_src) = source.else_body_src {
+                linter.empty_block(else_body_src);
+            }
+        }
+        self.body.lint(linter);
+        self.else_body.lint(linter);
+    }
+}
+
+impl<'src> Lint<'src> for ast::Invoke<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        self.receiver.lint(linter);
+        linter.field_reads.insert(self.callee);
+        self.args.lint(linter);
+    }
+}
+
+impl<'src> Lint<'src> for ast::InvokeSuper<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        self.args.lint(linter);
+    }
+}
+
+impl<'src> Lint<'src> for ast::Loop<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        if let ExprSource::Loop(source) = linter.source(self.header.source) {
+            linter.empty_block(source.body_src());
+        }
+        self.body.lint(linter);
+    }
+}
+
+impl<'src> Lint<'src> for ast::Method<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        self.fun.lint(linter);
+    }
+}
+
+impl<'src> Lint<'src> for ast::Propagate<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        self.value.lint(linter);
+    }
+}
+
+impl<'src> Lint<'src> for ast::Return<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        self.value.lint(linter);
+    }
+}
+
+impl<'src> Lint<'src> for ast::Set<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        if let Some(variable) = linter.variable(self.symbol.as_ref()) {
+            variable.writes += 1;
+        }
+        self.value.lint(linter);
+    }
+}
+
+impl<'src> Lint<'src> for ast::SetProperty<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        self.receiver.lint(linter);
+        let receiver: &Expr<'src> = linter.arena.expect(self.receiver);
+        if let Expr::Get(get) = receiver {
+            if get.name == "self" {
+                let source = linter.source(self.header.source);
+                linter.field_writes.push((self.property, source.token().span));
+            }
+        }
+        self.value.lint(linter);
+    }
+}
+
+impl<'src> Lint<'src> for ast::Throw<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        self.value.lint(linter);
+    }
+}
+
+impl<'src> Lint<'src> for ast::Try<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        if let ExprSource::Try(source) = linter.source(self.header.source) {
+            linter.empty_block(source.body_src);
+            linter.empty_block(source.catch_body_src);
+            if let Some(finally_body_src) = source.finally_body_src {
+                linter.empty_block(finally_body_src);
+            }
+        }
+        self.body.lint(linter);
+        linter.define(&self.catch_param, Rule::UnusedVariable);
+        self.catch_body.lint(linter);
+        self.finally_body.lint(linter);
+    }
+}
+
+impl<'src> Lint<'src> for ast::UnOp<'src> {
+    fn lint(&self, linter: &mut Linter<'_, 'src>) {
+        self.expr.lint(linter);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lint_source, Level, LintConfig};
+    use crate::error::{Kind, Severity};
+
+    /// The lints of `code` as `(rule, line)` pairs.
+    fn lints(code: &str, config: &LintConfig) -> Vec<(&'static str, usize)> {
+        let lints = lint_source(code, config).unwrap();
+        lints
+            .iter()
+            .map(|lint| match lint.kind {
+                Kind::Lint(rule) => (rule, lint.span.unwrap().start.line),
+                _ => panic!("not a lint: {}", lint),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unused_and_shadowed_locals() {
+        let code = "\
+fun add (a, b, _c) {
+    let unused = 1
+    let total = 0
+    total = a
+    if a > 0 {
+        let a = 2
+        return a
+    }
+}
+";
+        assert_eq!(
+            lints(code, &LintConfig::default()),
+            vec![
+                ("unused_parameter", 1),
+                ("unused_variable", 2),
+                ("unused_variable", 3),
+                ("shadowed_local", 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn unreachable_code_empty_blocks_and_comparisons() {
+        let code = "\
+fun f (a) {
+    if a == 'a' {}
+    while a != 1 {
+        -- Waiting.
+    }
+    return a
+    log(a)
+}
+";
+        assert_eq!(
+            lints(code, &LintConfig::default()),
+            vec![("empty_block", 2), ("unreachable_code", 7)]
+        );
+
+        let code = "let n = 1\nlog(n == 'one')\nlog(n != 1.0)\nlog(n == 2)\n";
+        assert_eq!(
+            lints(code, &LintConfig::default()),
+            vec![("constant_comparison", 2), ("constant_comparison", 3)]
+        );
+    }
+
+    #[test]
+    fn unread_fields() {
+        let code = "\
+class Point {
+    let x = 0
+    let label = ''
+    init (y) {
+        self.y = y
+        self.z = 1
+    }
+    get () { return self.x + self.y }
+}
+";
+        assert_eq!(
+            lints(code, &LintConfig::default()),
+            vec![("unread_field", 3), ("unread_field", 6)]
+        );
+    }
+
+    #[test]
+    fn levels_from_config_and_directives() {
+        let code = "\
+-- lint: deny(unused_parameter)
+fun f (a, b, c) {
+    -- lint: allow(unused_parameter)
+    return fun (d) {}
+}
+fun g (e) {} -- lint: warn(unused_parameter)
+";
+        let mut config = LintConfig::default();
+        config.set("empty_block", Level::Allow).unwrap();
+        let lints = lint_source(code, &config).unwrap();
+        let found: Vec<(usize, bool)> = lints
+            .iter()
+            .map(|lint| (lint.span.unwrap().start.line, matches!(lint.severity, Severity::Error)))
+            .collect();
+        assert_eq!(found, vec![(2, true), (2, true), (2, true), (6, false)]);
+
+        assert!(config.set("unused", Level::Deny).is_err());
+        config.set("all", Level::Allow).unwrap();
+        assert!(lint_source("fun f (a) {}", &config).unwrap().is_empty());
+        assert!(lint_source("-- lint: allow(nothing)\n", &config).is_err());
+    }
+}
@@ -1,4 +1,8 @@
 pub mod auto_impl;
+pub mod complete;
+pub mod index;
 pub mod infer;
+pub mod lint;
 pub mod pretty;
+pub mod rename;
 pub mod resolve;
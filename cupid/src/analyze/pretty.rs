@@ -240,6 +240,16 @@ impl<'src> PrettyPrint<'src> for ast::Method<'src> {
     }
 }
 
+impl<'src> PrettyPrint<'src> for ast::Propagate<'src> {
+    fn pretty_print(&self, arena: &ExprArena<'src>) -> String {
+        format!(
+            "Propagate {{ value: {}, ty: {} }}",
+            self.value.pretty_print(arena),
+            self.ty().pretty_print(arena)
+        )
+    }
+}
+
 impl<'src> PrettyPrint<'src> for ast::Return<'src> {
     fn pretty_print(&self, arena: &ExprArena<'src>) -> String {
         format!(
@@ -275,6 +285,29 @@ impl<'src> PrettyPrint<'src> for ast::SetProperty<'src> {
     }
 }
 
+impl<'src> PrettyPrint<'src> for ast::Throw<'src> {
+    fn pretty_print(&self, arena: &ExprArena<'src>) -> String {
+        format!(
+            "Throw {{ value: {}, ty: {} }}",
+            self.value.pretty_print(arena),
+            self.ty().pretty_print(arena)
+        )
+    }
+}
+
+impl<'src> PrettyPrint<'src> for ast::Try<'src> {
+    fn pretty_print(&self, arena: &ExprArena<'src>) -> String {
+        format!(
+            "Try {{ body: {}, catch_param: {}, catch_body: {}, finally_body: {}, ty: {} }}",
+            self.body.pretty_print(arena),
+            self.catch_param.pretty_print(arena),
+            self.catch_body.pretty_print(arena),
+            self.finally_body.pretty_print(arena),
+            self.ty().pretty_print(arena)
+        )
+    }
+}
+
 impl<'src> PrettyPrint<'src> for ast::UnOp<'src> {
     fn pretty_print(&self, arena: &ExprArena<'src>) -> String {
         format!(
@@ -0,0 +1,174 @@
+use crate::{
+    analyze::index::{index_source, SymbolId, SymbolIndex, SymbolKind},
+    code::Code,
+    error::CupidError,
+    scanner::Scanner,
+    span::Span,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub span: Span,
+    pub new_text: String,
+}
+
+/// Every occurrence of the symbol at the given byte offset of `code`: its definition first, if it
+/// has one, and then its references.
+pub fn find_references(code: &str, offset: usize) -> Result<Vec<Span>, CupidError> {
+    let index = index_source(code)?;
+    let id = symbol_at(&index, offset)?;
+    Ok(occurrences(&index, id))
+}
+
+/// The edits renaming the symbol at the given byte offset of `code` to `new_name`, everywhere it is
+/// used. Renames that would change what another name refers to are refused.
+pub fn rename(code: &str, offset: usize, new_name: &str) -> Result<Vec<TextEdit>, CupidError> {
+    let index = index_source(code)?;
+    let id = symbol_at(&index, offset)?;
+    let symbol = index.symbol(id);
+    let span = match symbol.span {
+        Some(span) => span,
+        None => return Err(error(format!("Cannot rename built-in `{}`.", symbol.name), None)),
+    };
+    let is_keyword = Scanner::new("").keywords().any(|keyword| keyword == new_name);
+    let starts_with_digit = new_name.starts_with(|c: char| c.is_ascii_digit());
+    if new_name.is_empty()
+        || starts_with_digit
+        || is_keyword
+        || !new_name.chars().all(|c| c.is_alphanumeric() || c == '_')
+    {
+        return Err(error(format!("`{new_name}` is not a valid name."), Some(span)));
+    }
+    if let Some(conflict) = conflict(&index, id, new_name) {
+        let message = format!("Renaming `{}` to `{new_name}` would conflict with it.", symbol.name);
+        let mut error = error(message, index.symbol(conflict).span.or(Some(span)));
+        if error.span != Some(span) {
+            error.secondary.push((span, format!("`{}` is defined here", symbol.name)));
+        }
+        return Err(error);
+    }
+    let edits = occurrences(&index, id)
+        .into_iter()
+        .map(|span| TextEdit {
+            span,
+            new_text: new_name.to_string(),
+        })
+        .collect();
+    Ok(edits)
+}
+
+fn symbol_at(index: &SymbolIndex, offset: usize) -> Result<SymbolId, CupidError> {
+    match index.symbol_at(offset) {
+        Some(id) => Ok(id),
+        None => Err(error("No symbol found at the cursor.", None)),
+    }
+}
+
+fn occurrences(index: &SymbolIndex, id: SymbolId) -> Vec<Span> {
+    let definition = index.symbol(id).span;
+    let mut spans: Vec<Span> = definition.into_iter().chain(index.references_to(id)).collect();
+    spans[definition.is_some() as usize..].sort_by_key(|span| span.start.index);
+    spans
+}
+
+/// Finds a symbol named `new_name` that either would be confused with `id` where they are defined,
+/// or would change meaning at one of its occurrences.
+fn conflict(index: &SymbolIndex, id: SymbolId, new_name: &str) -> Option<SymbolId> {
+    let is_named = |other: &SymbolId| *other != id && index.symbol(*other).name == new_name;
+    let symbol = index.symbol(id);
+    if matches!(symbol.kind, SymbolKind::Field | SymbolKind::Method) {
+        // A member must not clash with the other members of any class that has it, inherited or
+        // not.
+        let classes = index.classes.iter().filter(|class| index.members(&class.name).contains(&id));
+        return classes
+            .flat_map(|class| index.members(&class.name))
+            .find(|other| is_named(other));
+    }
+    // A name defined in the same scope, or one in scope wherever the symbol is used.
+    let occurrences = occurrences(index, id);
+    let shadowed = occurrences
+        .iter()
+        .flat_map(|span| index.visible_at(span.start.index))
+        .find(|other| is_named(other));
+    if shadowed.is_some() {
+        return shadowed;
+    }
+    let defined_alongside = index
+        .scopes
+        .iter()
+        .filter(|scope| scope.symbols.contains(&id))
+        .flat_map(|scope| scope.symbols.iter().copied())
+        .find(|other| is_named(other));
+    if defined_alongside.is_some() {
+        return defined_alongside;
+    }
+    // Uses of another symbol named `new_name` that would find the renamed one first.
+    let captured = index
+        .references
+        .iter()
+        .filter(|reference| is_named(&reference.symbol))
+        .find(|reference| index.visible_at(reference.span.start.index).contains(&id));
+    captured.map(|reference| reference.symbol)
+}
+
+fn error(message: impl ToString, span: Option<Span>) -> CupidError {
+    let mut error = CupidError::name_error(Code::InvalidRename, message, "");
+    error.span = span;
+    error
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_references, rename};
+
+    fn offset(code: &str, needle: &str, nth: usize) -> usize {
+        code.match_indices(needle).nth(nth).unwrap().0
+    }
+
+    fn apply(code: &str, offset: usize, new_name: &str) -> String {
+        let mut edits = rename(code, offset, new_name).unwrap();
+        edits.sort_by_key(|edit| std::cmp::Reverse(edit.span.start.index));
+        let mut code = code.to_string();
+        for edit in edits {
+            code.replace_range(edit.span.start.index..edit.span.end.index, &edit.new_text);
+        }
+        code
+    }
+
+    #[test]
+    fn respects_shadowing() {
+        let code = "let a = 1\nfun f () {\n    let a = 2\n    log(a)\n}\nlog(a)\n";
+        let renamed = apply(code, offset(code, "a", 0), "b");
+        assert_eq!(renamed, "let b = 1\nfun f () {\n    let a = 2\n    log(a)\n}\nlog(b)\n");
+
+        let renamed = apply(code, offset(code, "a", 2), "c");
+        assert_eq!(renamed, "let a = 1\nfun f () {\n    let c = 2\n    log(c)\n}\nlog(a)\n");
+    }
+
+    #[test]
+    fn renames_members_through_self_and_super() {
+        let code = "class A {\n    let size = 1\n    grow () { return self.size }\n}\n\
+            class B < A {\n    grow () { return super.grow() }\n}\n\
+            let b = B()\nlog(b.size)\nb.grow()\n";
+        let renamed = apply(code, offset(code, "size", 2), "width");
+        assert_eq!(renamed.matches("width").count(), 3);
+        assert!(!renamed.contains("size"));
+
+        let references = find_references(code, offset(code, "grow", 2)).unwrap();
+        let starts: Vec<usize> = references.iter().map(|span| span.start.index).collect();
+        assert_eq!(starts, vec![offset(code, "grow", 0), offset(code, "grow", 2)]);
+    }
+
+    #[test]
+    fn refuses_collisions() {
+        let code = "let a = 1\nlet b = 2\nfun f (x) { return x + b }\n";
+        assert!(rename(code, offset(code, "a", 0), "b").is_err());
+        assert!(rename(code, offset(code, "x", 0), "b").is_err());
+        assert!(rename(code, offset(code, "x", 0), "let").is_err());
+        assert!(rename(code, offset(code, "x", 0), "y").is_ok());
+        assert!(rename("log(1)", 0, "print").is_err());
+
+        let code = "class A {\n    let size = 1\n    grow () {}\n}\n";
+        assert!(rename(code, offset(code, "size", 0), "grow").is_err());
+    }
+}
@@ -2,8 +2,8 @@ use crate::{
     arena::{EntryId, ExprArena, UseArena},
     ast::{
         Array, BinOp, Block, Break, Call, Class, Constant, Define, Expr, Fun, Get, GetProperty,
-        GetSuper, GetTy, Header, If, Invoke, InvokeSuper, Loop, Method, Return, Set, SetProperty,
-        UnOp,
+        GetSuper, GetTy, Header, If, Invoke, InvokeSuper, Loop, Method, Propagate, Return, Set,
+        SetProperty, Throw, Try, UnOp,
     },
     auto_impl, base_pass,
     error::CupidError,
@@ -31,7 +31,10 @@ base_pass! {
         Constant,
         If,
         Loop,
+        Propagate,
         Return,
+        Throw,
+        Try,
         UnOp
     }
 }
@@ -56,10 +59,13 @@ impl<'src> Resolve<'src> for EntryId {
 impl<'src> Resolve<'src> for Class<'src> {
     fn resolve(mut self, arena: &mut ExprArena<'src>) -> Result<Self, CupidError> {
         let name = self.name;
-        self.scope_mut().define(name);
+        let symbol = self.symbol.take();
+        let symbol = self.scope_mut().define_symbol(name, symbol);
+        self.symbol = Some(symbol);
 
         let scope = self.class_scope.clone();
-        self.scope_mut().insert_class(name, scope);
+        let super_class = self.super_class;
+        self.scope_mut().insert_class(name, scope, super_class);
 
         self.class_scope_mut().define("self");
         self.class_scope_mut().annotate_class(name);
@@ -76,7 +82,9 @@ impl<'src> Resolve<'src> for Class<'src> {
 impl<'src> Resolve<'src> for Define<'src> {
     fn resolve(mut self, arena: &mut ExprArena<'src>) -> Result<Self, CupidError> {
         let name = self.name;
-        self.scope_mut().define(name);
+        let symbol = self.symbol.take();
+        let symbol = self.scope_mut().define_symbol(name, symbol);
+        self.symbol = Some(symbol);
         pass!(Define::resolve(self, arena));
         Ok(self)
     }
@@ -85,7 +93,9 @@ impl<'src> Resolve<'src> for Define<'src> {
 impl<'src> Resolve<'src> for Fun<'src> {
     fn resolve(mut self, arena: &mut ExprArena<'src>) -> Result<Self, CupidError> {
         if let Some(name) = self.name {
-            self.scope_mut().define(name);
+            let symbol = self.symbol.take();
+            let symbol = self.scope_mut().define_symbol(name, symbol);
+            self.symbol = Some(symbol);
         }
         pass!(Fun::resolve(self, arena));
         Ok(self)
@@ -104,7 +114,7 @@ impl<'src> Resolve<'src> for GetProperty<'src> {
         pass!(GetProperty::resolve(self, arena));
         let receiver_ty = UseArena::<Expr>::expect(arena, self.receiver).ty();
         match receiver_ty {
-            Type::Class(_) => {
+            Type::Class(_) | Type::Instance(_) => {
                 let symbol = self.scope().lookup_property(receiver_ty, self.property)?;
                 self.symbol = symbol;
             }
@@ -126,7 +136,7 @@ impl<'src> Resolve<'src> for Invoke<'src> {
         pass!(Invoke::resolve(self, arena));
         let receiver_ty = UseArena::<Expr>::expect(arena, self.receiver).ty();
         match receiver_ty {
-            Type::Class(_) => {
+            Type::Class(_) | Type::Instance(_) => {
                 let symbol = self.scope().lookup_property(receiver_ty, self.callee)?;
                 self.symbol = symbol;
             }
@@ -147,7 +157,9 @@ impl<'src> Resolve<'src> for InvokeSuper<'src> {
 impl<'src> Resolve<'src> for Method<'src> {
     fn resolve(mut self, arena: &mut ExprArena<'src>) -> Result<Self, CupidError> {
         let name = self.name;
-        self.scope_mut().define(name);
+        let symbol = self.symbol.take();
+        let symbol = self.scope_mut().define_symbol(name, symbol);
+        self.symbol = Some(symbol);
         Ok(Method {
             fun: self.fun.resolve(arena)?,
             ..self
@@ -170,7 +182,7 @@ impl<'src> Resolve<'src> for SetProperty<'src> {
         pass!(SetProperty::resolve(self, arena));
         let receiver_ty = UseArena::<Expr>::expect(arena, self.receiver).ty();
         match receiver_ty {
-            Type::Class(_) => {
+            Type::Class(_) | Type::Instance(_) => {
                 let symbol = self.scope().lookup_property(receiver_ty, self.property)?;
                 self.symbol = symbol;
             }
@@ -1,5 +1,7 @@
 use std::ops;
 
+use serde::{ser::SerializeMap, Serialize, Serializer};
+
 use crate::{
     ast::Expr,
     cst::{expr::ExprSource, SourceArena},
@@ -20,6 +22,16 @@ impl Default for Arena<Expr<'_>> {
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
 pub struct EntryId(pub usize);
 
+/// Serialized as a reference to the expression, `{"$expr": 3}`, which [`crate::emit`] replaces
+/// with the expression itself in the syntax tree.
+impl Serialize for EntryId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry("$expr", &self.0)?;
+        map.end()
+    }
+}
+
 pub struct Entry<T> {
     pub id: EntryId,
     pub value: Option<T>,
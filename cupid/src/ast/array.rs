@@ -1,9 +1,11 @@
+use serde::Serialize;
+
 use crate::{arena::EntryId, with_header};
 
 use super::{Expr, ExprHeader, Header};
 
 with_header! {
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Serialize)]
     pub struct Array<'src> {
         pub items: Vec<EntryId>,
     }
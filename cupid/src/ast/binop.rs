@@ -1,8 +1,10 @@
+use serde::Serialize;
+
 use super::{Expr, ExprHeader, Header};
 use crate::{arena::EntryId, token::TokenType, with_header};
 
 with_header! {
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Serialize)]
     pub struct BinOp<'src> {
         pub left: EntryId,
         pub right: EntryId,
@@ -1,8 +1,10 @@
+use serde::Serialize;
+
 use super::{Expr, ExprHeader, Header};
 use crate::{arena::EntryId, with_header};
 
 with_header! {
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Serialize)]
     pub struct Call<'src> {
         pub callee: EntryId,
         pub args: Vec<EntryId>,
@@ -1,16 +1,26 @@
 use std::cell::{Ref, RefMut};
 
-use super::{Define, Expr, ExprHeader, Header, Method};
-use crate::{pointer::Pointer, scope::Scope, with_header};
+use serde::Serialize;
+
+use super::{Define, DocComment, Expr, ExprHeader, HasSymbol, Header, Method};
+use crate::{
+    pointer::Pointer,
+    scope::{symbol::Symbol, Scope},
+    with_header,
+};
 
 with_header! {
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Serialize)]
     pub struct Class<'src> {
         pub name: &'src str,
+        pub doc: Option<DocComment<'src>>,
         pub super_class: Option<&'src str>,
         pub fields: Vec<Define<'src>>,
         pub methods: Vec<Method<'src>>,
+        #[serde(skip)]
         pub class_scope: Pointer<Scope<'src>>,
+        #[serde(skip)]
+        pub symbol: Option<Pointer<Symbol<'src>>>,
     }
 }
 
@@ -23,6 +33,21 @@ impl<'src> Class<'src> {
     }
 }
 
+impl<'src> HasSymbol<'src> for Class<'src> {
+    fn symbol_name(&self) -> &'src str {
+        self.name
+    }
+    fn symbol(&self) -> Option<&Pointer<Symbol<'src>>> {
+        self.symbol.as_ref()
+    }
+    fn symbol_mut(&mut self) -> Option<&mut Pointer<Symbol<'src>>> {
+        self.symbol.as_mut()
+    }
+    fn set_symbol(&mut self, symbol: Option<Pointer<Symbol<'src>>>) {
+        self.symbol = symbol;
+    }
+}
+
 impl<'src> From<Class<'src>> for Expr<'src> {
     fn from(value: Class<'src>) -> Self {
         Expr::Class(value.into())
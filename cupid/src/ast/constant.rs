@@ -1,8 +1,10 @@
+use serde::Serialize;
+
 use super::{Expr, ExprHeader, Header};
 use crate::{value::Value, with_header};
 
 with_header! {
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Serialize)]
     pub struct Constant<'src> {
         pub value: Value,
     }
@@ -1,11 +1,31 @@
-use super::{Expr, ExprHeader, Header};
-use crate::{arena::EntryId, with_header};
+use serde::Serialize;
+
+use super::{DocComment, Expr, ExprHeader, HasSymbol, Header};
+use crate::{arena::EntryId, pointer::Pointer, scope::symbol::Symbol, with_header};
 
 with_header! {
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Serialize)]
     pub struct Define<'src> {
         pub name: &'src str,
+        pub doc: Option<DocComment<'src>>,
         pub value: Option<EntryId>,
+        #[serde(skip)]
+        pub symbol: Option<Pointer<Symbol<'src>>>,
+    }
+}
+
+impl<'src> HasSymbol<'src> for Define<'src> {
+    fn symbol_name(&self) -> &'src str {
+        self.name
+    }
+    fn symbol(&self) -> Option<&Pointer<Symbol<'src>>> {
+        self.symbol.as_ref()
+    }
+    fn symbol_mut(&mut self) -> Option<&mut Pointer<Symbol<'src>>> {
+        self.symbol.as_mut()
+    }
+    fn set_symbol(&mut self, symbol: Option<Pointer<Symbol<'src>>>) {
+        self.symbol = symbol;
     }
 }
 
@@ -0,0 +1,55 @@
+use serde::Serialize;
+
+/// The `##` comment lines written right before a declaration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DocComment<'src>(pub &'src str);
+
+impl<'src> DocComment<'src> {
+    /// Finds the doc comment at the end of the trivia leading a declaration: the `##` lines right
+    /// above it, with no blank line or other comment in between.
+    pub fn from_trivia(trivia: &'src str) -> Option<Self> {
+        let end = trivia.rfind('\n')?;
+        // What comes before the declaration on its own line.
+        if !trivia[end + 1..].trim().is_empty() {
+            return None;
+        }
+        let mut start = end + 1;
+        for line in trivia[..start].split_inclusive('\n').rev() {
+            if !line.trim_start().starts_with("##") {
+                break;
+            }
+            start -= line.len();
+        }
+        match start < end {
+            true => Some(DocComment(trivia[start..end].trim_start())),
+            false => None,
+        }
+    }
+
+    /// The text of the comment, without the `##` markers.
+    pub fn text(&self) -> String {
+        let lines: Vec<&str> = self
+            .0
+            .lines()
+            .map(|line| {
+                let line = line.trim_start().trim_start_matches("##");
+                line.strip_prefix(' ').unwrap_or(line).trim_end()
+            })
+            .collect();
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DocComment;
+
+    #[test]
+    fn takes_the_lines_right_above() {
+        let doc = DocComment::from_trivia("-- Not this.\n## Adds.\n##\n##   Indented.\n    ");
+        assert_eq!(doc.map(|doc| doc.text()), Some("Adds.\n\n  Indented.".to_string()));
+        assert_eq!(DocComment::from_trivia("## Detached.\n\n"), None);
+        assert_eq!(DocComment::from_trivia("\n"), None);
+        assert_eq!(DocComment::from_trivia(""), None);
+    }
+}
@@ -11,12 +11,14 @@ use std::{
     fmt,
 };
 
+use serde::Serialize;
+
 use super::{
     Array, BinOp, Block, Break, Call, Class, Constant, Define, Fun, Get, GetProperty, GetSuper,
-    Header, If, Invoke, InvokeSuper, Loop, Return, Set, SetProperty, UnOp,
+    Header, If, Invoke, InvokeSuper, Loop, Propagate, Return, Set, SetProperty, Throw, Try, UnOp,
 };
 
-#[derive(Clone)]
+#[derive(Clone, Serialize)]
 pub enum Expr<'src> {
     Array(Array<'src>),
     BinOp(BinOp<'src>),
@@ -34,9 +36,12 @@ pub enum Expr<'src> {
     Invoke(Invoke<'src>),
     InvokeSuper(InvokeSuper<'src>),
     Loop(Loop<'src>),
+    Propagate(Propagate<'src>),
     Return(Return<'src>),
     Set(Set<'src>),
     SetProperty(SetProperty<'src>),
+    Throw(Throw<'src>),
+    Try(Try<'src>),
     UnOp(UnOp<'src>),
 }
 
@@ -60,9 +65,12 @@ macro_rules! for_expr_variant {
             Self::Invoke($inner) => $fun,
             Self::InvokeSuper($inner) => $fun,
             Self::Loop($inner) => $fun,
+            Self::Propagate($inner) => $fun,
             Self::Return($inner) => $fun,
             Self::Set($inner) => $fun,
             Self::SetProperty($inner) => $fun,
+            Self::Throw($inner) => $fun,
+            Self::Try($inner) => $fun,
             Self::UnOp($inner) => $fun,
         }
     };
@@ -70,26 +78,22 @@ macro_rules! for_expr_variant {
 
 pub(crate) use for_expr_variant;
 
-pub trait HasSymbol<'src> {
+pub trait HasSymbol<'src>: Header<'src> {
     fn symbol_name(&self) -> &'src str;
     fn symbol(&self) -> Option<&Pointer<Symbol<'src>>>;
     fn symbol_mut(&mut self) -> Option<&mut Pointer<Symbol<'src>>>;
     fn set_symbol(&mut self, symbol: Option<Pointer<Symbol<'src>>>);
     fn expect_symbol(&self) -> Result<Ref<Symbol<'src>>, CupidError> {
-        match self.symbol().as_ref() {
+        match self.symbol() {
             Some(symbol) => Ok(symbol.borrow()),
-            None => {
-                let name = self.symbol_name();
-                Err(CupidError::name_error(format!("Undefined: `{}`", name), name.to_string()))
-            }
+            None => Err(self.scope().undefined_name(self.symbol_name())),
         }
     }
     fn expect_symbol_mut(&mut self) -> Result<RefMut<Symbol<'src>>, CupidError> {
-        let name = self.symbol_name();
-        if let Some(symbol) = self.symbol_mut() {
-            return Ok(symbol.borrow_mut());
+        match self.symbol() {
+            Some(symbol) => Ok(symbol.borrow_mut()),
+            None => Err(self.scope().undefined_name(self.symbol_name())),
         }
-        Err(CupidError::name_error(format!("Undefined: `{}`", name), name.to_string()))
     }
 }
 
@@ -1,13 +1,20 @@
-use super::{Define, Expr, ExprHeader, Header};
-use crate::{arena::EntryId, compiler::FunctionType, with_header};
+use serde::Serialize;
+
+use super::{Define, DocComment, Expr, ExprHeader, Header};
+use crate::{
+    arena::EntryId, compiler::FunctionType, pointer::Pointer, scope::symbol::Symbol, with_header,
+};
 
 with_header! {
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Serialize)]
     pub struct Fun<'src> {
         pub kind: FunctionType,
         pub name: Option<&'src str>,
+        pub doc: Option<DocComment<'src>>,
         pub params: Vec<Define<'src>>,
         pub body: EntryId,
+        #[serde(skip)]
+        pub symbol: Option<Pointer<Symbol<'src>>>,
     }
 }
 
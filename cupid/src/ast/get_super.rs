@@ -1,10 +1,13 @@
+use serde::Serialize;
+
 use super::{Expr, ExprHeader, HasSymbol, Header};
 use crate::{pointer::Pointer, scope::symbol::Symbol, with_header};
 
 with_header! {
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Serialize)]
     pub struct GetSuper<'src> {
         pub name: &'src str,
+        #[serde(skip)]
         pub symbol: Option<Pointer<Symbol<'src>>>
     }
 }
@@ -1,16 +1,27 @@
 use std::cell::{Ref, RefMut};
 
+use serde::{Serialize, Serializer};
+
 use crate::{cst::SourceId, for_expr_variant, pointer::Pointer, scope::Scope, ty::Type};
 
 use super::Expr;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ExprHeader<'src> {
     pub ty: Type<'src>,
+    /// Serialized as the depth of the scope, 0 being the global one.
+    #[serde(rename = "scope_depth", serialize_with = "serialize_depth")]
     pub scope: Pointer<Scope<'src>>,
     pub source: SourceId,
 }
 
+fn serialize_depth<S: Serializer>(
+    scope: &Pointer<Scope>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(scope.borrow().depth as u64)
+}
+
 pub trait Header<'src> {
     fn header(&self) -> &ExprHeader<'src>;
     fn header_mut(&mut self) -> &mut ExprHeader<'src>;
@@ -45,13 +56,13 @@ macro_rules! with_header {
     (
         #[derive( $($der:ty),* )]
         $v:vis struct $name:ident<'src> {
-            $( $field_v:vis $field_name:ident: $field_ty:ty ),* $(,)?
+            $( $(#[$field_attr:meta])* $field_v:vis $field_name:ident: $field_ty:ty ),* $(,)?
         }
     ) => {
         #[derive( $($der),* )]
         $v struct $name<'src> {
             pub header: ExprHeader<'src>,
-            $( $field_v $field_name: $field_ty ),*
+            $( $(#[$field_attr])* $field_v $field_name: $field_ty ),*
         }
         impl<'src> Header<'src> for $name<'src> {
             fn header(&self) -> &ExprHeader<'src> {
@@ -1,12 +1,15 @@
+use serde::Serialize;
+
 use super::{Expr, ExprHeader, HasSymbol, Header};
 use crate::{arena::EntryId, pointer::Pointer, scope::symbol::Symbol, with_header};
 
 with_header! {
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Serialize)]
     pub struct Invoke<'src> {
         pub receiver: EntryId,
         pub callee: &'src str,
         pub args: Vec<EntryId>,
+        #[serde(skip)]
         pub symbol: Option<Pointer<Symbol<'src>>>
     }
 }
@@ -1,11 +1,14 @@
+use serde::Serialize;
+
 use super::{Expr, ExprHeader, HasSymbol, Header};
 use crate::{arena::EntryId, pointer::Pointer, scope::symbol::Symbol, with_header};
 
 with_header! {
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, Serialize)]
     pub struct InvokeSuper<'src> {
         pub name: &'src str,
         pub args: Vec<EntryId>,
+        #[serde(skip)]
         pub symbol: Option<Pointer<Symbol<'src>>>
     }
 }
@@ -1,13 +1,32 @@
 use std::fmt;
 
-use super::{ExprHeader, Fun, Header};
-use crate::with_header;
+use serde::Serialize;
+
+use super::{ExprHeader, Fun, HasSymbol, Header};
+use crate::{pointer::Pointer, scope::symbol::Symbol, with_header};
 
 with_header! {
-    #[derive(Clone)]
+    #[derive(Clone, Serialize)]
     pub struct Method<'src> {
         pub name: &'src str,
         pub fun: Fun<'src>,
+        #[serde(skip)]
+        pub symbol: Option<Pointer<Symbol<'src>>>,
+    }
+}
+
+impl<'src> HasSymbol<'src> for Method<'src> {
+    fn symbol_name(&self) -> &'src str {
+        self.name
+    }
+    fn symbol(&self) -> Option<&Pointer<Symbol<'src>>> {
+        self.symbol.as_ref()
+    }
+    fn symbol_mut(&mut self) -> Option<&mut Pointer<Symbol<'src>>> {
+        self.symbol.as_mut()
+    }
+    fn set_symbol(&mut self, symbol: Option<Pointer<Symbol<'src>>>) {
+        self.symbol = symbol;
     }
 }
 
@@ -22,6 +22,9 @@ pub use self::constant::*;
 pub mod define;
 pub use self::define::*;
 
+pub mod doc;
+pub use self::doc::*;
+
 pub mod fun;
 pub use self::fun::*;
 
@@ -49,6 +52,9 @@ pub use self::r#loop::*;
 pub mod method;
 pub use self::method::*;
 
+pub mod propagate;
+pub use self::propagate::*;
+
 pub mod r#return;
 pub use self::r#return::*;
 
@@ -58,6 +64,12 @@ pub use self::set_property::*;
 pub mod set;
 pub use self::set::*;
 
+pub mod throw;
+pub use self::throw::*;
+
+pub mod r#try;
+pub use self::r#try::*;
+
 pub mod unop;
 pub use self::unop::*;
 
@@ -0,0 +1,19 @@
+use serde::Serialize;
+
+use super::{Expr, ExprHeader, Header};
+use crate::{arena::EntryId, with_header};
+
+with_header! {
+    #[derive(Debug, Clone, Serialize)]
+    pub struct Propagate<'src> {
+        /// An option or a result, returned from the enclosing function when it is none or an
+        /// error, and unwrapped otherwise.
+        pub value: EntryId,
+    }
+}
+
+impl<'src> From<Propagate<'src>> for Expr<'src> {
+    fn from(value: Propagate<'src>) -> Self {
+        Expr::Propagate(value)
+    }
+}
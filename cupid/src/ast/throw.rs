@@ -0,0 +1,17 @@
+use serde::Serialize;
+
+use super::{Expr, ExprHeader, Header};
+use crate::{arena::EntryId, with_header};
+
+with_header! {
+    #[derive(Debug, Clone, Serialize)]
+    pub struct Throw<'src> {
+        pub value: EntryId,
+    }
+}
+
+impl<'src> From<Throw<'src>> for Expr<'src> {
+    fn from(value: Throw<'src>) -> Self {
+        Expr::Throw(value)
+    }
+}
@@ -0,0 +1,22 @@
+use serde::Serialize;
+
+use super::{Define, Expr, ExprHeader, Header};
+use crate::{arena::EntryId, with_header};
+
+with_header! {
+    #[derive(Debug, Clone, Serialize)]
+    pub struct Try<'src> {
+        pub body: EntryId,
+        pub catch_param: Define<'src>,
+        pub catch_body: EntryId,
+        /// Runs after the body or the catch body however it is left, whether it ends, throws,
+        /// or is left by a `break` or `return`.
+        pub finally_body: Option<EntryId>,
+    }
+}
+
+impl<'src> From<Try<'src>> for Expr<'src> {
+    fn from(value: Try<'src>) -> Self {
+        Expr::Try(value)
+    }
+}
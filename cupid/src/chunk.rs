@@ -15,6 +15,8 @@ pub enum Instruction {
     Divide,
     Equal,
     False,
+    /// Records a field declared in the body of the class on top of the stack.
+    Field(u8),
     GetGlobal(u8),
     GetLocal(u8),
     GetProperty(u8),
@@ -34,6 +36,13 @@ pub enum Instruction {
     Nil,
     Not,
     Pop,
+    PopHandler,
+    /// Unwraps the some or ok on top of the stack and jumps ahead by the offset, or leaves a none
+    /// or an err for the next instruction to return.
+    Propagate(u16),
+    /// Starts a try body: an error thrown before the matching `PopHandler` jumps ahead by the
+    /// offset to the catch body, with the stack cut back to the given number of locals.
+    PushHandler(u16, u8),
     Return,
     RoleImpl(u8),
     SetGlobal(u8),
@@ -42,6 +51,7 @@ pub enum Instruction {
     SetUpvalue(u8),
     Subtract,
     SuperInvoke(u8, u8),
+    Throw,
     True,
 }
 
@@ -53,6 +63,13 @@ pub struct Chunk {
 }
 
 impl Chunk {
+    /// Bytes taken up by the code, constants and line numbers
+    pub fn heap_size(&self) -> usize {
+        self.code.capacity() * std::mem::size_of::<Instruction>()
+            + self.constants.capacity() * std::mem::size_of::<Value>()
+            + self.lines.capacity() * std::mem::size_of::<usize>()
+    }
+
     pub fn write(&mut self, instruction: Instruction, line: usize) -> usize {
         self.code.push(instruction);
         self.lines.push(line);
@@ -77,6 +94,42 @@ impl Chunk {
     }
 }
 
+impl Chunk {
+    /// Lists the instructions one per line, each with its offset, its line and the constant it
+    /// reads, if any.
+    pub fn disassemble(&self) -> String {
+        let lines = self.code.iter().zip(&self.lines).enumerate();
+        let lines = lines.map(|(offset, (&instruction, line))| {
+            let name = format!("{instruction:?}");
+            match self.operand(instruction) {
+                Some(constant) => format!("{offset:04} {line:>4} {name:<20} {constant}\n"),
+                None => format!("{offset:04} {line:>4} {name}\n"),
+            }
+        });
+        lines.collect()
+    }
+
+    fn operand(&self, instruction: Instruction) -> Option<Value> {
+        match instruction {
+            Instruction::Class(index)
+            | Instruction::Closure(index)
+            | Instruction::Constant(index)
+            | Instruction::DefineGlobal(index)
+            | Instruction::Field(index)
+            | Instruction::GetGlobal(index)
+            | Instruction::GetProperty(index)
+            | Instruction::GetSuper(index)
+            | Instruction::Invoke(index, _)
+            | Instruction::Method(index)
+            | Instruction::RoleImpl(index)
+            | Instruction::SetGlobal(index)
+            | Instruction::SetProperty(index)
+            | Instruction::SuperInvoke(index, _) => Some(self.read_constant(index)),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Debug for Chunk {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let exprs: Vec<String> = self.code.iter().map(|expr| format!("{expr:?}")).collect();
@@ -0,0 +1,220 @@
+use std::process;
+
+use crate::{
+    analyze::lint::{Level, LintConfig},
+    diagnostic::ErrorFormat,
+    doc::DocFormat,
+    emit::Emit,
+    format,
+    vm::VmConfig,
+};
+
+pub const USAGE: &str = "\
+Usage: cupid [options] [path]
+       cupid [options] --emit <ast-json|cst-json> <path>
+       cupid fmt [--check] [--width=<width>] <path>
+       cupid doc [--html] <path>
+       cupid test [options] [path]
+       cupid lint [--allow=<lints>] [--warn=<lints>] [--deny=<lints>] <path>
+       cupid explain [code]
+
+Commands:
+    fmt                         Format the file in place
+        --check                 Only check that the file is formatted, failing if it isn't
+        --width=<width>         Wrap lines longer than this (default 80)
+    doc                         Print the documentation of the file's declarations as Markdown
+        --html                  Print it as HTML instead
+    test                        Run the `test_` functions of the Cupid files at the path (default .)
+    lint                        Report likely mistakes in the file, failing if any lint is denied
+        --allow=<lints>         Don't report these comma separated lints, or all of them with 'all'
+        --warn=<lints>          Report these lints (the default)
+        --deny=<lints>          Report these lints as errors
+    explain                     Explain the error code or lint, such as E0102, or list every code
+
+Options:
+    --emit <output>             Print the file's typed AST ('ast-json') or syntax tree ('cst-json')
+                                as JSON instead of running it
+    --error-format=<format>     Print errors and lints as 'human' text (the default) or as 'json',
+                                one object per line
+    --max-call-depth=<depth>    Calls nested deeper than this are a stack overflow (default 1024)
+    --fuel=<instructions>       Stop the program once it has run this many instructions
+    --gc-initial-heap=<size>    Heap size that triggers the first major collection (default 1m)
+    --gc-grow-factor=<factor>   Heap growth factor between major collections (default 2)
+    --gc-nursery-size=<size>    Nursery size that triggers a minor collection (default 256k)
+    --gc-max-heap=<size>        Stop the program once its live objects take up more than this
+    --gc-stress                 Collect on every allocation and verify the heap after each collection
+    --gc-log                    Print a line for every collection
+    --gc-stats                  Print the collector's statistics when the program ends
+    --gc-verify                 Verify the heap after each collection
+
+Sizes are in bytes and accept a k, m or g suffix. Each option can also be set through its
+CUPID_* environment variable, e.g. CUPID_MAX_CALL_DEPTH=4096, CUPID_GC_NURSERY_SIZE=64k,
+CUPID_ERROR_FORMAT=json or CUPID_GC_STRESS=1.";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Runs the file at the path, or the REPL without one.
+    Run,
+    Fmt {
+        check: bool,
+        width: usize,
+    },
+    Doc {
+        format: DocFormat,
+    },
+    /// Runs the tests found at the path, or in the current directory without one.
+    Test,
+    Lint {
+        config: LintConfig,
+    },
+    /// Explains the error code given as the path, or lists every code without one.
+    Explain,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub command: Command,
+    pub path: Option<String>,
+    /// The tree to print instead of running the file.
+    pub emit: Option<Emit>,
+    pub vm: VmConfig,
+}
+
+impl Options {
+    /// Parses the command line arguments (without the program name), with any command line flag
+    /// taking precedence over the environment.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.into_iter().peekable();
+        let command = match args.peek().map(String::as_str) {
+            Some("fmt") => {
+                args.next();
+                Command::Fmt {
+                    check: false,
+                    width: format::DEFAULT_WIDTH,
+                }
+            }
+            Some("doc") => {
+                args.next();
+                Command::Doc {
+                    format: DocFormat::Markdown,
+                }
+            }
+            Some("test") => {
+                args.next();
+                Command::Test
+            }
+            Some("lint") => {
+                args.next();
+                Command::Lint {
+                    config: LintConfig::default(),
+                }
+            }
+            Some("explain") => {
+                args.next();
+                Command::Explain
+            }
+            _ => Command::Run,
+        };
+        let mut options = Options {
+            command,
+            path: None,
+            emit: None,
+            vm: VmConfig::from_env()?,
+        };
+        while let Some(arg) = args.next() {
+            if let Command::Fmt { check, width } = &mut options.command {
+                if arg == "--check" {
+                    *check = true;
+                    continue;
+                } else if let Some(value) = arg.strip_prefix("--width=") {
+                    *width = match value.parse() {
+                        Ok(value) if value > 0 => value,
+                        _ => return Err(format!("Invalid width '{value}'.")),
+                    };
+                    continue;
+                }
+            }
+            if let Command::Doc { format } = &mut options.command {
+                if arg == "--html" {
+                    *format = DocFormat::Html;
+                    continue;
+                }
+            }
+            if let Command::Lint { config } = &mut options.command {
+                let levels = [
+                    ("--allow=", Level::Allow),
+                    ("--warn=", Level::Warn),
+                    ("--deny=", Level::Deny),
+                ];
+                let level = levels
+                    .iter()
+                    .find_map(|(prefix, level)| Some((arg.strip_prefix(prefix)?, *level)));
+                if let Some((ids, level)) = level {
+                    config.set(ids, level)?;
+                    continue;
+                }
+            }
+            if let (Command::Run, Some(value)) = (&options.command, arg.strip_prefix("--emit=")) {
+                options.emit = Some(Emit::from_name(value)?);
+            } else if let (Command::Run, "--emit") = (&options.command, arg.as_str()) {
+                let value = args.next().ok_or("Expected the output to emit.")?;
+                options.emit = Some(Emit::from_name(&value)?);
+            } else if let Some(value) = arg.strip_prefix("--gc-initial-heap=") {
+                options.vm.gc.set_initial_heap(value)?;
+            } else if let Some(value) = arg.strip_prefix("--gc-grow-factor=") {
+                options.vm.gc.set_grow_factor(value)?;
+            } else if let Some(value) = arg.strip_prefix("--gc-nursery-size=") {
+                options.vm.gc.set_nursery_size(value)?;
+            } else if let Some(value) = arg.strip_prefix("--gc-max-heap=") {
+                options.vm.gc.set_max_heap(value)?;
+            } else if let Some(value) = arg.strip_prefix("--max-call-depth=") {
+                options.vm.set_max_call_depth(value)?;
+            } else if let Some(value) = arg.strip_prefix("--fuel=") {
+                options.vm.set_fuel(value)?;
+            } else if let Some(value) = arg.strip_prefix("--error-format=") {
+                options.vm.error_format = ErrorFormat::from_name(value)?;
+            } else if arg == "--gc-stress" {
+                options.vm.gc.stress = true;
+            } else if arg == "--gc-log" {
+                options.vm.gc.log = true;
+            } else if arg == "--gc-stats" {
+                options.vm.gc.stats = true;
+            } else if arg == "--gc-verify" {
+                options.vm.gc.verify = true;
+            } else if arg.starts_with("--") {
+                return Err(format!("Unknown option '{arg}'."));
+            } else if options.path.is_none() {
+                options.path = Some(arg);
+            } else {
+                return Err(format!("Unexpected argument '{arg}'."));
+            }
+        }
+        match (&options.command, &options.path) {
+            (Command::Fmt { .. }, None) => {
+                return Err("Expected the path of a file to format.".to_string())
+            }
+            (Command::Doc { .. }, None) => {
+                return Err("Expected the path of a file to document.".to_string())
+            }
+            (Command::Lint { .. }, None) => {
+                return Err("Expected the path of a file to lint.".to_string())
+            }
+            (Command::Run, None) if options.emit.is_some() => {
+                return Err("Expected the path of a file to emit.".to_string())
+            }
+            _ => (),
+        }
+        Ok(options)
+    }
+
+    /// Parses the arguments of the current process, exiting with a usage error if they are invalid.
+    pub fn from_args() -> Self {
+        match Self::parse(std::env::args().skip(1)) {
+            Ok(options) => options,
+            Err(error) => {
+                eprintln!("{error}\n\n{USAGE}");
+                process::exit(64);
+            }
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! Stable codes for every diagnostic, along with the long-form explanations printed by
+//! `cupid explain <code>`. Codes are grouped by range: `E00xx` for syntax, `E01xx` for names,
+//! `E02xx` for types, `E03xx` for calls and the runtime, and `W0xxx` for lints. A code is never
+//! reused once it has been given out.
+
+use std::{fmt, process};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Code {
+    UnexpectedToken,
+    InvalidLintDirective,
+    MisplacedKeyword,
+    LimitExceeded,
+    UnsupportedOperator,
+    InvalidRename,
+    UndefinedVariable,
+    UndefinedProperty,
+    DuplicateLocal,
+    LocalInOwnInitializer,
+    NotCallable,
+    NotAnInstance,
+    InvalidOperands,
+    NotAClass,
+    InvalidPropagation,
+    ArityMismatch,
+    StackOverflow,
+    Internal,
+    Io,
+    OutOfFuel,
+    HeapLimit,
+    Interrupted,
+    Uncaught,
+    IndexOutOfBounds,
+    AssertionFailed,
+    UnusedVariable,
+    UnusedParameter,
+    ShadowedLocal,
+    UnreachableCode,
+    UnreadField,
+    EmptyBlock,
+    ConstantComparison,
+}
+
+impl Code {
+    pub const ALL: [Code; 32] = [
+        Code::UnexpectedToken,
+        Code::InvalidLintDirective,
+        Code::MisplacedKeyword,
+        Code::LimitExceeded,
+        Code::UnsupportedOperator,
+        Code::InvalidRename,
+        Code::UndefinedVariable,
+        Code::UndefinedProperty,
+        Code::DuplicateLocal,
+        Code::LocalInOwnInitializer,
+        Code::NotCallable,
+        Code::NotAnInstance,
+        Code::InvalidOperands,
+        Code::NotAClass,
+        Code::InvalidPropagation,
+        Code::ArityMismatch,
+        Code::StackOverflow,
+        Code::Internal,
+        Code::Io,
+        Code::OutOfFuel,
+        Code::HeapLimit,
+        Code::Interrupted,
+        Code::Uncaught,
+        Code::IndexOutOfBounds,
+        Code::AssertionFailed,
+        Code::UnusedVariable,
+        Code::UnusedParameter,
+        Code::ShadowedLocal,
+        Code::UnreachableCode,
+        Code::UnreadField,
+        Code::EmptyBlock,
+        Code::ConstantComparison,
+    ];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Code::UnexpectedToken => "E0001",
+            Code::InvalidLintDirective => "E0002",
+            Code::MisplacedKeyword => "E0003",
+            Code::LimitExceeded => "E0004",
+            Code::UnsupportedOperator => "E0005",
+            Code::InvalidRename => "E0101",
+            Code::UndefinedVariable => "E0102",
+            Code::UndefinedProperty => "E0103",
+            Code::DuplicateLocal => "E0104",
+            Code::LocalInOwnInitializer => "E0105",
+            Code::NotCallable => "E0201",
+            Code::NotAnInstance => "E0202",
+            Code::InvalidOperands => "E0203",
+            Code::NotAClass => "E0204",
+            Code::InvalidPropagation => "E0205",
+            Code::ArityMismatch => "E0301",
+            Code::StackOverflow => "E0302",
+            Code::Internal => "E0303",
+            Code::Io => "E0304",
+            Code::OutOfFuel => "E0305",
+            Code::HeapLimit => "E0306",
+            Code::Interrupted => "E0307",
+            Code::Uncaught => "E0308",
+            Code::IndexOutOfBounds => "E0309",
+            Code::AssertionFailed => "E0310",
+            Code::UnusedVariable => "W0001",
+            Code::UnusedParameter => "W0002",
+            Code::ShadowedLocal => "W0003",
+            Code::UnreachableCode => "W0004",
+            Code::UnreadField => "W0005",
+            Code::EmptyBlock => "W0006",
+            Code::ConstantComparison => "W0007",
+        }
+    }
+
+    /// A short description of the problem, shown next to the code in listings.
+    pub fn title(self) -> &'static str {
+        match self {
+            Code::UnexpectedToken => "unexpected token",
+            Code::InvalidLintDirective => "invalid lint directive",
+            Code::MisplacedKeyword => "keyword used where it has no meaning",
+            Code::LimitExceeded => "compiler limit exceeded",
+            Code::UnsupportedOperator => "unsupported operator",
+            Code::InvalidRename => "invalid rename",
+            Code::UndefinedVariable => "undefined variable",
+            Code::UndefinedProperty => "undefined property",
+            Code::DuplicateLocal => "duplicate local",
+            Code::LocalInOwnInitializer => "local read in its own initializer",
+            Code::NotCallable => "call of a value that is not callable",
+            Code::NotAnInstance => "property of a value that is not an instance",
+            Code::InvalidOperands => "invalid operands",
+            Code::NotAClass => "class expected",
+            Code::InvalidPropagation => "`?` in a function that cannot return its value",
+            Code::ArityMismatch => "wrong number of arguments",
+            Code::StackOverflow => "stack overflow",
+            Code::Internal => "internal error",
+            Code::Io => "failed input or output",
+            Code::OutOfFuel => "out of fuel",
+            Code::HeapLimit => "heap limit exceeded",
+            Code::Interrupted => "interrupted",
+            Code::Uncaught => "uncaught error",
+            Code::IndexOutOfBounds => "index out of bounds",
+            Code::AssertionFailed => "failed assertion",
+            Code::UnusedVariable => "unused variable",
+            Code::UnusedParameter => "unused parameter",
+            Code::ShadowedLocal => "shadowed local",
+            Code::UnreachableCode => "unreachable code",
+            Code::UnreadField => "unread field",
+            Code::EmptyBlock => "empty block",
+            Code::ConstantComparison => "constant comparison",
+        }
+    }
+
+    /// The long-form description of the problem, with an example of the mistake and its fix.
+    pub fn explanation(self) -> &'static str {
+        match self {
+            Code::UnexpectedToken => include_str!("code/E0001.md"),
+            Code::InvalidLintDirective => include_str!("code/E0002.md"),
+            Code::MisplacedKeyword => include_str!("code/E0003.md"),
+            Code::LimitExceeded => include_str!("code/E0004.md"),
+            Code::UnsupportedOperator => include_str!("code/E0005.md"),
+            Code::InvalidRename => include_str!("code/E0101.md"),
+            Code::UndefinedVariable => include_str!("code/E0102.md"),
+            Code::UndefinedProperty => include_str!("code/E0103.md"),
+            Code::DuplicateLocal => include_str!("code/E0104.md"),
+            Code::LocalInOwnInitializer => include_str!("code/E0105.md"),
+            Code::NotCallable => include_str!("code/E0201.md"),
+            Code::NotAnInstance => include_str!("code/E0202.md"),
+            Code::InvalidOperands => include_str!("code/E0203.md"),
+            Code::NotAClass => include_str!("code/E0204.md"),
+            Code::InvalidPropagation => include_str!("code/E0205.md"),
+            Code::ArityMismatch => include_str!("code/E0301.md"),
+            Code::StackOverflow => include_str!("code/E0302.md"),
+            Code::Internal => include_str!("code/E0303.md"),
+            Code::Io => include_str!("code/E0304.md"),
+            Code::OutOfFuel => include_str!("code/E0305.md"),
+            Code::HeapLimit => include_str!("code/E0306.md"),
+            Code::Interrupted => include_str!("code/E0307.md"),
+            Code::Uncaught => include_str!("code/E0308.md"),
+            Code::IndexOutOfBounds => include_str!("code/E0309.md"),
+            Code::AssertionFailed => include_str!("code/E0310.md"),
+            Code::UnusedVariable => include_str!("code/W0001.md"),
+            Code::UnusedParameter => include_str!("code/W0002.md"),
+            Code::ShadowedLocal => include_str!("code/W0003.md"),
+            Code::UnreachableCode => include_str!("code/W0004.md"),
+            Code::UnreadField => include_str!("code/W0005.md"),
+            Code::EmptyBlock => include_str!("code/W0006.md"),
+            Code::ConstantComparison => include_str!("code/W0007.md"),
+        }
+    }
+
+    /// Finds the code with the given ID, in any case and with or without its leading zeros.
+    pub fn from_id(id: &str) -> Option<Code> {
+        let id = id.trim().to_ascii_uppercase();
+        let (letter, number) = id.split_at(id.len().min(1));
+        let number: u32 = number.parse().ok()?;
+        Code::ALL
+            .iter()
+            .copied()
+            .find(|code| code.id() == format!("{letter}{number:04}"))
+    }
+
+    /// The line pointing at the explanation of this code, printed after the error.
+    pub fn note(self) -> String {
+        format!("For more information about this error, try `cupid explain {self}`.")
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.id())
+    }
+}
+
+/// The text printed by `cupid explain`: the explanation of the code or lint with the given ID, or
+/// every code and its title without one.
+pub fn explain(id: Option<&str>) -> Result<String, String> {
+    let id = match id {
+        Some(id) => id,
+        None => {
+            let codes = Code::ALL.iter().map(|code| format!("{code}  {}\n", code.title()));
+            return Ok(codes.collect());
+        }
+    };
+    let code = Code::from_id(id).or_else(|| {
+        let rule = crate::analyze::lint::Rule::from_id(id)?;
+        Some(rule.code())
+    });
+    match code {
+        Some(code) => Ok(format!("{code}: {}\n\n{}", code.title(), code.explanation())),
+        None => Err(format!("Unknown error code '{id}'.")),
+    }
+}
+
+/// Prints the explanation of the code with the given ID, exiting with a usage error if there is
+/// no such code.
+pub fn explain_command(id: Option<&str>) {
+    match explain(id) {
+        Ok(text) => print!("{text}"),
+        Err(error) => {
+            eprintln!("{error}");
+            process::exit(64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{explain, Code};
+
+    #[test]
+    fn ids_are_unique_and_explained() {
+        for (i, code) in Code::ALL.iter().enumerate() {
+            assert!(Code::ALL[i + 1..].iter().all(|other| other.id() != code.id()));
+            assert_eq!(Code::from_id(code.id()), Some(*code));
+            let explanation = code.explanation();
+            assert!(explanation.contains("```"), "{} has no example", code);
+        }
+    }
+
+    #[test]
+    fn explains_codes_and_lints() {
+        assert_eq!(Code::from_id("e102"), Some(Code::UndefinedVariable));
+        assert_eq!(Code::from_id("E9999"), None);
+        assert_eq!(Code::from_id(""), None);
+        assert!(explain(Some("E0102")).unwrap().starts_with("E0102: undefined variable\n"));
+        assert!(explain(Some("unused_variable")).unwrap().starts_with("W0001: unused variable"));
+        assert!(explain(None).unwrap().lines().any(|line| line == "E0302  stack overflow"));
+        assert!(explain(Some("nope")).is_err());
+    }
+}
@@ -1,6 +1,10 @@
 use std::fmt;
 
+use serde::Serialize;
+
 use crate::{
+    arena::EntryId,
+    code::Code,
     gc::GcRef,
     objects::FunctionUpvalue,
     objects::{Function, Str},
@@ -30,7 +34,15 @@ impl fmt::Debug for Local<'_> {
     }
 }
 
+/// A try or catch body being compiled, which a `return` or `break` out of it has to clean up after.
 #[derive(Debug, Clone, Copy)]
+pub struct TryBody {
+    pub finally_body: Option<EntryId>,
+    /// How many locals there were before the try, the only ones its finally body can see.
+    pub locals: usize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
 pub enum FunctionType {
     Function,
     Initializer,
@@ -45,6 +57,8 @@ pub struct Compiler<'src> {
     pub function_type: FunctionType,
     pub locals: Vec<Local<'src>>,
     pub scope_depth: i32,
+    /// The try and catch bodies being compiled, innermost last.
+    pub handlers: Vec<TryBody>,
 }
 
 impl<'src> Compiler<'src> {
@@ -57,6 +71,7 @@ impl<'src> Compiler<'src> {
             function_type: kind,
             locals: Vec::with_capacity(Compiler::LOCAL_COUNT),
             scope_depth: 0,
+            handlers: vec![],
         };
 
         let token = match kind {
@@ -67,11 +82,18 @@ impl<'src> Compiler<'src> {
         Box::new(compiler)
     }
 
-    pub fn resolve_local(&mut self, name: &str, errors: &mut Vec<&'static str>) -> Option<u8> {
+    pub fn resolve_local(
+        &mut self,
+        name: &str,
+        errors: &mut Vec<(Code, &'static str)>,
+    ) -> Option<u8> {
         for (i, local) in self.locals.iter().enumerate().rev() {
             if name == local.name {
                 if local.depth == -1 {
-                    errors.push("Can't read local variable in its own initializer.");
+                    errors.push((
+                        Code::LocalInOwnInitializer,
+                        "Can't read local variable in its own initializer.",
+                    ));
                 }
                 return Some(i as u8);
             }
@@ -79,7 +101,11 @@ impl<'src> Compiler<'src> {
         None
     }
 
-    pub fn resolve_upvalue(&mut self, name: &str, errors: &mut Vec<&'static str>) -> Option<u8> {
+    pub fn resolve_upvalue(
+        &mut self,
+        name: &str,
+        errors: &mut Vec<(Code, &'static str)>,
+    ) -> Option<u8> {
         if let Some(enclosing) = self.enclosing.as_mut() {
             if let Some(index) = enclosing.resolve_local(name, errors) {
                 enclosing.locals[index as usize].is_captured = true;
@@ -92,7 +118,12 @@ impl<'src> Compiler<'src> {
         None
     }
 
-    pub fn add_upvalue(&mut self, index: u8, is_local: bool, errors: &mut Vec<&'static str>) -> u8 {
+    pub fn add_upvalue(
+        &mut self,
+        index: u8,
+        is_local: bool,
+        errors: &mut Vec<(Code, &'static str)>,
+    ) -> u8 {
         for (i, upvalue) in self.function.upvalues.iter().enumerate() {
             if upvalue.index == index && upvalue.is_local == is_local {
                 return i as u8;
@@ -101,7 +132,7 @@ impl<'src> Compiler<'src> {
         let count = self.function.upvalues.len();
 
         if count == Compiler::LOCAL_COUNT {
-            errors.push("Too many closure variables in function.");
+            errors.push((Code::LimitExceeded, "Too many closure variables in function."));
             return 0;
         }
 
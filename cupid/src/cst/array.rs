@@ -1,8 +1,10 @@
+use serde::Serialize;
+
 use crate::token::Token;
 
 use super::{expr::ExprSource, HasToken, SourceId};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ArraySource<'src> {
     pub open_bracket: Token<'src>,
     pub close_bracket: Token<'src>,
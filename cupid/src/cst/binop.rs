@@ -1,7 +1,9 @@
+use serde::Serialize;
+
 use super::{ExprSource, HasToken, SourceId};
 use crate::token::Token;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct BinOpSource<'src> {
     pub left_src: SourceId,
     pub right_src: SourceId,
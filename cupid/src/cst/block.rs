@@ -1,7 +1,9 @@
+use serde::Serialize;
+
 use super::{ExprSource, HasToken, SourceId};
 use crate::token::Token;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum BlockSource<'src> {
     ArrowBlock(ArrowBlockSource<'src>),
     BraceBlock(BraceBlockSource<'src>),
@@ -16,7 +18,7 @@ impl<'src> HasToken<'src> for BlockSource<'src> {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ArrowBlockSource<'src> {
     pub arrow: Token<'src>,
     pub body_src: SourceId,
@@ -28,7 +30,7 @@ impl<'src> HasToken<'src> for ArrowBlockSource<'src> {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct BraceBlockSource<'src> {
     pub open_brace: Token<'src>,
     pub close_brace: Token<'src>,
@@ -1,7 +1,9 @@
+use serde::Serialize;
+
 use super::{ExprSource, HasToken, SourceId};
 use crate::token::Token;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct BreakSource<'src> {
     pub break_kw: Token<'src>,
     pub value_src: Option<SourceId>,
@@ -1,7 +1,9 @@
+use serde::Serialize;
+
 use super::{ExprSource, HasToken, SourceId};
 use crate::token::Token;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct CallSource<'src> {
     pub open_paren: Token<'src>,
     pub close_paren: Token<'src>,
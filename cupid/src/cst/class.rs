@@ -1,7 +1,9 @@
+use serde::Serialize;
+
 use super::{ExprSource, HasToken, SourceId};
 use crate::token::Token;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ClassSource<'src> {
     pub open_brace: Token<'src>,
     pub close_brace: Token<'src>,
@@ -0,0 +1,11 @@
+use serde::Serialize;
+
+use crate::token::Token;
+
+/// The tokens of code that doesn't parse, from the start of the first top level expression that
+/// fails to the end of the file. They are kept as they were scanned, trivia included, so that a
+/// file with a syntax error still prints back as it was written.
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorSource<'src> {
+    pub tokens: Vec<Token<'src>>,
+}
@@ -1,10 +1,33 @@
+use serde::Serialize;
+
+use crate::{arena::ExprArena, span::Span, token::Token};
+
 use super::{
-    array::ArraySource, binop::BinOpSource, block::BlockSource, call::CallSource,
-    class::ClassSource, constant::ConstantSource, define::DefineSource, fun::FunSource,
-    get::GetSource, get_property::GetPropertySource, get_super::GetSuperSource,
-    invoke::InvokeSource, invoke_super::InvokeSuperSource, r#break::BreakSource, r#if::IfSource,
-    r#loop::LoopSource, r#return::ReturnSource, set::SetSource, set_property::SetPropertySource,
+    array::ArraySource,
+    binop::BinOpSource,
+    block::{ArrowBlockSource, BlockSource, BraceBlockSource},
+    call::CallSource,
+    class::ClassSource,
+    constant::ConstantSource,
+    define::DefineSource,
+    fun::FunSource,
+    get::GetSource,
+    get_property::GetPropertySource,
+    get_super::GetSuperSource,
+    invoke::InvokeSource,
+    invoke_super::InvokeSuperSource,
+    propagate::PropagateSource,
+    push_tokens,
+    r#break::BreakSource,
+    r#if::IfSource,
+    r#loop::{ForLoopSource, LoopSource, PlainLoopSource, WhileLoopSource},
+    r#return::ReturnSource,
+    r#try::TrySource,
+    set::SetSource,
+    set_property::SetPropertySource,
+    throw::ThrowSource,
     unop::UnOpSource,
+    SourceId,
 };
 
 pub trait UnwrapEnum<T> {
@@ -14,12 +37,12 @@ pub trait UnwrapEnum<T> {
 macro_rules! unwrappable {
     (
 
-        #[derive(Debug)]
+        #[derive( $($der:ident),* )]
         pub enum $name:ident <'src> {
             $( $variant:ident ( $inner:ty ) ),* $(,)?
         }
     ) => {
-        #[derive(Debug)]
+        #[derive( $($der),* )]
         pub enum $name <'src> {
             $( $variant ($inner) ),*
         }
@@ -42,7 +65,7 @@ macro_rules! unwrappable {
 }
 
 unwrappable! {
-    #[derive(Debug)]
+    #[derive(Debug, Serialize)]
     pub enum ExprSource<'src> {
         Array(ArraySource<'src>),
         BinOp(BinOpSource<'src>),
@@ -60,9 +83,293 @@ unwrappable! {
         InvokeSuper(InvokeSuperSource<'src>),
         Invoke(InvokeSource<'src>),
         Loop(LoopSource<'src>),
+        Propagate(PropagateSource<'src>),
         Return(ReturnSource<'src>),
         SetProperty(SetPropertySource<'src>),
         Set(SetSource<'src>),
+        Throw(ThrowSource<'src>),
+        Try(TrySource<'src>),
         UnOp(UnOpSource<'src>),
     }
 }
+
+impl<'src> ExprSource<'src> {
+    /// The token that best identifies the expression, used to point at it in diagnostics.
+    pub fn token(&self) -> Token<'src> {
+        match self {
+            Self::Array(array) => array.open_bracket,
+            Self::BinOp(binop) => binop.op,
+            Self::Block(BlockSource::ArrowBlock(block)) => block.arrow,
+            Self::Block(BlockSource::BraceBlock(block)) => block.open_brace,
+            Self::Break(brk) => brk.break_kw,
+            Self::Call(call) => call.open_paren,
+            Self::Class(class) => class.name,
+            Self::Constant(constant) => constant.value,
+            Self::Define(define) => define.name,
+            Self::Fun(fun) => fun.name.unwrap_or(fun.fun_kw),
+            Self::GetProperty(get) => get.property,
+            Self::GetSuper(get) => get.name,
+            Self::Get(get) => get.name,
+            Self::If(stmt) => stmt.if_kw,
+            Self::InvokeSuper(invoke) => invoke.name,
+            Self::Invoke(invoke) => invoke.callee,
+            Self::Loop(stmt) => stmt.keyword(),
+            Self::Propagate(propagate) => propagate.question,
+            Self::Return(ret) => ret.return_kw,
+            Self::SetProperty(set) => set.property,
+            Self::Set(set) => set.name,
+            Self::Throw(throw) => throw.throw_kw,
+            Self::Try(stmt) => stmt.try_kw,
+            Self::UnOp(unop) => unop.op,
+        }
+    }
+
+    /// The first token of the expression, operands and the parentheses around them included.
+    pub fn first_token(&self, arena: &ExprArena<'src>) -> Token<'src> {
+        let first = |id| match arena.source.groups(id).last() {
+            Some(group) => group.open_paren,
+            None => arena.expect_source(id).first_token(arena),
+        };
+        match self {
+            Self::BinOp(binop) => first(binop.left_src),
+            Self::Call(call) => first(call.callee_src),
+            Self::Class(class) => class.class_kw,
+            Self::Define(define) => define.let_kw.unwrap_or(define.name),
+            Self::Fun(fun) if fun.fun_kw.span.is_synthetic() => fun.name.unwrap_or(fun.open_paren),
+            Self::Fun(fun) => fun.fun_kw,
+            Self::GetProperty(get) => first(get.receiver),
+            Self::Propagate(propagate) => first(propagate.value_src),
+            Self::Invoke(invoke) => first(invoke.receiver),
+            Self::SetProperty(set) => first(set.receiver),
+            _ => self.token(),
+        }
+    }
+
+    /// The last token of the expression, operands and the parentheses around them included.
+    pub fn last_token(&self, arena: &ExprArena<'src>) -> Token<'src> {
+        let last = |id| match arena.source.groups(id).last() {
+            Some(group) => group.close_paren,
+            None => arena.expect_source(id).last_token(arena),
+        };
+        match self {
+            Self::Array(array) => array.close_bracket,
+            Self::BinOp(binop) => last(binop.right_src),
+            Self::Block(BlockSource::ArrowBlock(block)) => last(block.body_src),
+            Self::Block(BlockSource::BraceBlock(block)) => block.close_brace,
+            Self::Break(brk) => brk.value_src.map(last).unwrap_or(brk.break_kw),
+            Self::Call(call) => call.close_paren,
+            Self::Class(class) => class.close_brace,
+            Self::Constant(constant) => constant.value,
+            Self::Define(define) => define.value_src.map(last).unwrap_or(define.name),
+            Self::Fun(fun) => last(fun.body_src),
+            Self::GetProperty(get) => get.property,
+            Self::GetSuper(get) => get.name,
+            Self::Get(get) => get.name,
+            Self::If(stmt) => last(stmt.else_body_src.unwrap_or(stmt.body_src)),
+            Self::InvokeSuper(invoke) => invoke.close_paren,
+            Self::Invoke(invoke) => invoke.close_paren,
+            Self::Loop(stmt) => last(stmt.body_src()),
+            Self::Propagate(propagate) => propagate.question,
+            Self::Return(ret) => ret.value_src.map(last).unwrap_or(ret.return_kw),
+            Self::SetProperty(set) => last(set.value),
+            Self::Set(set) => last(set.value_src),
+            Self::Throw(throw) => last(throw.value_src),
+            Self::Try(stmt) => last(stmt.finally_body_src.unwrap_or(stmt.catch_body_src)),
+            Self::UnOp(unop) => last(unop.expr_src),
+        }
+    }
+
+    /// Pushes the tokens of the expression to `tokens` in the order of the code, leaving out the
+    /// ones made up by the parser.
+    pub fn push_tokens(&self, arena: &ExprArena<'src>, tokens: &mut Vec<Token<'src>>) {
+        let token = |token: Token<'src>, tokens: &mut Vec<Token<'src>>| {
+            if !token.span.is_synthetic() {
+                tokens.push(token);
+            }
+        };
+        let list = |items: &[SourceId], commas: &[Token<'src>], tokens: &mut Vec<Token<'src>>| {
+            for (i, item) in items.iter().enumerate() {
+                push_tokens(*item, arena, tokens);
+                tokens.extend(commas.get(i));
+            }
+        };
+        match self {
+            Self::Array(array) => {
+                token(array.open_bracket, tokens);
+                list(&array.items_src, &array.commas, tokens);
+                token(array.close_bracket, tokens);
+            }
+            Self::BinOp(binop) => {
+                push_tokens(binop.left_src, arena, tokens);
+                token(binop.op, tokens);
+                push_tokens(binop.right_src, arena, tokens);
+            }
+            Self::Block(BlockSource::ArrowBlock(ArrowBlockSource { arrow, body_src })) => {
+                token(*arrow, tokens);
+                push_tokens(*body_src, arena, tokens);
+            }
+            Self::Block(BlockSource::BraceBlock(BraceBlockSource {
+                open_brace,
+                close_brace,
+                body_src,
+            })) => {
+                token(*open_brace, tokens);
+                body_src.iter().for_each(|id| push_tokens(*id, arena, tokens));
+                token(*close_brace, tokens);
+            }
+            Self::Break(brk) => {
+                token(brk.break_kw, tokens);
+                if let Some(id) = brk.value_src {
+                    push_tokens(id, arena, tokens);
+                }
+            }
+            Self::Call(call) => {
+                push_tokens(call.callee_src, arena, tokens);
+                token(call.open_paren, tokens);
+                list(&call.args_src, &call.commas, tokens);
+                token(call.close_paren, tokens);
+            }
+            Self::Class(class) => {
+                token(class.class_kw, tokens);
+                token(class.name, tokens);
+                tokens.extend(class.super_class);
+                tokens.extend(class.super_class_name);
+                token(class.open_brace, tokens);
+                class.fields.iter().for_each(|id| push_tokens(*id, arena, tokens));
+                class.methods.iter().for_each(|id| push_tokens(*id, arena, tokens));
+                token(class.close_brace, tokens);
+            }
+            Self::Constant(constant) => token(constant.value, tokens),
+            Self::Define(define) => {
+                tokens.extend(define.let_kw);
+                token(define.name, tokens);
+                tokens.extend(define.equal);
+                if let Some(id) = define.value_src {
+                    push_tokens(id, arena, tokens);
+                }
+            }
+            Self::Fun(fun) => {
+                token(fun.fun_kw, tokens);
+                tokens.extend(fun.name);
+                token(fun.open_paren, tokens);
+                list(&fun.params_src, &fun.commas, tokens);
+                token(fun.close_paren, tokens);
+                push_tokens(fun.body_src, arena, tokens);
+            }
+            Self::GetProperty(get) => {
+                push_tokens(get.receiver, arena, tokens);
+                token(get.dot, tokens);
+                token(get.property, tokens);
+            }
+            Self::GetSuper(get) => token(get.name, tokens),
+            Self::Get(get) => token(get.name, tokens),
+            Self::If(stmt) => {
+                token(stmt.if_kw, tokens);
+                push_tokens(stmt.condition_src, arena, tokens);
+                push_tokens(stmt.body_src, arena, tokens);
+                tokens.extend(stmt.else_kw);
+                if let Some(id) = stmt.else_body_src {
+                    push_tokens(id, arena, tokens);
+                }
+            }
+            Self::InvokeSuper(invoke) => {
+                token(invoke.name, tokens);
+                token(invoke.open_paren, tokens);
+                list(&invoke.args, &invoke.commas, tokens);
+                token(invoke.close_paren, tokens);
+            }
+            Self::Invoke(invoke) => {
+                push_tokens(invoke.receiver, arena, tokens);
+                token(invoke.dot, tokens);
+                token(invoke.callee, tokens);
+                token(invoke.open_paren, tokens);
+                list(&invoke.args, &invoke.commas, tokens);
+                token(invoke.close_paren, tokens);
+            }
+            Self::Loop(LoopSource::Plain(PlainLoopSource { loop_kw, body_src })) => {
+                token(*loop_kw, tokens);
+                push_tokens(*body_src, arena, tokens);
+            }
+            Self::Loop(LoopSource::While(WhileLoopSource {
+                while_kw,
+                condition_src,
+                body_src,
+            })) => {
+                token(*while_kw, tokens);
+                push_tokens(*condition_src, arena, tokens);
+                push_tokens(*body_src, arena, tokens);
+            }
+            Self::Loop(LoopSource::For(for_loop)) => {
+                let ForLoopSource {
+                    for_kw,
+                    open_paren,
+                    init_src,
+                    condition_src,
+                    condition_semicolon,
+                    increment_src,
+                    close_paren,
+                    body_src,
+                } = **for_loop;
+                token(for_kw, tokens);
+                token(open_paren, tokens);
+                push_tokens(init_src, arena, tokens);
+                push_tokens(condition_src, arena, tokens);
+                token(condition_semicolon, tokens);
+                push_tokens(increment_src, arena, tokens);
+                token(close_paren, tokens);
+                push_tokens(body_src, arena, tokens);
+            }
+            Self::Propagate(propagate) => {
+                push_tokens(propagate.value_src, arena, tokens);
+                token(propagate.question, tokens);
+            }
+            Self::Return(ret) => {
+                token(ret.return_kw, tokens);
+                if let Some(id) = ret.value_src {
+                    push_tokens(id, arena, tokens);
+                }
+            }
+            Self::SetProperty(set) => {
+                push_tokens(set.receiver, arena, tokens);
+                token(set.dot, tokens);
+                token(set.property, tokens);
+                token(set.equal, tokens);
+                push_tokens(set.value, arena, tokens);
+            }
+            Self::Set(set) => {
+                token(set.name, tokens);
+                token(set.equal, tokens);
+                push_tokens(set.value_src, arena, tokens);
+            }
+            Self::Throw(throw) => {
+                token(throw.throw_kw, tokens);
+                push_tokens(throw.value_src, arena, tokens);
+            }
+            Self::Try(stmt) => {
+                token(stmt.try_kw, tokens);
+                push_tokens(stmt.body_src, arena, tokens);
+                token(stmt.catch_kw, tokens);
+                token(stmt.open_paren, tokens);
+                push_tokens(stmt.catch_param_src, arena, tokens);
+                token(stmt.close_paren, tokens);
+                push_tokens(stmt.catch_body_src, arena, tokens);
+                tokens.extend(stmt.finally_kw);
+                if let Some(id) = stmt.finally_body_src {
+                    push_tokens(id, arena, tokens);
+                }
+            }
+            Self::UnOp(unop) => {
+                token(unop.op, tokens);
+                push_tokens(unop.expr_src, arena, tokens);
+            }
+        }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        let span = self.token().span;
+        match span.is_synthetic() {
+            true => None,
+            false => Some(span),
+        }
+    }
+}
@@ -0,0 +1,11 @@
+use serde::Serialize;
+
+use crate::token::Token;
+
+/// Parentheses around an expression. They only change how the code is parsed, so they are kept
+/// beside the syntax tree rather than in it.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct GroupSource<'src> {
+    pub open_paren: Token<'src>,
+    pub close_paren: Token<'src>,
+}
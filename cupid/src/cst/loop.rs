@@ -1,20 +1,114 @@
+use serde::Serialize;
+
 use super::{ExprSource, HasToken, SourceId};
 use crate::token::Token;
 
-#[derive(Debug, Clone)]
-pub struct LoopSource<'src> {
+/// `while` and `for` loops are turned into plain loops by the parser, but their sources keep what
+/// was written.
+#[derive(Debug, Clone, Serialize)]
+pub enum LoopSource<'src> {
+    Plain(PlainLoopSource<'src>),
+    While(WhileLoopSource<'src>),
+    For(Box<ForLoopSource<'src>>),
+}
+
+impl<'src> LoopSource<'src> {
+    /// The `loop`, `while` or `for` keyword.
+    pub fn keyword(&self) -> Token<'src> {
+        match self {
+            Self::Plain(inner) => inner.loop_kw,
+            Self::While(inner) => inner.while_kw,
+            Self::For(inner) => inner.for_kw,
+        }
+    }
+
+    pub fn body_src(&self) -> SourceId {
+        match self {
+            Self::Plain(inner) => inner.body_src,
+            Self::While(inner) => inner.body_src,
+            Self::For(inner) => inner.body_src,
+        }
+    }
+}
+
+impl<'src> HasToken<'src> for LoopSource<'src> {
+    fn has_token(&self, token: Token<'src>) -> bool {
+        match self {
+            Self::Plain(inner) => inner.has_token(token),
+            Self::While(inner) => inner.has_token(token),
+            Self::For(inner) => inner.has_token(token),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlainLoopSource<'src> {
     pub loop_kw: Token<'src>,
     pub body_src: SourceId,
 }
 
-impl<'src> HasToken<'src> for LoopSource<'src> {
+impl<'src> HasToken<'src> for PlainLoopSource<'src> {
     fn has_token(&self, token: Token<'src>) -> bool {
         self.loop_kw == token
     }
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct WhileLoopSource<'src> {
+    pub while_kw: Token<'src>,
+    pub condition_src: SourceId,
+    pub body_src: SourceId,
+}
+
+impl<'src> HasToken<'src> for WhileLoopSource<'src> {
+    fn has_token(&self, token: Token<'src>) -> bool {
+        self.while_kw == token
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ForLoopSource<'src> {
+    pub for_kw: Token<'src>,
+    pub open_paren: Token<'src>,
+    /// The definition, which ends with the first `;`: the `;` is trivia like any other ending a
+    /// statement.
+    pub init_src: SourceId,
+    pub condition_src: SourceId,
+    pub condition_semicolon: Token<'src>,
+    pub increment_src: SourceId,
+    pub close_paren: Token<'src>,
+    pub body_src: SourceId,
+}
+
+impl<'src> HasToken<'src> for ForLoopSource<'src> {
+    fn has_token(&self, token: Token<'src>) -> bool {
+        self.for_kw == token
+            || self.open_paren == token
+            || self.condition_semicolon == token
+            || self.close_paren == token
+    }
+}
+
 impl<'src> From<LoopSource<'src>> for ExprSource<'src> {
     fn from(value: LoopSource<'src>) -> Self {
-        ExprSource::Loop(value.into())
+        ExprSource::Loop(value)
+    }
+}
+
+impl<'src> From<PlainLoopSource<'src>> for ExprSource<'src> {
+    fn from(value: PlainLoopSource<'src>) -> Self {
+        LoopSource::Plain(value).into()
+    }
+}
+
+impl<'src> From<WhileLoopSource<'src>> for ExprSource<'src> {
+    fn from(value: WhileLoopSource<'src>) -> Self {
+        LoopSource::While(value).into()
+    }
+}
+
+impl<'src> From<ForLoopSource<'src>> for ExprSource<'src> {
+    fn from(value: ForLoopSource<'src>) -> Self {
+        LoopSource::For(Box::new(value)).into()
     }
 }
@@ -1,7 +1,9 @@
+use serde::Serialize;
+
 use super::{fun::FunSource, HasToken};
 use crate::token::Token;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct MethodSource<'src> {
     pub name: Token<'src>,
     pub fun: FunSource<'src>,
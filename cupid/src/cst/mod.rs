@@ -1,10 +1,14 @@
+use std::collections::BTreeMap;
+
+use serde::{ser::SerializeMap, Serialize, Serializer};
+
 use crate::{
-    arena::{Arena, Entry, EntryId, UseArena},
+    arena::{Arena, Entry, EntryId, ExprArena, UseArena},
     for_expr_variant,
     token::Token,
 };
 
-use self::expr::ExprSource;
+use self::{error::ErrorSource, expr::ExprSource, group::GroupSource};
 
 pub mod array;
 pub mod binop;
@@ -14,24 +18,40 @@ pub mod call;
 pub mod class;
 pub mod constant;
 pub mod define;
+pub mod error;
 pub mod expr;
 pub mod fun;
 pub mod get;
 pub mod get_property;
 pub mod get_super;
+pub mod group;
 pub mod r#if;
 pub mod invoke;
 pub mod invoke_super;
 pub mod r#loop;
 pub mod method;
+pub mod propagate;
 pub mod r#return;
 pub mod set;
 pub mod set_property;
+pub mod throw;
+pub mod r#try;
 pub mod unop;
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
 pub struct SourceId(pub EntryId);
 
+/// Serialized as a reference to the source of the expression, `{"$source": 3}`, which
+/// [`crate::emit`] replaces with the source itself in the syntax tree, and with its span in the
+/// typed AST.
+impl Serialize for SourceId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry("$source", &*self.0)?;
+        map.end()
+    }
+}
+
 impl From<EntryId> for SourceId {
     fn from(value: EntryId) -> Self {
         Self(value)
@@ -54,19 +74,75 @@ impl<'src> HasToken<'src> for ExprSource<'src> {
     }
 }
 
+/// The expressions of a parsed file, along with the end of file token which holds the trivia that
+/// follows them.
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceFile<'src> {
+    pub exprs: Vec<SourceId>,
+    /// The code after the expressions, when some of it doesn't parse.
+    pub error: Option<ErrorSource<'src>>,
+    pub eof: Token<'src>,
+}
+
+impl<'src> SourceFile<'src> {
+    /// The tokens of the file in the order of the code, leaving out the ones made up by the parser.
+    pub fn tokens(&self, arena: &ExprArena<'src>) -> Vec<Token<'src>> {
+        let mut tokens = vec![];
+        for id in &self.exprs {
+            push_tokens(*id, arena, &mut tokens);
+        }
+        if let Some(error) = &self.error {
+            tokens.extend(&error.tokens);
+        }
+        tokens.push(self.eof);
+        tokens
+    }
+
+    /// Prints the file back from its tokens and their trivia, which gives the exact code it was
+    /// parsed from.
+    pub fn print(&self, arena: &ExprArena<'src>) -> String {
+        let mut code = String::new();
+        for token in self.tokens(arena) {
+            code.push_str(token.leading);
+            code.push_str(token.lexeme);
+            code.push_str(token.trailing);
+        }
+        code
+    }
+}
+
+/// Pushes the tokens of the expression written at `id` to `tokens`, parentheses around it included.
+pub fn push_tokens<'src>(id: SourceId, arena: &ExprArena<'src>, tokens: &mut Vec<Token<'src>>) {
+    let groups = arena.source.groups(id);
+    tokens.extend(groups.iter().rev().map(|group| group.open_paren));
+    arena.expect_source(id).push_tokens(arena, tokens);
+    tokens.extend(groups.iter().map(|group| group.close_paren));
+}
+
 pub struct SourceArena<'src> {
     pub arena: Arena<ExprSource<'src>>,
+    /// The parentheses written around expressions, innermost first.
+    pub groups: BTreeMap<SourceId, Vec<GroupSource<'src>>>,
 }
 
 impl<'src> Default for SourceArena<'src> {
     fn default() -> Self {
         Self {
             arena: Arena { entries: vec![] },
+            groups: BTreeMap::new(),
         }
     }
 }
 
 impl<'src> SourceArena<'src> {
+    pub fn group(&mut self, id: SourceId, group: GroupSource<'src>) {
+        self.groups.entry(id).or_default().push(group);
+    }
+
+    pub fn groups(&self, id: SourceId) -> &[GroupSource<'src>] {
+        self.groups.get(&id).map_or(&[], Vec::as_slice)
+    }
+
     pub fn find(&self, token: Token<'src>) -> Option<&ExprSource<'src>> {
         self.arena.entries.iter().find_map(|entry| match &entry.value {
             Some(value) if value.has_token(token) => Some(value),
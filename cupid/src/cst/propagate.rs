@@ -0,0 +1,22 @@
+use serde::Serialize;
+
+use super::{ExprSource, HasToken, SourceId};
+use crate::token::Token;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PropagateSource<'src> {
+    pub value_src: SourceId,
+    pub question: Token<'src>,
+}
+
+impl<'src> HasToken<'src> for PropagateSource<'src> {
+    fn has_token(&self, token: Token<'src>) -> bool {
+        self.question == token
+    }
+}
+
+impl<'src> From<PropagateSource<'src>> for ExprSource<'src> {
+    fn from(value: PropagateSource<'src>) -> Self {
+        ExprSource::Propagate(value)
+    }
+}
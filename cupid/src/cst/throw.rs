@@ -0,0 +1,22 @@
+use serde::Serialize;
+
+use super::{ExprSource, HasToken, SourceId};
+use crate::token::Token;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ThrowSource<'src> {
+    pub throw_kw: Token<'src>,
+    pub value_src: SourceId,
+}
+
+impl<'src> HasToken<'src> for ThrowSource<'src> {
+    fn has_token(&self, token: Token<'src>) -> bool {
+        self.throw_kw == token
+    }
+}
+
+impl<'src> From<ThrowSource<'src>> for ExprSource<'src> {
+    fn from(value: ThrowSource<'src>) -> Self {
+        ExprSource::Throw(value)
+    }
+}
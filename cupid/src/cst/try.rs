@@ -0,0 +1,33 @@
+use serde::Serialize;
+
+use super::{ExprSource, HasToken, SourceId};
+use crate::token::Token;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrySource<'src> {
+    pub try_kw: Token<'src>,
+    pub body_src: SourceId,
+    pub catch_kw: Token<'src>,
+    pub open_paren: Token<'src>,
+    pub catch_param_src: SourceId,
+    pub close_paren: Token<'src>,
+    pub catch_body_src: SourceId,
+    pub finally_kw: Option<Token<'src>>,
+    pub finally_body_src: Option<SourceId>,
+}
+
+impl<'src> HasToken<'src> for TrySource<'src> {
+    fn has_token(&self, token: Token<'src>) -> bool {
+        self.try_kw == token
+            || self.catch_kw == token
+            || self.open_paren == token
+            || self.close_paren == token
+            || self.finally_kw == Some(token)
+    }
+}
+
+impl<'src> From<TrySource<'src>> for ExprSource<'src> {
+    fn from(value: TrySource<'src>) -> Self {
+        ExprSource::Try(value)
+    }
+}
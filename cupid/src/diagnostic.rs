@@ -0,0 +1,233 @@
+//! Machine-readable diagnostics. With `--error-format=json`, every error and lint is printed to
+//! stderr as one JSON object per line instead of as colored text:
+//!
+//! ```text
+//! {"code":"E0102","kind":"runtime","severity":"error","message":"Undefined variable 'amont'.",
+//!  "spans":[{"file":"main.cupid","primary":true,"label":"in script","line_start":2,...}],
+//!  "suggestions":["did you mean `amount`?"]}
+//! ```
+//!
+//! Columns and lines start at 1, and byte offsets are into the file, with `byte_end` exclusive.
+//! Runtime errors only know the lines of their call stack, so their spans have no columns or
+//! offsets.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{
+    code::Code,
+    error::{CupidError, Kind, Severity},
+    span::Span,
+    vm::Frames,
+};
+
+/// How errors and lints are printed.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// Colored text for people.
+    #[default]
+    Human,
+    /// One JSON object per line for tools.
+    Json,
+}
+
+impl ErrorFormat {
+    pub const VAR: &'static str = "CUPID_ERROR_FORMAT";
+
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.trim() {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(format!("Invalid error format '{name}': expected 'human' or 'json'.")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub kind: String,
+    pub severity: String,
+    pub message: String,
+    /// The primary span first, if the error has a location, followed by the secondary ones.
+    pub spans: Vec<DiagnosticSpan>,
+    pub suggestions: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiagnosticSpan {
+    pub file: Option<String>,
+    pub primary: bool,
+    pub label: Option<String>,
+    pub line_start: usize,
+    pub line_end: usize,
+    pub column_start: Option<usize>,
+    pub column_end: Option<usize>,
+    pub byte_start: Option<usize>,
+    pub byte_end: Option<usize>,
+}
+
+impl DiagnosticSpan {
+    fn new(file: Option<&str>, span: Span, primary: bool, label: Option<String>) -> Self {
+        Self {
+            file: file.map(str::to_string),
+            primary,
+            label,
+            line_start: span.start.line,
+            line_end: span.end.line,
+            column_start: Some(span.start.col),
+            column_end: Some(span.end.col),
+            byte_start: Some(span.start.index),
+            byte_end: Some(span.end.index),
+        }
+    }
+
+    fn line(file: Option<&str>, line: usize, primary: bool, label: String) -> Self {
+        Self {
+            file: file.map(str::to_string),
+            primary,
+            label: Some(label),
+            line_start: line,
+            line_end: line,
+            column_start: None,
+            column_end: None,
+            byte_start: None,
+            byte_end: None,
+        }
+    }
+}
+
+impl Diagnostic {
+    /// The diagnostic for an error found in `file` before running it.
+    pub fn from_error(error: &CupidError, file: Option<&str>) -> Self {
+        let primary = error.span.map(|span| DiagnosticSpan::new(file, span, true, None));
+        let secondary = error
+            .secondary
+            .iter()
+            .map(|(span, label)| DiagnosticSpan::new(file, *span, false, Some(label.clone())));
+        Self {
+            code: error.code.id(),
+            kind: error.kind.to_string(),
+            severity: error.severity.to_string(),
+            message: error.message.clone(),
+            spans: primary.into_iter().chain(secondary).collect(),
+            suggestions: error.help.iter().cloned().collect(),
+        }
+    }
+
+    /// The diagnostic for an error raised while running `file`: the line being run is its primary
+    /// span, and the lines of the calls that led there are the secondary ones.
+    pub fn runtime(
+        code: Code,
+        message: impl ToString,
+        help: Option<String>,
+        frames: &Frames,
+        file: Option<&str>,
+    ) -> Self {
+        let spans = frames.calls().into_iter().enumerate().map(|(i, (line, name, count))| {
+            let label = match count {
+                1 => format!("in {name}"),
+                _ => format!("in {name} (repeated {count} times)"),
+            };
+            DiagnosticSpan::line(file, line, i == 0, label)
+        });
+        Self {
+            code: code.id(),
+            kind: Kind::Runtime.to_string(),
+            severity: Severity::Error.to_string(),
+            message: message.to_string(),
+            spans: spans.collect(),
+            suggestions: help.into_iter().collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Diagnostics always serialize")
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    /// The diagnostic as plain text: `error[E0102]: Undefined variable 'x'.`, followed by where it
+    /// happened and any suggestions.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)?;
+        if let Some(span) = self.spans.first().filter(|span| span.primary) {
+            let file = span.file.as_deref().unwrap_or("<eval>");
+            match span.column_start {
+                Some(column) => write!(f, "\n  --> {}:{}:{}", file, span.line_start, column)?,
+                None => write!(f, "\n  --> {}:{}", file, span.line_start)?,
+            }
+        }
+        for suggestion in &self.suggestions {
+            write!(f, "\nhelp: {suggestion}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The errors that kept a program from compiling or running to its end.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Diagnostics {
+    /// The diagnostic of a single error, found in `file` before running it.
+    pub fn from_error(error: &CupidError, file: Option<&str>) -> Self {
+        Self(vec![Diagnostic::from_error(error, file)])
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, diagnostic) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{diagnostic}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// Prints `error`, found in the file at `path`, to stderr in the given format.
+pub fn report(error: &CupidError, format: ErrorFormat, path: &str) {
+    match (format, error.span) {
+        (ErrorFormat::Human, Some(span)) => {
+            eprintln!("{}  --> {}:{}:{}\n", error, path, span.start.line, span.start.col)
+        }
+        (ErrorFormat::Human, None) => eprintln!("{error}"),
+        (ErrorFormat::Json, _) => {
+            eprintln!("{}", Diagnostic::from_error(error, Some(path)).to_json())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Diagnostic;
+    use crate::analyze::lint::{lint_source, LintConfig};
+
+    #[test]
+    fn serializes_errors_one_per_line() {
+        let code = "fun f () {\n    let unused = 1\n}\n";
+        let lints = lint_source(code, &LintConfig::default()).unwrap();
+        let json = Diagnostic::from_error(&lints[0], Some("main.cupid")).to_json();
+        assert!(!json.contains('\n'));
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["code"], "W0001");
+        assert_eq!(value["kind"], "unused_variable");
+        assert_eq!(value["severity"], "warning");
+        let span = &value["spans"][0];
+        assert_eq!(span["file"], "main.cupid");
+        assert_eq!(span["primary"], true);
+        assert_eq!(
+            (span["line_start"].as_u64(), span["column_start"].as_u64()),
+            (Some(2), Some(9))
+        );
+        let (start, end) =
+            (span["byte_start"].as_u64().unwrap(), span["byte_end"].as_u64().unwrap());
+        assert_eq!(&code[start as usize..end as usize], "unused");
+    }
+}
@@ -0,0 +1,442 @@
+use std::{fs, process};
+
+use crate::{
+    arena::ExprArena,
+    ast::{expr::GetSource, Class, Define, DocComment, Expr, Fun, GetTy},
+    error::CupidError,
+    gc::Gc,
+    parse::parser::Parser,
+    pointer::Pointer,
+    run::do_passes,
+    scope::symbol::Symbol,
+    ty::Type,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocFormat {
+    Markdown,
+    Html,
+}
+
+/// A declaration, as it is documented.
+#[derive(Debug, Clone, PartialEq)]
+struct Item {
+    name: String,
+    /// `[return_type] name (params)` for functions and methods, `[type] name` for variables and
+    /// fields, `class Name < SuperClass` for classes.
+    signature: String,
+    line: usize,
+    doc: Option<String>,
+    /// The fields and methods of a class.
+    members: Vec<Item>,
+}
+
+impl Item {
+    /// The heading of the item, with a link to its source.
+    fn heading(&self, path: &str) -> String {
+        format!("`{}` [source]({}#L{})", self.signature, path, self.line)
+    }
+
+    /// The anchor of the heading, the way Markdown renderers make it up from the heading's text.
+    fn anchor(&self) -> String {
+        let text = format!("{} source", self.signature).to_lowercase();
+        text.chars()
+            .filter_map(|char| match char {
+                ' ' => Some('-'),
+                '-' | '_' => Some(char),
+                _ if char.is_alphanumeric() => Some(char),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// The declarations of a file, by kind.
+#[derive(Debug, Default)]
+struct Items {
+    functions: Vec<Item>,
+    classes: Vec<Item>,
+    variables: Vec<Item>,
+}
+
+/// Documents the functions and classes declared at the top level of `code`, along with the
+/// variables that have a doc comment. Each declaration is shown as `[return_type] name (params)`
+/// with the types that could be inferred, followed by its `##` doc comment and a link to its line
+/// in `path`.
+pub fn document_source(code: &str, path: &str, format: DocFormat) -> Result<String, CupidError> {
+    let mut gc = Gc::default();
+    let mut parser = Parser::new(code);
+    let exprs = parser.parse(&mut gc)?;
+    let exprs = do_passes(exprs, &mut parser.arena).map_err(|err| err.locate(&parser.arena))?;
+    let mut items = Items::default();
+    for expr in &exprs {
+        let arena = &parser.arena;
+        match expr {
+            Expr::Class(class) => items.classes.push(class_item(class, arena)),
+            Expr::Fun(fun) if fun.name.is_some() => items.functions.push(fun_item(fun, arena)),
+            Expr::Define(define) => match define.value.map(|value| arena.expect_expr(value)) {
+                Some(Expr::Fun(fun)) => items.functions.push(Item {
+                    name: define.name.to_string(),
+                    signature: fun_signature(define.name, fun, false, arena),
+                    ..define_item(define, arena)
+                }),
+                _ if define.doc.is_some() => items.variables.push(define_item(define, arena)),
+                _ => (),
+            },
+            _ => (),
+        }
+    }
+    Ok(match format {
+        DocFormat::Markdown => markdown(&items, path),
+        DocFormat::Html => html(&items, path),
+    })
+}
+
+/// Prints the documentation of the file at `path`.
+pub fn document_file(path: &str, format: DocFormat) {
+    let code = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) => {
+            eprintln!("Unable to read file {}: {}", path, error);
+            process::exit(74);
+        }
+    };
+    match document_source(&code, path, format) {
+        Ok(docs) => print!("{docs}"),
+        Err(error) => {
+            eprintln!("{error}");
+            process::exit(65);
+        }
+    }
+}
+
+fn line<'src>(expr: &impl GetSource<'src>, arena: &ExprArena<'src>) -> usize {
+    expr.source(arena).first_token(arena).span.start.line
+}
+
+fn doc_text(doc: Option<DocComment>) -> Option<String> {
+    doc.map(|doc| doc.text())
+}
+
+fn class_item<'src>(class: &Class<'src>, arena: &ExprArena<'src>) -> Item {
+    let signature = match class.super_class {
+        Some(super_class) => format!("class {} < {}", class.name, super_class),
+        None => format!("class {}", class.name),
+    };
+    let fields = class.fields.iter().map(|field| define_item(field, arena));
+    let methods = class.methods.iter().map(|method| Item {
+        name: method.name.to_string(),
+        signature: fun_signature(method.name, &method.fun, true, arena),
+        ..fun_item(&method.fun, arena)
+    });
+    Item {
+        name: class.name.to_string(),
+        signature,
+        line: line(class, arena),
+        doc: doc_text(class.doc),
+        members: fields.chain(methods).collect(),
+    }
+}
+
+fn fun_item<'src>(fun: &Fun<'src>, arena: &ExprArena<'src>) -> Item {
+    let name = fun.name.unwrap_or_default();
+    Item {
+        name: name.to_string(),
+        signature: fun_signature(name, fun, false, arena),
+        line: line(fun, arena),
+        doc: doc_text(fun.doc),
+        members: vec![],
+    }
+}
+
+fn define_item<'src>(define: &Define<'src>, arena: &ExprArena<'src>) -> Item {
+    Item {
+        name: define.name.to_string(),
+        signature: format!("[{}] {}", type_name(symbol_ty(&define.symbol), arena), define.name),
+        line: line(define, arena),
+        doc: doc_text(define.doc),
+        members: vec![],
+    }
+}
+
+fn fun_signature<'src>(
+    name: &str,
+    fun: &Fun<'src>,
+    is_method: bool,
+    arena: &ExprArena<'src>,
+) -> String {
+    let returns = match fun.ty() {
+        Type::Function { returns } => type_name(*arena.expect_ty(returns), arena),
+        _ => type_name(Type::Unknown, arena),
+    };
+    let params = fun.params.iter().map(|param| match symbol_ty(&param.symbol) {
+        Type::Unknown => param.name.to_string(),
+        ty => format!("{} {}", type_name(ty, arena), param.name),
+    });
+    let params: Vec<String> = match is_method {
+        true => std::iter::once("self".to_string()).chain(params).collect(),
+        false => params.collect(),
+    };
+    format!("[{}] {} ({})", returns, name, params.join(", "))
+}
+
+fn symbol_ty<'src>(symbol: &Option<Pointer<Symbol<'src>>>) -> Type<'src> {
+    match symbol {
+        Some(symbol) => symbol.borrow().ty,
+        None => Type::Unknown,
+    }
+}
+
+/// The name of a type, as it is written in the documentation.
+fn type_name<'src>(ty: Type<'src>, arena: &ExprArena<'src>) -> String {
+    match ty {
+        Type::Array(items) => format!("array<{}>", type_name(*arena.expect_ty(items), arena)),
+        Type::Bool => "bool".to_string(),
+        Type::Class(class) => format!("class<{}>", class.0),
+        Type::Int => "int".to_string(),
+        Type::Float => "float".to_string(),
+        Type::Nil | Type::Unit => "nil".to_string(),
+        Type::String => "string".to_string(),
+        Type::Instance(class) => class.0.to_string(),
+        Type::Function { returns } => {
+            format!("fun<{}>", type_name(*arena.expect_ty(returns), arena))
+        }
+        Type::Option(some) => format!("option<{}>", type_name(*arena.expect_ty(some), arena)),
+        Type::Result { ok, err } => format!(
+            "result<{}, {}>",
+            type_name(*arena.expect_ty(ok), arena),
+            type_name(*arena.expect_ty(err), arena)
+        ),
+        Type::Unknown => "any".to_string(),
+        Type::Type => "type".to_string(),
+    }
+}
+
+fn markdown(items: &Items, path: &str) -> String {
+    let mut out = format!("# `{path}`\n");
+    let sections = [
+        ("Functions", &items.functions),
+        ("Classes", &items.classes),
+        ("Variables", &items.variables),
+    ];
+    for (title, items) in sections {
+        if items.is_empty() {
+            continue;
+        }
+        out += &format!("\n## {title}\n");
+        for item in items {
+            out += &format!("\n### {}\n", item.heading(path));
+            if let Some(doc) = &item.doc {
+                out += &format!("\n{doc}\n");
+            }
+            if !item.members.is_empty() {
+                out.push('\n');
+            }
+            for member in &item.members {
+                out += &format!("1. [{}](#{})\n", member.name, member.anchor());
+            }
+            for member in &item.members {
+                out += &format!("\n#### {}\n", member.heading(path));
+                if let Some(doc) = &member.doc {
+                    out += &format!("\n{doc}\n");
+                }
+            }
+        }
+    }
+    out
+}
+
+fn html(items: &Items, path: &str) -> String {
+    let path = escape(path);
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{path}</title>\n\
+        </head>\n<body>\n<h1><code>{path}</code></h1>\n"
+    );
+    let heading = |level: usize, item: &Item| {
+        format!(
+            "<h{level} id=\"{}\"><code>{}</code> <a href=\"{}#L{}\">source</a></h{level}>\n{}",
+            item.anchor(),
+            escape(&item.signature),
+            path,
+            item.line,
+            item.doc.as_deref().map(html_text).unwrap_or_default(),
+        )
+    };
+    let sections = [
+        ("Functions", &items.functions),
+        ("Classes", &items.classes),
+        ("Variables", &items.variables),
+    ];
+    for (title, items) in sections {
+        if items.is_empty() {
+            continue;
+        }
+        out += &format!("<h2>{title}</h2>\n");
+        for item in items {
+            out += &heading(3, item);
+            if !item.members.is_empty() {
+                out += "<ol>\n";
+                for member in &item.members {
+                    out +=
+                        &format!("<li><a href=\"#{}\">{}</a></li>\n", member.anchor(), member.name);
+                }
+                out += "</ol>\n";
+            }
+            for member in &item.members {
+                out += &heading(4, member);
+            }
+        }
+    }
+    out + "</body>\n</html>\n"
+}
+
+/// Converts the Markdown of a doc comment to HTML: paragraphs, fenced code blocks and inline code.
+fn html_text(doc: &str) -> String {
+    let mut out = String::new();
+    let mut paragraph: Vec<&str> = vec![];
+    let mut code: Option<Vec<&str>> = None;
+    let end_paragraph = |paragraph: &mut Vec<&str>, out: &mut String| {
+        if !paragraph.is_empty() {
+            *out += &format!("<p>{}</p>\n", inline_code(&escape(&paragraph.join("\n"))));
+            paragraph.clear();
+        }
+    };
+    for line in doc.lines() {
+        match (&mut code, line.trim_start().starts_with("```")) {
+            (Some(lines), true) => {
+                out += &format!("<pre><code>{}</code></pre>\n", escape(&lines.join("\n")));
+                code = None;
+            }
+            (Some(lines), false) => lines.push(line),
+            (None, true) => {
+                end_paragraph(&mut paragraph, &mut out);
+                code = Some(vec![]);
+            }
+            (None, false) if line.trim().is_empty() => end_paragraph(&mut paragraph, &mut out),
+            (None, false) => paragraph.push(line),
+        }
+    }
+    if let Some(lines) = code {
+        out += &format!("<pre><code>{}</code></pre>\n", escape(&lines.join("\n")));
+    }
+    end_paragraph(&mut paragraph, &mut out);
+    out
+}
+
+/// Wraps the text between pairs of backticks in `<code>` tags.
+fn inline_code(text: &str) -> String {
+    let mut out = String::new();
+    for (i, part) in text.split('`').enumerate() {
+        match i % 2 {
+            0 if i > 0 => out += &format!("</code>{part}"),
+            0 => out += part,
+            _ => out += &format!("<code>{part}"),
+        }
+    }
+    if text.matches('`').count() % 2 == 1 {
+        out += "</code>";
+    }
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{document_source, DocFormat};
+
+    const CODE: &str = "\
+## Adds `a` and `b`.
+fun add (a, b) {
+    return a + b
+}
+
+-- Not documented.
+class Point {
+    ## How far right.
+    let x = 0
+
+    ## The distance to the origin, squared.
+    norm () {
+        return self.x * self.x
+    }
+}
+
+## The answer.
+let answer = 42
+let hidden = 1
+";
+
+    #[test]
+    fn documents_declarations_as_markdown() {
+        let docs = document_source(CODE, "point.cupid", DocFormat::Markdown).unwrap();
+        assert_eq!(
+            docs,
+            "\
+# `point.cupid`
+
+## Functions
+
+### `[any] add (a, b)` [source](point.cupid#L2)
+
+Adds `a` and `b`.
+
+## Classes
+
+### `class Point` [source](point.cupid#L7)
+
+1. [x](#int-x-source)
+1. [norm](#any-norm-self-source)
+
+#### `[int] x` [source](point.cupid#L9)
+
+How far right.
+
+#### `[any] norm (self)` [source](point.cupid#L12)
+
+The distance to the origin, squared.
+
+## Variables
+
+### `[int] answer` [source](point.cupid#L18)
+
+The answer.
+"
+        );
+    }
+
+    #[test]
+    fn documents_declarations_as_html() {
+        let docs = document_source(CODE, "point.cupid", DocFormat::Html).unwrap();
+        assert!(docs.contains(
+            "<h3 id=\"any-add-a-b-source\"><code>[any] add (a, b)</code> \
+            <a href=\"point.cupid#L2\">source</a></h3>\n<p>Adds <code>a</code> and <code>b</code>.</p>"
+        ));
+        assert!(docs.contains("<li><a href=\"#int-x-source\">x</a></li>"));
+        assert!(!docs.contains("hidden"));
+    }
+
+    #[test]
+    fn names_option_and_result_types() {
+        let code = "\
+fun find (items) {
+    if len(items) == 0 {
+        return none
+    }
+    return some(1)
+}
+
+fun parse (s) {
+    return err(s)
+}
+";
+        let docs = document_source(code, "find.cupid", DocFormat::Markdown).unwrap();
+        assert!(docs.contains("### `[option<int>] find (items)`"));
+        assert!(docs.contains("### `[result<any, any>] parse (s)`"));
+    }
+}
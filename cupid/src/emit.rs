@@ -0,0 +1,207 @@
+//! Exports of the compiler's trees as JSON, for tools and the playground to render. The nodes are
+//! serialized as they are, except that the references between them (`{"$expr": 3}` for
+//! expressions and `{"$source": 3}` for their sources) are replaced by the nodes they point at, so
+//! that each tree comes out nested. The `source` of a typed expression is replaced by the span of
+//! the code it was parsed from, or null for the ones the compiler makes up.
+
+use std::{fs, process};
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    arena::{EntryId, ExprArena, UseArena},
+    ast::Expr,
+    cst::SourceId,
+    diagnostic::{self, ErrorFormat},
+    error::CupidError,
+    gc::Gc,
+    parse::parser::Parser,
+    run::do_passes,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Emit {
+    /// The typed AST, after name resolution and type inference.
+    AstJson,
+    /// The concrete syntax tree, with every token and its trivia.
+    CstJson,
+}
+
+impl Emit {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "ast-json" => Ok(Emit::AstJson),
+            "cst-json" => Ok(Emit::CstJson),
+            _ => Err(format!("Unknown output '{name}': expected 'ast-json' or 'cst-json'.")),
+        }
+    }
+}
+
+/// The checked, typed expressions of `code`.
+pub fn ast_json(code: &str) -> Result<Value, CupidError> {
+    let mut gc = Gc::default();
+    let mut parser = Parser::new(code);
+    let exprs = parser.parse(&mut gc)?;
+    let exprs = do_passes(exprs, &mut parser.arena).map_err(|err| err.locate(&parser.arena))?;
+    Ok(exprs_json(&exprs, &parser.arena))
+}
+
+/// The JSON of expressions that have already been checked, with the nodes they refer to in `arena`
+/// nested into them.
+pub fn exprs_json(exprs: &[Expr], arena: &ExprArena) -> Value {
+    let exprs = exprs.iter().map(|expr| {
+        let expr = serde_json::to_value(expr).expect("Expressions always serialize");
+        let expr = nest(expr, "$expr", &|id| {
+            let expr = UseArena::<Expr>::expect(arena, EntryId(id));
+            serde_json::to_value(expr).expect("Expressions always serialize")
+        });
+        nest(expr, "$source", &|id| span_json(arena, SourceId(EntryId(id))))
+    });
+    Value::Array(exprs.collect())
+}
+
+/// The span of a source, from its first token to its last, or null if the parser made it up.
+fn span_json(arena: &ExprArena, id: SourceId) -> Value {
+    let source = arena.expect_source(id);
+    let (first, last) = (source.first_token(arena).span, source.last_token(arena).span);
+    match first.is_synthetic() || last.is_synthetic() {
+        true => Value::Null,
+        false => serde_json::to_value(first.to(last)).expect("Spans always serialize"),
+    }
+}
+
+/// The syntax tree of `code` as it is written, before it is desugared and checked. Parentheses
+/// around an expression are listed in its `groups`.
+pub fn cst_json(code: &str) -> Result<Value, CupidError> {
+    let mut gc = Gc::default();
+    let mut parser = Parser::new(code);
+    let file = parser.parse_source(&mut gc)?;
+    let arena = &parser.arena;
+    let file = serde_json::to_value(&file).expect("Sources always serialize");
+    Ok(nest(file, "$source", &|id| source_json(arena, SourceId(EntryId(id)))))
+}
+
+fn source_json(arena: &ExprArena, id: SourceId) -> Value {
+    let mut source =
+        serde_json::to_value(arena.expect_source(id)).expect("Sources always serialize");
+    let groups = arena.source.groups(id);
+    if let (false, Value::Object(variant)) = (groups.is_empty(), &mut source) {
+        for (_, fields) in variant.iter_mut() {
+            if let Value::Object(fields) = fields {
+                fields.insert("groups".to_string(), json!(groups));
+            }
+        }
+    }
+    source
+}
+
+/// Replaces every `{marker: id}` object in `value`, however deep, with the node `resolve` gives
+/// for the ID, itself nested the same way.
+fn nest(value: Value, marker: &str, resolve: &dyn Fn(usize) -> Value) -> Value {
+    match value {
+        Value::Object(object) => {
+            if let (1, Some(id)) = (object.len(), object.get(marker).and_then(Value::as_u64)) {
+                return nest(resolve(id as usize), marker, resolve);
+            }
+            let object: Map<String, Value> = object
+                .into_iter()
+                .map(|(key, value)| (key, nest(value, marker, resolve)))
+                .collect();
+            Value::Object(object)
+        }
+        Value::Array(items) => {
+            Value::Array(items.into_iter().map(|item| nest(item, marker, resolve)).collect())
+        }
+        value => value,
+    }
+}
+
+/// Prints the tree of the file at `path` as JSON, exiting with an error if the file doesn't parse
+/// or check.
+pub fn emit_file(path: &str, emit: Emit, format: ErrorFormat) {
+    let code = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) => {
+            eprintln!("Unable to read file {}: {}", path, error);
+            process::exit(74);
+        }
+    };
+    let tree = match emit {
+        Emit::AstJson => ast_json(&code),
+        Emit::CstJson => cst_json(&code),
+    };
+    match tree {
+        Ok(tree) => println!("{tree}"),
+        Err(error) => {
+            diagnostic::report(&error, format, path);
+            process::exit(65);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::{ast_json, cst_json};
+
+    #[test]
+    fn nests_typed_expressions() {
+        let ast = ast_json("let a = 1 + 2\n").unwrap();
+        let define = &ast[0]["Define"];
+        assert_eq!(define["name"], "a");
+        assert_eq!(define["header"]["scope_depth"], 0);
+        let sum = &define["value"]["BinOp"];
+        assert_eq!(sum["header"]["ty"], "Int");
+        assert_eq!(sum["op"], "Plus");
+        assert_eq!(sum["left"]["Constant"]["value"], 1);
+    }
+
+    #[test]
+    fn locates_typed_expressions() {
+        fn visit<'a>(value: &'a Value, sources: &mut Vec<&'a Value>) {
+            match value {
+                Value::Object(object) => {
+                    if let Some(header) = object.get("header") {
+                        sources.push(&header["source"]);
+                    }
+                    object.values().for_each(|value| visit(value, sources));
+                }
+                Value::Array(items) => items.iter().for_each(|item| visit(item, sources)),
+                _ => (),
+            }
+        }
+        let code = "class A {\n    let n = 1\n    get () => self.n\n}\nlet a = A()\n\
+            a.n = 2 * (a.get() + 3)\nwhile a.n > 0 {\n    a.n = a.n - 1\n}\n";
+        let ast = ast_json(code).unwrap();
+        let text = |source: &Value| {
+            let (start, end) = (&source["start"]["index"], &source["end"]["index"]);
+            &code[start.as_u64().unwrap() as usize..end.as_u64().unwrap() as usize]
+        };
+        let mut sources = vec![];
+        visit(&ast, &mut sources);
+        assert!(sources.len() > 20);
+        for source in sources.iter().filter(|source| !source.is_null()) {
+            assert!(!text(source).is_empty(), "{}", source);
+        }
+
+        let set = &ast[2]["SetProperty"];
+        assert_eq!(text(&set["header"]["source"]), "a.n = 2 * (a.get() + 3)");
+        let product = &set["value"]["BinOp"];
+        assert_eq!(text(&product["header"]["source"]), "2 * (a.get() + 3)");
+        assert_eq!(text(&product["left"]["Constant"]["header"]["source"]), "2");
+        assert_eq!(text(&product["right"]["BinOp"]["header"]["source"]), "a.get() + 3");
+    }
+
+    #[test]
+    fn nests_sources_with_their_tokens() {
+        let code = "log((1))\n";
+        let cst = cst_json(code).unwrap();
+        let call = &cst["exprs"][0]["Call"];
+        assert_eq!(call["callee_src"]["Get"]["name"]["lexeme"], "log");
+        let constant = &call["args_src"][0]["Constant"];
+        assert_eq!(constant["value"]["span"]["start"]["index"], 5);
+        assert_eq!(constant["groups"][0]["open_paren"]["lexeme"], "(");
+        assert_eq!(cst["eof"]["kind"], "Eof");
+    }
+}
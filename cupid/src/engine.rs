@@ -0,0 +1,240 @@
+//! The interpreter as a library, for programs that embed Cupid:
+//!
+//! ```
+//! use cupid::{engine::Engine, value::Value};
+//!
+//! let mut engine = Engine::new();
+//! engine.eval("fun double (x) { return x * 2 }").unwrap();
+//! assert_eq!(engine.call_global("double", &[Value::Int(21)]).unwrap(), Value::Int(42));
+//! ```
+//!
+//! Each call to [`Engine::eval`] runs in the same vm, so the globals defined by one are seen by the
+//! next. Errors are returned as [`Diagnostics`] instead of being printed, while `log` still prints
+//! to stdout unless the vm is given another stream, such as a [`Buffer`](crate::vm::Buffer), with
+//! [`Vm::set_stdout`].
+
+use std::{cell::RefCell, fmt, fs, io, path::Path};
+
+use crate::{
+    diagnostic::Diagnostics,
+    error::CupidErr,
+    gc::GcRef,
+    objects::{Class, Foreign, Function, Str},
+    parse::{bytecode::BytecodeCompiler, parser::Parser},
+    run::do_passes,
+    scope::Lookup,
+    value::Value,
+    vm::{Vm, VmConfig},
+};
+
+/// Why [`Engine::run_file`] failed.
+#[derive(Debug)]
+pub enum FileError {
+    Unreadable(io::Error),
+    Diagnostics(Diagnostics),
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileError::Unreadable(error) => write!(f, "Unable to read file: {error}"),
+            FileError::Diagnostics(diagnostics) => write!(f, "{diagnostics}"),
+        }
+    }
+}
+
+impl std::error::Error for FileError {}
+
+impl From<Diagnostics> for FileError {
+    fn from(diagnostics: Diagnostics) -> Self {
+        FileError::Diagnostics(diagnostics)
+    }
+}
+
+pub struct Engine {
+    vm: Vm,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::with_config(VmConfig::default())
+    }
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_config(config: VmConfig) -> Self {
+        let mut vm = Vm::with_config(config);
+        vm.initialize();
+        vm.diagnostics = Some(RefCell::default());
+        Self { vm }
+    }
+
+    /// The vm running the code, for settings that the engine doesn't expose.
+    pub fn vm(&mut self) -> &mut Vm {
+        &mut self.vm
+    }
+
+    /// Runs `code`, returning the value of its last top level expression, or nil if it ends with a
+    /// statement such as a definition.
+    pub fn eval(&mut self, code: &str) -> Result<Value, Diagnostics> {
+        let function = self.compile(code)?;
+        let base = self.vm.stack.len();
+        let result = self.vm.interpret_function(function);
+        // Left on the stack by the script's return
+        let value = result.map(|()| self.vm.stack.peek(0));
+        self.finish(base, value)
+    }
+
+    /// Runs the file at `path` like [`Engine::eval`], naming it in the diagnostics.
+    pub fn run_file(&mut self, path: impl AsRef<Path>) -> Result<Value, FileError> {
+        let path = path.as_ref();
+        let code = fs::read_to_string(path).map_err(FileError::Unreadable)?;
+        let previous = self.vm.path.replace(path.display().to_string());
+        let result = self.eval(&code);
+        self.vm.path = previous;
+        Ok(result?)
+    }
+
+    /// Calls the function, class or native defined as the global `name` with `args`, returning
+    /// what it returns.
+    pub fn call_global(&mut self, name: &str, args: &[Value]) -> Result<Value, Diagnostics> {
+        let callee = match self.global(name) {
+            Some(callee) => callee,
+            None => {
+                let name = self.vm.intern(name.to_string());
+                let result = self.vm.undefined_variable(name);
+                return self.finish(self.vm.stack.len(), result.map(|()| Value::Nil));
+            }
+        };
+        let base = self.vm.stack.len();
+        let result = self.vm.call(callee, args);
+        self.finish(base, result)
+    }
+
+    /// The value of the global `name`, if it is defined.
+    pub fn global(&self, name: &str) -> Option<Value> {
+        self.globals().find(|(global, _)| global.s == name).map(|(_, value)| value)
+    }
+
+    /// Defines the global `name` or, if it is already defined, changes its value. Code evaluated
+    /// afterwards can refer to it, but its type is unknown to the checks.
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        // Keeps the value alive while the name is interned
+        self.vm.stack.push(value);
+        let name = self.vm.intern(name.to_string());
+        self.vm.stack.pop();
+        self.vm.globals.set(name, value);
+    }
+
+    /// Every global with its name, natives included.
+    pub fn globals(&self) -> impl Iterator<Item = (GcRef<Str>, Value)> + '_ {
+        self.vm.globals.iter()
+    }
+
+    /// Defines the global `name` as a native taking `arity` arguments. Unlike the builtins, it may
+    /// capture state, like a counter shared with the host.
+    pub fn register_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&mut Vm, &[Value]) -> Result<Value, CupidErr> + 'static,
+    ) {
+        self.vm.register_native(name, arity, function)
+    }
+
+    /// A class for foreign objects, whose methods are added with [`Engine::register_method`].
+    pub fn foreign_class(&mut self, name: &str) -> GcRef<Class> {
+        self.vm.foreign_class(name)
+    }
+
+    /// Adds the method `name` to a class of foreign objects. The native is given the object itself
+    /// followed by the `arity` arguments.
+    pub fn register_method(
+        &mut self,
+        class: GcRef<Class>,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&mut Vm, &[Value]) -> Result<Value, CupidErr> + 'static,
+    ) {
+        self.vm.register_method(class, name, arity, function)
+    }
+
+    /// Moves a value of the host into the heap, to be handed to Cupid code. It is subject to the
+    /// same caveat as [`Engine::string`].
+    pub fn foreign(&mut self, foreign: Foreign) -> Value {
+        self.vm.foreign(foreign)
+    }
+
+    /// A string value. Like any object, it is only kept alive while it is reachable from a global
+    /// or from running code, so it should be stored or passed on before anything else is
+    /// allocated.
+    pub fn string(&mut self, s: &str) -> Value {
+        Value::String(self.vm.intern(s.to_string()))
+    }
+
+    fn compile(&mut self, code: &str) -> Result<GcRef<Function>, Diagnostics> {
+        let file = self.vm.path.clone();
+        let globals: Vec<String> = self.globals().map(|(name, _)| name.s.clone()).collect();
+        let mut parser = Parser::new(code);
+        {
+            // Globals defined by earlier code, or by the host, are known to this code too
+            let mut scope = parser.scope.borrow_mut();
+            for name in &globals {
+                if scope.lookup_current(name.as_str()).is_none() {
+                    scope.define(name);
+                }
+            }
+        }
+        let exprs = parser
+            .parse(&mut self.vm.gc)
+            .map_err(|error| Diagnostics::from_error(&error, file.as_deref()))?;
+        let exprs = do_passes(exprs, &mut parser.arena).map_err(|error| {
+            Diagnostics::from_error(&error.locate(&parser.arena), file.as_deref())
+        })?;
+        BytecodeCompiler::new(exprs, parser.arena, &mut self.vm.gc)
+            .compile_returning_last()
+            .map_err(|error| Diagnostics::from_error(&error, file.as_deref()))
+    }
+
+    /// Returns the vm to where it was before the run, with `base` values on the stack, and turns
+    /// the errors it collected into diagnostics.
+    fn finish(
+        &mut self,
+        base: usize,
+        result: Result<Value, CupidErr>,
+    ) -> Result<Value, Diagnostics> {
+        self.vm.frames.frames.clear();
+        self.vm.open_upvalues.clear();
+        self.vm.stack.truncate(base);
+        let collected = self.vm.diagnostics.as_ref().map(RefCell::take).unwrap_or_default();
+        result.map_err(|_| Diagnostics(collected))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Engine;
+    use crate::value::Value;
+
+    #[test]
+    fn keeps_globals_between_evaluations() {
+        let mut engine = Engine::new();
+        assert_eq!(engine.eval("let total = 40\n").unwrap(), Value::Nil);
+        assert_eq!(engine.eval("total + 2\n").unwrap(), Value::Int(42));
+        assert_eq!(engine.global("total"), Some(Value::Int(40)));
+        assert_eq!(engine.vm().stack.len(), 0);
+    }
+
+    #[test]
+    fn recovers_from_runtime_errors() {
+        let mut engine = Engine::new();
+        let errors = engine.eval("fun f () { f() }\nf()\n").unwrap_err();
+        assert_eq!(errors.0[0].code, "E0302");
+        assert_eq!(engine.vm().frames.count(), 0);
+        assert_eq!(engine.eval("1 + 1\n").unwrap(), Value::Int(2));
+    }
+}
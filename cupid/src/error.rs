@@ -1,8 +1,11 @@
-use std::fmt::{self, Debug, Display, Formatter};
+use std::{
+    fmt::{self, Debug, Display, Formatter},
+    ops::{Deref, DerefMut},
+};
 
 use cupid_fmt::color;
 
-use crate::token::StaticToken;
+use crate::{arena::ExprArena, code::Code, cst::SourceId, span::Span, token::StaticToken};
 
 #[derive(Debug, Copy, Clone)]
 pub enum CupidErr {
@@ -17,6 +20,8 @@ pub enum Kind {
     Name,
     Syntax,
     Runtime,
+    /// A lint, with the ID of its rule.
+    Lint(&'static str),
 }
 
 impl Display for Kind {
@@ -27,6 +32,7 @@ impl Display for Kind {
             Self::Name => write!(f, "name"),
             Self::Syntax => write!(f, "syntax"),
             Self::Runtime => write!(f, "runtime"),
+            Self::Lint(rule) => write!(f, "{rule}"),
         }
     }
 }
@@ -52,12 +58,37 @@ pub trait Reportable: Display + Debug {}
 
 impl<T: Display + Debug> Reportable for T {}
 
+/// An error, with its details boxed so that the `Result`s it's returned in stay small.
 #[derive(Debug)]
-pub struct CupidError {
+pub struct CupidError(Box<ErrorDetails>);
+
+#[derive(Debug)]
+pub struct ErrorDetails {
+    pub code: Code,
     pub kind: Kind,
     pub severity: Severity,
     pub message: String,
     pub data: Vec<Box<dyn Reportable>>,
+    pub span: Option<Span>,
+    pub source: Option<SourceId>,
+    /// A hint on how to fix the error, such as the name that was likely meant.
+    pub help: Option<String>,
+    /// Other places that take part in the error, each with a note on the part it plays.
+    pub secondary: Vec<(Span, String)>,
+}
+
+impl Deref for CupidError {
+    type Target = ErrorDetails;
+
+    fn deref(&self) -> &ErrorDetails {
+        &self.0
+    }
+}
+
+impl DerefMut for CupidError {
+    fn deref_mut(&mut self) -> &mut ErrorDetails {
+        &mut self.0
+    }
 }
 
 impl Display for CupidError {
@@ -65,7 +96,7 @@ impl Display for CupidError {
         write!(
             f,
             "{}",
-            color(format!("{} {}: ", self.kind.to_string(), self.severity.to_string()))
+            color(format!("{} {}[{}]: ", self.kind, self.severity, self.code))
                 .bold()
                 .red()
                 .ok()
@@ -74,39 +105,103 @@ impl Display for CupidError {
         for item in &self.data {
             writeln!(f, "{item}")?;
         }
+        if let Some(help) = &self.help {
+            writeln!(f, "{} {}", color("help:").bold().ok(), help)?;
+        }
         Ok(())
     }
 }
 
 impl CupidError {
+    pub fn new(details: ErrorDetails) -> Self {
+        Self(Box::new(details))
+    }
+
     pub fn parse_error(msg: impl ToString, token: Option<StaticToken>) -> Self {
+        let span = token.as_ref().map(|token| token.span);
         let data: Vec<Box<dyn Reportable>> = match token {
             Some(token) => vec![Box::new(token)],
             None => vec![],
         };
-        Self {
+        Self::new(ErrorDetails {
+            code: Code::UnexpectedToken,
             kind: Kind::Parse,
             severity: Severity::Error,
             message: msg.to_string(),
             data,
-        }
+            span,
+            source: None,
+            help: None,
+            secondary: vec![],
+        })
     }
 
-    pub fn name_error(msg: impl ToString, token: impl Reportable + 'static) -> Self {
-        Self {
-            kind: Kind::Parse,
+    pub fn name_error(code: Code, msg: impl ToString, token: impl Reportable + 'static) -> Self {
+        Self::new(ErrorDetails {
+            code,
+            kind: Kind::Name,
             severity: Severity::Error,
             message: msg.to_string(),
             data: vec![Box::new(token)],
-        }
+            span: None,
+            source: None,
+            help: None,
+            secondary: vec![],
+        })
     }
 
-    pub fn type_error(msg: impl ToString, data: impl Reportable + 'static) -> Self {
-        Self {
+    pub fn type_error(code: Code, msg: impl ToString, data: impl Reportable + 'static) -> Self {
+        Self::new(ErrorDetails {
+            code,
             kind: Kind::Type,
             severity: Severity::Error,
             message: msg.to_string(),
             data: vec![Box::new(data)],
+            span: None,
+            source: None,
+            help: None,
+            secondary: vec![],
+        })
+    }
+
+    /// An error found while compiling to bytecode, located by the expression being compiled.
+    pub fn compile_error(kind: Kind, code: Code, msg: impl ToString) -> Self {
+        Self::new(ErrorDetails {
+            code,
+            kind,
+            severity: Severity::Error,
+            message: msg.to_string(),
+            data: vec![],
+            span: None,
+            source: None,
+            help: None,
+            secondary: vec![],
+        })
+    }
+
+    pub fn with_code(mut self, code: Code) -> Self {
+        self.code = code;
+        self
+    }
+
+    pub fn with_help(mut self, help: Option<String>) -> Self {
+        self.help = help;
+        self
+    }
+
+    /// Records the expression that raised this error, unless a more specific one already has.
+    pub fn at_source(mut self, source: SourceId) -> Self {
+        if self.span.is_none() && self.source.is_none() {
+            self.source = Some(source);
+        }
+        self
+    }
+
+    /// Fills in the span of the error from the source of the expression that raised it.
+    pub fn locate(mut self, arena: &ExprArena) -> Self {
+        if let (None, Some(source)) = (self.span, self.source) {
+            self.span = arena.expect_source(source).span();
         }
+        self
     }
 }
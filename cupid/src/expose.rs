@@ -1,48 +1,230 @@
-use crate::{value::Value, vm::Vm};
+use crate::{
+    code::Code,
+    error::CupidErr,
+    gc::GcRef,
+    objects::{Array, Instance},
+    value::Value,
+    vm::{Runtime, Vm},
+};
+use std::io::BufRead;
 
 pub fn cupid_clock(vm: &Vm, _args: &[Value]) -> Value {
-    let time = vm.start_time.elapsed().unwrap().as_secs_f64();
-    Value::Float(time)
+    Value::Float(vm.clock.seconds())
 }
 
-pub fn cupid_panic(_vm: &Vm, args: &[Value]) -> Value {
-    let mut terms: Vec<String> = vec![];
+/// Throws an `Error` with `message`, which stops the program unless it is caught.
+pub fn cupid_panic(vm: &mut Vm, args: &[Value]) -> Result<Value, CupidErr> {
+    let instance = vm.alloc(Instance::new(vm.error_class));
+    vm.stack.push(Value::Instance(instance));
+    let trace = vm.frames.trace();
+    vm.init_error(instance, args[0], None, &trace);
+    let error = vm.stack.pop();
+    Err(vm.throw(error))
+}
 
-    for &arg in args.iter().filter(|a| **a != Value::Nil) {
-        let term = format!("{}", arg);
-        terms.push(term);
+/// The initializer of `Error`, which keeps the message along with the stack trace of where the
+/// error was made.
+pub fn cupid_error_init(vm: &mut Vm, args: &[Value]) -> Result<Value, CupidErr> {
+    if let Value::Instance(instance) = args[0] {
+        let trace = vm.frames.trace();
+        vm.init_error(instance, args[1], None, &trace);
     }
+    Ok(args[0])
+}
+
+pub fn cupid_some(vm: &mut Vm, args: &[Value]) -> Result<Value, CupidErr> {
+    Ok(vm.wrap(vm.some_class, args[0]))
+}
+
+pub fn cupid_ok(vm: &mut Vm, args: &[Value]) -> Result<Value, CupidErr> {
+    Ok(vm.wrap(vm.ok_class, args[0]))
+}
+
+pub fn cupid_err(vm: &mut Vm, args: &[Value]) -> Result<Value, CupidErr> {
+    Ok(vm.wrap(vm.err_class, args[0]))
+}
+
+/// Adds `item` to the end of `array`, returning its new length.
+pub fn cupid_push(vm: &mut Vm, args: &[Value]) -> Result<Value, CupidErr> {
+    let mut array = expect_array(vm, "push", args[0])?;
+    array.items.push(args[1]);
+    vm.gc.write_barrier(array);
+    vm.gc.resize(array);
+    Ok(Value::Int(array.items.len() as i32))
+}
 
-    panic!("panic: {}", terms.join(", "))
+/// Removes the last item of `array` and returns it, or nil if it is empty.
+pub fn cupid_pop(vm: &mut Vm, args: &[Value]) -> Result<Value, CupidErr> {
+    let mut array = expect_array(vm, "pop", args[0])?;
+    Ok(array.items.pop().unwrap_or(Value::Nil))
 }
 
-pub fn cupid_push(_vm: &Vm, args: &[Value]) -> Value {
+/// The number of items in `array`.
+pub fn cupid_len(vm: &mut Vm, args: &[Value]) -> Result<Value, CupidErr> {
+    let array = expect_array(vm, "len", args[0])?;
+    Ok(Value::Int(array.items.len() as i32))
+}
+
+/// The item of `array` at `index`, or a runtime error if there is none.
+pub fn cupid_get(vm: &mut Vm, args: &[Value]) -> Result<Value, CupidErr> {
+    let array = expect_array(vm, "get", args[0])?;
+    let item = match args[1] {
+        Value::Int(index) if index >= 0 => array.items.get(index as usize).copied(),
+        Value::Int(_) => None,
+        index => {
+            let msg = format!("Expected an int as the index of 'get', not {index}.");
+            return Err(vm.as_runtime_err(Code::InvalidOperands, msg));
+        }
+    };
+    item.ok_or_else(|| {
+        let msg = format!(
+            "Index {} is out of bounds for an array of length {}.",
+            args[1],
+            array.items.len()
+        );
+        vm.as_runtime_err(Code::IndexOutOfBounds, msg)
+    })
+}
+
+/// Fails unless `condition` is `true`.
+pub fn cupid_assert(vm: &mut Vm, args: &[Value]) -> Result<Value, CupidErr> {
     match args[0] {
-        Value::Array(mut array) => {
-            array.items.push(args[1]);
-            Value::Int(array.items.len() as i32)
+        Value::Bool(true) => Ok(Value::Nil),
+        condition => {
+            let msg = format!("Assertion failed: expected true, got {condition}.");
+            Err(vm.as_runtime_err(Code::AssertionFailed, msg))
         }
-        _ => panic!("expected array"),
     }
 }
 
-pub fn cupid_pop(_vm: &Vm, args: &[Value]) -> Value {
-    match args[0] {
-        Value::Array(mut array) => array.items.pop().unwrap_or(Value::Nil),
-        _ => panic!("expected array"),
+/// Fails, showing both values, unless `left` and `right` are equal.
+pub fn cupid_assert_eq(vm: &mut Vm, args: &[Value]) -> Result<Value, CupidErr> {
+    let (left, right) = (args[0], args[1]);
+    if left == right {
+        return Ok(Value::Nil);
     }
+    let msg = format!("Assertion failed: values are not equal\n  left: {left}\n right: {right}");
+    Err(vm.as_runtime_err(Code::AssertionFailed, msg))
 }
 
-pub fn cupid_len(_vm: &Vm, args: &[Value]) -> Value {
-    match args[0] {
-        Value::Array(array) => Value::Int(array.items.len() as i32),
-        _ => panic!("expected array"),
+/// Calls `f` without arguments, and fails unless it ends with an error: a runtime error, a thrown
+/// value or a failed assertion. Errors that stop the program, like running out of fuel, go on.
+pub fn cupid_assert_err(vm: &mut Vm, args: &[Value]) -> Result<Value, CupidErr> {
+    match vm.try_call(args[0], &[])? {
+        Ok(value) => {
+            let msg =
+                format!("Assertion failed: expected an error, but the function returned {value}.");
+            Err(vm.as_runtime_err(Code::AssertionFailed, msg))
+        }
+        Err(_) => Ok(Value::Nil),
+    }
+}
+
+/// The next line of input without its line ending, or nil once the input has run out.
+pub fn cupid_read_line(vm: &mut Vm, _args: &[Value]) -> Result<Value, CupidErr> {
+    let mut line = String::new();
+    let read = vm.streams.get_mut().stdin.read_line(&mut line);
+    match read {
+        Ok(0) => Ok(Value::Nil),
+        Ok(_) => {
+            let len = line.trim_end_matches(['\n', '\r']).len();
+            line.truncate(len);
+            Ok(Value::String(vm.intern(line)))
+        }
+        Err(error) => {
+            Err(vm.as_runtime_err(Code::Io, format!("Unable to read from stdin: {error}.")))
+        }
+    }
+}
+
+/// The array `value`, or a runtime error naming the native it was given to.
+fn expect_array(vm: &Vm, native: &str, value: Value) -> Result<GcRef<Array>, CupidErr> {
+    match value {
+        Value::Array(array) => Ok(array),
+        value => Err(vm.as_runtime_err(
+            Code::InvalidOperands,
+            format!("Expected an array as the first argument of '{native}', not {value}."),
+        )),
     }
 }
 
-pub fn cupid_get(_vm: &Vm, args: &[Value]) -> Value {
-    match (args[0], args[1]) {
-        (Value::Array(array), Value::Int(i)) => array.items[i as usize],
-        _ => panic!("expected array"),
+/// Collects what `each` gives for the items of `array` into a new array. The values stay on the
+/// stack, and so alive, until the array is allocated.
+fn collect(
+    vm: &mut Vm,
+    array: GcRef<Array>,
+    each: impl Fn(&mut Vm, Value) -> Result<Option<Value>, CupidErr>,
+) -> Result<Value, CupidErr> {
+    let first = vm.stack.len();
+    // The callback may change the array, so it is read again for every item
+    let mut i = 0;
+    while i < array.items.len() {
+        match each(vm, array.items[i]) {
+            Ok(Some(value)) => vm.stack.push(value),
+            Ok(None) => {}
+            Err(error) => {
+                vm.stack.truncate(first);
+                return Err(error);
+            }
+        }
+        i += 1;
+    }
+    let items = vm.stack.stack[first..].to_vec();
+    let result = vm.alloc(Array::new(items));
+    vm.stack.truncate(first);
+    Ok(Value::Array(result))
+}
+
+/// A new array with the result of calling `f` on each item of `array`.
+pub fn cupid_map(vm: &mut Vm, args: &[Value]) -> Result<Value, CupidErr> {
+    let (array, f) = (expect_array(vm, "map", args[0])?, args[1]);
+    collect(vm, array, |vm, item| vm.call(f, &[item]).map(Some))
+}
+
+/// A new array with the items of `array` for which `keep` returns a truthy value.
+pub fn cupid_filter(vm: &mut Vm, args: &[Value]) -> Result<Value, CupidErr> {
+    let (array, keep) = (expect_array(vm, "filter", args[0])?, args[1]);
+    collect(vm, array, |vm, item| {
+        let kept = !vm.call(keep, &[item])?.is_falsey();
+        Ok(kept.then_some(item))
+    })
+}
+
+/// Sorts `array` in place, and returns it, with `less` telling whether its first argument goes
+/// before its second. The sort is stable.
+pub fn cupid_sort_by(vm: &mut Vm, args: &[Value]) -> Result<Value, CupidErr> {
+    let (mut array, less) = (expect_array(vm, "sort_by", args[0])?, args[1]);
+    // The callback may change the array, so the items being sorted stay on the stack, and so
+    // alive, until they are all sorted
+    let first = vm.stack.len();
+    vm.stack.stack.extend_from_slice(&array.items);
+    let items = merge_sort(vm, vm.stack.stack[first..].to_vec(), less);
+    vm.stack.truncate(first);
+    array.items = items?;
+    vm.gc.write_barrier(array);
+    vm.gc.resize(array);
+    Ok(Value::Array(array))
+}
+
+fn merge_sort(vm: &mut Vm, mut items: Vec<Value>, less: Value) -> Result<Vec<Value>, CupidErr> {
+    if items.len() < 2 {
+        return Ok(items);
+    }
+    let left: Vec<Value> = items.drain(..items.len() / 2).collect();
+    let left = merge_sort(vm, left, less)?;
+    let right = merge_sort(vm, items, less)?;
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let (mut left, mut right) = (left.into_iter().peekable(), right.into_iter().peekable());
+    while let (Some(&a), Some(&b)) = (left.peek(), right.peek()) {
+        // An item of the right half only goes first if it is strictly less, keeping the sort stable
+        if vm.call(less, &[b, a])?.is_falsey() {
+            merged.push(a);
+            left.next();
+        } else {
+            merged.push(b);
+            right.next();
+        }
     }
+    merged.extend(left.chain(right));
+    Ok(merged)
 }
@@ -0,0 +1,584 @@
+use std::{fs, process};
+
+use cupid_fmt::doc::Doc;
+
+use crate::{
+    arena::ExprArena,
+    cst::{
+        block::{BlockSource, BraceBlockSource},
+        expr::ExprSource,
+        r#loop::LoopSource,
+        SourceId,
+    },
+    error::CupidError,
+    gc::Gc,
+    parse::{
+        parser::Parser,
+        precedence::{infix_binding_power, postfix_binding_power, prefix_binding_power},
+    },
+    scanner::Scanner,
+    token::{Token, TokenType},
+};
+
+/// The line width code is wrapped at, unless another one is given.
+pub const DEFAULT_WIDTH: usize = 80;
+
+const INDENT: usize = 4;
+
+/// Reprints `code` from its concrete syntax tree: one statement per line, indented by block,
+/// with single spaces around operators and lines longer than `width` wrapped. Comments are kept,
+/// as are single blank lines between statements.
+pub fn format_source(code: &str, width: usize) -> Result<String, CupidError> {
+    let mut gc = Gc::default();
+    let mut parser = Parser::new(code);
+    let file = parser.parse_source(&mut gc)?;
+    let mut formatter = Formatter {
+        code,
+        arena: &parser.arena,
+        comments: comments(code),
+        next_comment: 0,
+    };
+    let mut formatted = match formatter.statements(&file.exprs, code.len()) {
+        Some(doc) => doc.render(width),
+        None => String::new(),
+    };
+    if !formatted.is_empty() {
+        formatted.push('\n');
+    }
+    Ok(formatted)
+}
+
+/// Formats the file at `path` in place or, with `check`, exits with an error if it is not
+/// formatted already.
+pub fn format_file(path: &str, width: usize, check: bool) {
+    let code = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) => {
+            eprintln!("Unable to read file {}: {}", path, error);
+            process::exit(74);
+        }
+    };
+    let formatted = match format_source(&code, width) {
+        Ok(formatted) => formatted,
+        Err(error) => {
+            eprintln!("{error}");
+            process::exit(65);
+        }
+    };
+    if formatted == code {
+        return;
+    }
+    if check {
+        let line = code.lines().zip(formatted.lines()).take_while(|(a, b)| a == b).count() + 1;
+        eprintln!("{path} is not formatted, starting at line {line}.");
+        process::exit(1);
+    }
+    if let Err(error) = fs::write(path, formatted) {
+        eprintln!("Unable to write file {}: {}", path, error);
+        process::exit(74);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Comment<'src> {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) text: &'src str,
+}
+
+/// The comments of `code`, which are in the gaps between its tokens.
+pub(crate) fn comments(code: &str) -> Vec<Comment<'_>> {
+    let mut comments = vec![];
+    let mut scanner = Scanner::new(code);
+    let mut gap_start = 0;
+    loop {
+        let token = scanner.scan_token();
+        let gap_end = token.span.start.index.max(gap_start);
+        let mut start = gap_start;
+        let find = |start| {
+            ["--", "##"].iter().filter_map(|marker| code[start..gap_end].find(marker)).min()
+        };
+        while let Some(offset) = find(start) {
+            let comment_start = start + offset;
+            let comment_end = match code[comment_start..gap_end].find('\n') {
+                Some(newline) => comment_start + newline,
+                None => gap_end,
+            };
+            comments.push(Comment {
+                start: comment_start,
+                end: comment_end,
+                text: code[comment_start..comment_end].trim_end(),
+            });
+            start = comment_end;
+        }
+        if token.kind == TokenType::Eof {
+            return comments;
+        }
+        gap_start = token.span.end.index;
+    }
+}
+
+struct Formatter<'a, 'src> {
+    code: &'src str,
+    arena: &'a ExprArena<'src>,
+    comments: Vec<Comment<'src>>,
+    next_comment: usize,
+}
+
+impl<'a, 'src> Formatter<'a, 'src> {
+    fn source(&self, id: SourceId) -> &'a ExprSource<'src> {
+        self.arena.expect_source(id)
+    }
+
+    /// Takes the comments not printed yet that start before `offset`.
+    fn take_comments(&mut self, offset: usize) -> Vec<Comment<'src>> {
+        let start = self.next_comment;
+        while self.comments.get(self.next_comment).is_some_and(|c| c.start < offset) {
+            self.next_comment += 1;
+        }
+        self.comments[start..self.next_comment].to_vec()
+    }
+
+    /// The statements one per line, with the comments before `end` that go with them, or `None`
+    /// when there are neither.
+    fn statements(&mut self, sources: &[SourceId], end: usize) -> Option<Doc> {
+        let mut lines: Vec<(bool, Doc)> = vec![];
+        let mut previous_end = None;
+        for id in sources {
+            let source = self.source(*id);
+            let start = source.first_token(self.arena).span.start.index;
+            let last = source.last_token(self.arena).span.end.index;
+            for comment in self.take_comments(start) {
+                lines.push(self.line(&mut previous_end, comment.start, Doc::text(comment.text)));
+                previous_end = Some(comment.end);
+            }
+            let mut statement = self.expr(*id);
+            // Comments within the statement but outside of its blocks go on the lines before it,
+            // and one after it on its last line stays there.
+            for comment in self.take_comments(last) {
+                lines.push(self.line(&mut previous_end, comment.start, Doc::text(comment.text)));
+            }
+            let mut statement_end = last;
+            if let Some(comment) = self.comments.get(self.next_comment).copied() {
+                if comment.start < end && !self.code[last..comment.start].contains('\n') {
+                    statement = statement + Doc::text(" ") + Doc::text(comment.text);
+                    statement_end = comment.end;
+                    self.next_comment += 1;
+                }
+            }
+            lines.push(self.line(&mut previous_end, start, statement));
+            previous_end = Some(statement_end);
+        }
+        for comment in self.take_comments(end) {
+            lines.push(self.line(&mut previous_end, comment.start, Doc::text(comment.text)));
+            previous_end = Some(comment.end);
+        }
+        if lines.is_empty() {
+            return None;
+        }
+        let mut doc = Doc::nil();
+        for (i, (after_blank, line)) in lines.into_iter().enumerate() {
+            if i > 0 {
+                doc = doc + Doc::hardline();
+                if after_blank {
+                    doc = doc + Doc::hardline();
+                }
+            }
+            doc = doc + line;
+        }
+        Some(doc)
+    }
+
+    /// A line starting at `start`, along with whether it follows blank lines in the code.
+    fn line(&self, previous_end: &mut Option<usize>, start: usize, doc: Doc) -> (bool, Doc) {
+        let after_blank = match *previous_end {
+            Some(end) if end <= start => self.code[end..start].matches('\n').count() > 1,
+            _ => false,
+        };
+        *previous_end = Some(start);
+        (after_blank, doc)
+    }
+
+    fn expr(&mut self, id: SourceId) -> Doc {
+        match self.source(id) {
+            ExprSource::Array(array) => self.list("[", &array.items_src, "]"),
+            ExprSource::BinOp(binop) => match binop.op.kind {
+                TokenType::Dot => self.property(binop.left_src, binop.right_src),
+                TokenType::Equal => {
+                    let (l_bp, r_bp) = infix_binding_power(TokenType::Equal);
+                    self.operand(binop.left_src, Some(l_bp), None)
+                        + Doc::text(" = ")
+                        + self.operand(binop.right_src, None, Some(r_bp))
+                }
+                _ => self.operators(id),
+            },
+            ExprSource::Block(BlockSource::ArrowBlock(block)) => {
+                Doc::text("=> ") + self.expr(block.body_src)
+            }
+            ExprSource::Block(BlockSource::BraceBlock(block)) => self.block(block),
+            ExprSource::Break(brk) => self.keyword(brk.break_kw, brk.value_src),
+            ExprSource::Call(call) => {
+                let (l_bp, ()) = postfix_binding_power(TokenType::LeftParen).unwrap();
+                self.operand(call.callee_src, Some(l_bp), None)
+                    + self.list("(", &call.args_src, ")")
+            }
+            ExprSource::Class(class) => {
+                let mut doc = Doc::text("class ") + Doc::text(class.name.lexeme);
+                if let Some(super_class) = class.super_class_name {
+                    doc = doc + Doc::text(" < ") + Doc::text(super_class.lexeme);
+                }
+                let members: Vec<SourceId> =
+                    class.fields.iter().chain(&class.methods).copied().collect();
+                let end = class.close_brace.span.start.index;
+                doc + Doc::text(" ") + self.braces(&members, end)
+            }
+            ExprSource::Constant(constant) => Doc::text(constant.value.lexeme),
+            ExprSource::Define(define) => {
+                let mut doc = Doc::text(define.name.lexeme);
+                if define.let_kw.is_some() {
+                    doc = Doc::text("let ") + doc;
+                }
+                match define.value_src {
+                    Some(value) => doc + Doc::text(" = ") + self.expr(value),
+                    None => doc,
+                }
+            }
+            ExprSource::Fun(fun) => {
+                // Methods have no `fun` keyword.
+                let mut doc = match fun.fun_kw.span.is_synthetic() {
+                    true => Doc::nil(),
+                    false => Doc::text("fun "),
+                };
+                if let Some(name) = fun.name {
+                    doc = doc + Doc::text(name.lexeme) + Doc::text(" ");
+                }
+                doc + self.list("(", &fun.params_src, ")")
+                    + Doc::text(" ")
+                    + self.expr(fun.body_src)
+            }
+            ExprSource::GetProperty(get) => {
+                self.operand(get.receiver, Some(infix_binding_power(TokenType::Dot).0), None)
+                    + Doc::text(".")
+                    + Doc::text(get.property.lexeme)
+            }
+            ExprSource::GetSuper(get) => Doc::text(get.name.lexeme),
+            ExprSource::Get(get) => Doc::text(get.name.lexeme),
+            ExprSource::If(stmt) => {
+                let doc = Doc::text("if ")
+                    + self.operand(stmt.condition_src, None, Some(0))
+                    + Doc::text(" ")
+                    + self.expr(stmt.body_src);
+                match stmt.else_body_src {
+                    Some(else_body) => doc + Doc::text(" else ") + self.expr(else_body),
+                    None => doc,
+                }
+            }
+            ExprSource::InvokeSuper(invoke) => {
+                Doc::text(invoke.name.lexeme) + self.list("(", &invoke.args, ")")
+            }
+            ExprSource::Invoke(invoke) => {
+                self.operand(invoke.receiver, Some(infix_binding_power(TokenType::Dot).0), None)
+                    + Doc::text(".")
+                    + Doc::text(invoke.callee.lexeme)
+                    + self.list("(", &invoke.args, ")")
+            }
+            ExprSource::Loop(LoopSource::Plain(stmt)) => {
+                Doc::text("loop ") + self.expr(stmt.body_src)
+            }
+            ExprSource::Loop(LoopSource::While(stmt)) => {
+                Doc::text("while ")
+                    + self.operand(stmt.condition_src, None, Some(0))
+                    + Doc::text(" ")
+                    + self.expr(stmt.body_src)
+            }
+            ExprSource::Loop(LoopSource::For(stmt)) => {
+                Doc::text("for (")
+                    + self.expr(stmt.init_src)
+                    + Doc::text("; ")
+                    + self.operand(stmt.condition_src, None, Some(0))
+                    + Doc::text("; ")
+                    + self.operand(stmt.increment_src, None, Some(0))
+                    + Doc::text(") ")
+                    + self.expr(stmt.body_src)
+            }
+            ExprSource::Propagate(propagate) => {
+                let (l_bp, ()) = postfix_binding_power(TokenType::Question).unwrap();
+                self.operand(propagate.value_src, Some(l_bp), None) + Doc::text("?")
+            }
+            ExprSource::Return(ret) => self.keyword(ret.return_kw, ret.value_src),
+            ExprSource::SetProperty(set) => {
+                self.operand(set.receiver, Some(infix_binding_power(TokenType::Dot).0), None)
+                    + Doc::text(".")
+                    + Doc::text(set.property.lexeme)
+                    + Doc::text(" = ")
+                    + self.expr(set.value)
+            }
+            ExprSource::Set(set) => {
+                Doc::text(set.name.lexeme) + Doc::text(" = ") + self.expr(set.value_src)
+            }
+            ExprSource::Throw(throw) => self.keyword(throw.throw_kw, Some(throw.value_src)),
+            ExprSource::Try(stmt) => {
+                let doc = Doc::text("try ")
+                    + self.expr(stmt.body_src)
+                    + Doc::text(" catch (")
+                    + self.expr(stmt.catch_param_src)
+                    + Doc::text(") ")
+                    + self.expr(stmt.catch_body_src);
+                match stmt.finally_body_src {
+                    Some(finally_body) => doc + Doc::text(" finally ") + self.expr(finally_body),
+                    None => doc,
+                }
+            }
+            ExprSource::UnOp(unop) => {
+                let ((), r_bp) = prefix_binding_power(unop.op.kind);
+                let operand = self.operand(unop.expr_src, None, Some(r_bp));
+                // `--` would start a comment.
+                match operand.first_text().is_some_and(|text| text.starts_with('-')) {
+                    true => Doc::text(unop.op.lexeme) + Doc::text(" ") + operand,
+                    false => Doc::text(unop.op.lexeme) + operand,
+                }
+            }
+        }
+    }
+
+    /// `return` or `break`, with the value if there is one.
+    fn keyword(&mut self, keyword: Token<'src>, value: Option<SourceId>) -> Doc {
+        match value {
+            Some(value) => Doc::text(keyword.lexeme) + Doc::text(" ") + self.expr(value),
+            None => Doc::text(keyword.lexeme),
+        }
+    }
+
+    fn block(&mut self, block: &BraceBlockSource<'src>) -> Doc {
+        self.braces(&block.body_src, block.close_brace.span.start.index)
+    }
+
+    fn braces(&mut self, sources: &[SourceId], end: usize) -> Doc {
+        match self.statements(sources, end) {
+            Some(body) => {
+                Doc::text("{")
+                    + (Doc::hardline() + body).nest(INDENT)
+                    + Doc::hardline()
+                    + Doc::text("}")
+            }
+            None => Doc::text("{}"),
+        }
+    }
+
+    /// Items between brackets, all on one line if they fit and one per line otherwise. A last
+    /// item that takes several lines anyway (a function, say) stays on the line of the brackets.
+    fn list(&mut self, open: &str, items: &[SourceId], close: &str) -> Doc {
+        let items: Vec<Doc> = items.iter().map(|item| self.expr(*item)).collect();
+        let hug = match items.split_last() {
+            Some((last, others)) => last.is_multiline() && !others.iter().any(Doc::is_multiline),
+            None => true,
+        };
+        if hug {
+            return Doc::text(open) + Doc::join(items, Doc::text(", ")) + Doc::text(close);
+        }
+        let mut inner = Doc::nil();
+        for (i, item) in items.into_iter().enumerate() {
+            inner = match i {
+                0 => inner + line_before(Doc::softline(), &item) + item,
+                _ => inner + Doc::text(",") + line_before(Doc::line(), &item) + item,
+            };
+        }
+        (Doc::text(open) + inner.nest(INDENT) + Doc::softline() + Doc::text(close)).group()
+    }
+
+    fn property(&mut self, receiver: SourceId, property: SourceId) -> Doc {
+        let (l_bp, r_bp) = infix_binding_power(TokenType::Dot);
+        self.operand(receiver, Some(l_bp), None)
+            + Doc::text(".")
+            + self.operand(property, None, Some(r_bp))
+    }
+
+    /// A chain of operators of the same precedence, such as `a + b - c`, which is broken after
+    /// each operator when it doesn't fit.
+    fn operators(&mut self, id: SourceId) -> Doc {
+        let mut chain: Vec<(Token<'src>, SourceId)> = vec![];
+        let mut first = id;
+        while let ExprSource::BinOp(binop) = self.source(first) {
+            let is_chained = match chain.last() {
+                Some((op, _)) => infix_binding_power(op.kind) == infix_binding_power(binop.op.kind),
+                None => true,
+            };
+            if !is_chained || matches!(binop.op.kind, TokenType::Dot | TokenType::Equal) {
+                break;
+            }
+            chain.push((binop.op, binop.right_src));
+            first = binop.left_src;
+        }
+        let (l_bp, _) = infix_binding_power(chain[0].0.kind);
+        let mut rest = Doc::nil();
+        let mut doc = self.operand(first, Some(l_bp), None);
+        for (op, right) in chain.into_iter().rev() {
+            let (_, r_bp) = infix_binding_power(op.kind);
+            let right = self.operand(right, None, Some(r_bp));
+            rest = rest + Doc::text(" ") + Doc::text(op.lexeme) + line_before(Doc::line(), &right);
+            rest = rest + right;
+        }
+        doc = doc + rest.nest(INDENT);
+        doc.group()
+    }
+
+    /// An operand, in parentheses if it would otherwise be read differently: as the left operand
+    /// of an operator with the left binding power `left_of`, or as the right operand of one with
+    /// the right binding power `right_of`.
+    fn operand(&mut self, id: SourceId, left_of: Option<u8>, right_of: Option<u8>) -> Doc {
+        let source = self.source(id);
+        let is_statement = matches!(
+            source,
+            ExprSource::Break(_)
+                | ExprSource::Class(_)
+                | ExprSource::Define(_)
+                | ExprSource::If(_)
+                | ExprSource::Loop(_)
+                | ExprSource::Return(_)
+                | ExprSource::Throw(_)
+                | ExprSource::Try(_)
+        );
+        let power = match source {
+            ExprSource::BinOp(binop) => Some(infix_binding_power(binop.op.kind)),
+            ExprSource::Set(_) | ExprSource::SetProperty(_) => {
+                Some(infix_binding_power(TokenType::Equal))
+            }
+            ExprSource::UnOp(unop) => Some((u8::MAX, prefix_binding_power(unop.op.kind).1)),
+            _ => None,
+        };
+        // Arrow bodies take in everything after them.
+        let is_open = match source {
+            ExprSource::Block(BlockSource::ArrowBlock(_)) => true,
+            ExprSource::Fun(fun) => {
+                matches!(self.source(fun.body_src), ExprSource::Block(BlockSource::ArrowBlock(_)))
+            }
+            _ => false,
+        };
+        let needs_parens = match (left_of, right_of) {
+            _ if is_statement => left_of.is_some() || right_of.is_some(),
+            (Some(_), _) if is_open => true,
+            (Some(left_of), _) => power.is_some_and(|(_, r_bp)| left_of >= r_bp),
+            (None, Some(right_of)) => power.is_some_and(|(l_bp, _)| l_bp < right_of),
+            (None, None) => false,
+        };
+        let doc = self.expr(id);
+        match needs_parens {
+            true => Doc::text("(") + doc + Doc::text(")"),
+            false => doc,
+        }
+    }
+}
+
+/// A line break right before `(` would end the statement, so it is avoided there.
+fn line_before(line: Doc, next: &Doc) -> Doc {
+    match (next.first_text(), line) {
+        (Some(text), Doc::Line) if text.starts_with('(') => Doc::text(" "),
+        (Some(text), _) if text.starts_with('(') => Doc::nil(),
+        (_, line) => line,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::{format_source, DEFAULT_WIDTH};
+    use crate::{analyze::pretty::PrettyPrint, gc::Gc, parse::parser::Parser};
+
+    fn format(code: &str) -> String {
+        format_source(code, DEFAULT_WIDTH).unwrap()
+    }
+
+    #[test]
+    fn canonical_layout() {
+        let code = "fun add(a,b){return a+b}\nclass A < B {\n  let size = 1\n  \
+            grow () { return self.size }\n  shrink (by) => self.size=self.size - by\n}\n\
+            if x<3 { log(x) } else log (!x);\nfor (let i = 0; i < 3; i = i + 1) { log (i) }\n";
+        let expected =
+            "fun add (a, b) {\n    return a + b\n}\nclass A < B {\n    let size = 1\n    \
+            grow () {\n        return self.size\n    }\n    \
+            shrink (by) => self.size = self.size - by\n}\n\
+            if x < 3 {\n    log(x)\n} else log(!x)\nfor (let i = 0; i < 3; i = i + 1) {\n    \
+            log(i)\n}\n";
+        assert_eq!(format(code), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn spaces_calls_alike() {
+        let expected = "log(dict([1]))\nf(x)(y)\nlist.push(1)\n";
+        assert_eq!(format("log (dict ([1]))\nf (x) (y)\nlist.push (1)\n"), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn keeps_comments_and_blank_lines() {
+        let code = "-- Header.\n\n\nlet a = 1   -- one\nlet b = [1, -- inside\n  2]\n\
+            while a < 3 {\n    a = a + 1\n\n    -- last\n}\n-- end\n";
+        let expected = "-- Header.\n\nlet a = 1 -- one\n-- inside\nlet b = [1, 2]\n\
+            while a < 3 {\n    a = a + 1\n\n    -- last\n}\n-- end\n";
+        assert_eq!(format(code), expected);
+    }
+
+    #[test]
+    fn parenthesizes_only_where_needed() {
+        let code = "log ((1 + 2) * 3)\nlog (1 + (2 * 3))\nlog (-(-(3)))\nlog ((a.b).c)\n\
+            log ((a + b).c)\nlog (a - (b - c))\n";
+        let expected = "log((1 + 2) * 3)\nlog(1 + 2 * 3)\nlog(- -3)\nlog(a.b.c)\n\
+            log((a + b).c)\nlog(a - (b - c))\n";
+        assert_eq!(format(code), expected);
+    }
+
+    #[test]
+    fn parenthesizes_propagated_operands() {
+        let code = "log ((a.b)?)\nlog ((a + b)?)\nlog (2 * (f())?)\nlog ((a?).b)\n";
+        let expected = "log(a.b?)\nlog((a + b)?)\nlog(2 * f()?)\nlog(a?.b)\n";
+        assert_eq!(format(code), expected);
+    }
+
+    #[test]
+    fn wraps_long_lines() {
+        let code = "let total = first(one, two) + second_value + third_value\n\
+            call(fun () { return 1 })\n";
+        let expected = "let total = first(one, two) +\n    second_value +\n    third_value\n\
+            call(fun () {\n    return 1\n})\n";
+        assert_eq!(format_source(code, 30).unwrap(), expected);
+
+        let expected = "let total = first(\n    one,\n    two\n) +\n";
+        assert!(format_source(code, 16).unwrap().starts_with(expected));
+    }
+
+    /// Formatting the integration tests that parse must not change what they mean, and must give
+    /// code that is formatted already.
+    #[test]
+    fn integration_files_keep_their_meaning() {
+        fn parse(code: &str) -> Option<String> {
+            let mut parser = Parser::new(code);
+            let mut gc = Gc::default();
+            let exprs = parser.parse(&mut gc).ok()?;
+            Some(exprs.pretty_print(&parser.arena))
+        }
+        fn visit(dir: &Path, files: &mut Vec<String>) {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                match path.is_dir() {
+                    true => visit(&path, files),
+                    false => files.push(fs::read_to_string(path).unwrap()),
+                }
+            }
+        }
+        let mut files = vec![];
+        visit(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/integration"), &mut files);
+        let mut formatted_files = 0;
+        for code in files {
+            // Some of the tests are about invalid code, which the parser may panic on.
+            let parsed = std::panic::catch_unwind(|| parse(&code)).ok().flatten();
+            let Some(parsed) = parsed else { continue };
+            let formatted = format(&code);
+            assert_eq!(parse(&formatted), Some(parsed), "{formatted}");
+            assert_eq!(format(&formatted), formatted);
+            formatted_files += 1;
+        }
+        assert!(formatted_files > 200);
+    }
+}
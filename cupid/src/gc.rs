@@ -1,24 +1,51 @@
+use std::cell::{Cell, Ref, RefCell};
+use std::collections::HashSet;
+use std::env;
 use std::fmt::Debug;
+use std::mem;
 use std::ptr::NonNull;
-use std::{alloc, mem};
+use std::time::Instant;
 use std::{
     ops::{Deref, DerefMut},
-    sync::atomic::AtomicUsize,
     usize,
 };
 
 use crate::{
     objects::{
-        Array, BoundMethod, Class, Closure, Function, Instance, ObjectType, RoleImpl, Str, Upvalue,
+        Array, BoundMethod, Class, Closure, Foreign, Function, Instance, Native, ObjectType,
+        RoleImpl, Str, Upvalue,
     },
     table::Table,
     value::Value,
 };
 
+/// The generation an object currently lives in. New objects are allocated into the nursery
+/// (`Young`) and promoted to `Old` once they survive a collection.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Generation {
+    Young,
+    Old,
+}
+
+/// A minor collection only traces and sweeps the nursery, a major collection traces the whole heap.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Collection {
+    Minor,
+    Major,
+}
+
+pub mod stats;
+pub use self::stats::*;
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct GcObject {
     marked: bool,
+    remembered: bool,
+    generation: Generation,
+    size: usize,
+    #[cfg(feature = "checked")]
+    freed: bool,
     next: Option<NonNull<GcObject>>,
     obj_type: ObjectType,
 }
@@ -27,10 +54,39 @@ impl GcObject {
     pub fn new(obj_type: ObjectType) -> Self {
         Self {
             marked: false,
+            remembered: false,
+            generation: Generation::Young,
+            size: 0,
+            #[cfg(feature = "checked")]
+            freed: false,
             next: None,
             obj_type,
         }
     }
+
+    pub fn generation(&self) -> Generation {
+        self.generation
+    }
+
+    pub fn obj_type(&self) -> ObjectType {
+        self.obj_type
+    }
+
+    /// The bytes accounted to this object, including the buffers it owns
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+/// Every heap object reports the memory it owns outside of itself (e.g. the items of an array), so
+/// that collections are triggered by the real size of the heap.
+pub trait HeapSize: Sized {
+    /// Bytes owned by the object outside of its own allocation
+    fn heap_size(&self) -> usize;
+
+    fn size(&self) -> usize {
+        mem::size_of::<Self>() + self.heap_size()
+    }
 }
 
 #[derive(Debug)]
@@ -44,18 +100,39 @@ impl<T: Debug> GcRef<T> {
             pointer: NonNull::dangling(),
         }
     }
+
+    pub fn header(&self) -> &GcObject {
+        unsafe { self.object().as_ref() }
+    }
+
+    /// The header shared by every heap object, which is always the first field
+    pub fn object(&self) -> NonNull<GcObject> {
+        self.pointer.cast()
+    }
+
+    /// Panics if the object has already been collected. Only available with the `checked` feature,
+    /// which keeps collected objects around (but marked as freed) until the collector is dropped.
+    #[cfg(feature = "checked")]
+    pub fn assert_live(&self) {
+        let header = unsafe { self.object().as_ref() };
+        assert!(!header.freed, "Use of a freed {:?} object", header.obj_type);
+    }
 }
 
 impl<T: Debug> Deref for GcRef<T> {
     type Target = T;
 
     fn deref(&self) -> &T {
+        #[cfg(feature = "checked")]
+        self.assert_live();
         unsafe { self.pointer.as_ref() }
     }
 }
 
 impl<T: Debug> DerefMut for GcRef<T> {
     fn deref_mut(&mut self) -> &mut T {
+        #[cfg(feature = "checked")]
+        self.assert_live();
         unsafe { self.pointer.as_mut() }
     }
 }
@@ -76,40 +153,262 @@ impl<T: Debug> PartialEq for GcRef<T> {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Heap tuning and debugging knobs. Every setting can be given on the command line or through the
+/// environment:
+///
+/// | Setting        | Flag                  | Environment             | Default |
+/// | -------------- | --------------------- | ----------------------- | ------- |
+/// | `initial_heap` | `--gc-initial-heap=`  | `CUPID_GC_INITIAL_HEAP` | `1m`    |
+/// | `grow_factor`  | `--gc-grow-factor=`   | `CUPID_GC_GROW_FACTOR`  | `2`     |
+/// | `nursery_size` | `--gc-nursery-size=`  | `CUPID_GC_NURSERY_SIZE` | `256k`  |
+/// | `max_heap`     | `--gc-max-heap=`      | `CUPID_GC_MAX_HEAP`     | none    |
+/// | `stress`       | `--gc-stress`         | `CUPID_GC_STRESS`       | off     |
+/// | `log`          | `--gc-log`            | `CUPID_GC_LOG`          | off     |
+/// | `stats`        | `--gc-stats`          | `CUPID_GC_STATS`        | off     |
+/// | `verify`       | `--gc-verify`         | `CUPID_GC_VERIFY`       | off     |
+///
+/// Sizes are in bytes and accept a `k`, `m` or `g` suffix. Switches are turned on by any value
+/// other than `0`, `false` or an empty string.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GcConfig {
+    /// Heap size that triggers the first major collection
+    pub initial_heap: usize,
+    /// After a major collection, the next one is triggered once the heap has grown by this factor
+    pub grow_factor: f64,
+    /// Bytes allocated into the nursery before a minor collection is triggered
+    pub nursery_size: usize,
+    /// Live objects may not take up more than this. It is a soft limit: allocations always
+    /// succeed, and the vm only enforces it at calls and loop iterations, after a full collection
+    pub max_heap: Option<usize>,
+    /// Collect on every allocation (and verify the heap after every collection)
+    pub stress: bool,
+    /// Print a line to stderr for every collection
+    pub log: bool,
+    /// Print the collector's statistics to stderr when the program ends
+    pub stats: bool,
+    /// Verify the heap after every collection
+    pub verify: bool,
+}
+
+impl Default for GcConfig {
+    fn default() -> Self {
+        Self {
+            initial_heap: 1024 * 1024,
+            grow_factor: 2.0,
+            nursery_size: 256 * 1024,
+            max_heap: None,
+            stress: false,
+            log: false,
+            stats: false,
+            verify: false,
+        }
+    }
+}
+
+impl GcConfig {
+    pub const INITIAL_HEAP_VAR: &'static str = "CUPID_GC_INITIAL_HEAP";
+    pub const GROW_FACTOR_VAR: &'static str = "CUPID_GC_GROW_FACTOR";
+    pub const NURSERY_SIZE_VAR: &'static str = "CUPID_GC_NURSERY_SIZE";
+    pub const MAX_HEAP_VAR: &'static str = "CUPID_GC_MAX_HEAP";
+    pub const STRESS_VAR: &'static str = "CUPID_GC_STRESS";
+    pub const LOG_VAR: &'static str = "CUPID_GC_LOG";
+    pub const STATS_VAR: &'static str = "CUPID_GC_STATS";
+    pub const VERIFY_VAR: &'static str = "CUPID_GC_VERIFY";
+
+    /// Reads the defaults, overridden by any `CUPID_GC_*` environment variables.
+    pub fn from_env() -> Result<Self, String> {
+        let mut config = Self::default();
+        if let Ok(value) = env::var(Self::INITIAL_HEAP_VAR) {
+            config.set_initial_heap(&value)?;
+        }
+        if let Ok(value) = env::var(Self::GROW_FACTOR_VAR) {
+            config.set_grow_factor(&value)?;
+        }
+        if let Ok(value) = env::var(Self::NURSERY_SIZE_VAR) {
+            config.set_nursery_size(&value)?;
+        }
+        if let Ok(value) = env::var(Self::MAX_HEAP_VAR) {
+            config.set_max_heap(&value)?;
+        }
+        let switch = |name| env::var(name).is_ok_and(|value| parse_switch(&value));
+        config.stress = switch(Self::STRESS_VAR);
+        config.log = switch(Self::LOG_VAR);
+        config.stats = switch(Self::STATS_VAR);
+        config.verify = switch(Self::VERIFY_VAR);
+        Ok(config)
+    }
+
+    /// Whether the heap should be verified after each collection
+    pub fn verifies(&self) -> bool {
+        self.verify || self.stress
+    }
+
+    pub fn set_initial_heap(&mut self, value: &str) -> Result<(), String> {
+        self.initial_heap = parse_size(value)?;
+        Ok(())
+    }
+
+    pub fn set_grow_factor(&mut self, value: &str) -> Result<(), String> {
+        match value.trim().parse::<f64>() {
+            Ok(factor) if factor > 1.0 && factor.is_finite() => {
+                self.grow_factor = factor;
+                Ok(())
+            }
+            _ => Err(format!("Invalid heap grow factor '{value}': expected a number above 1.")),
+        }
+    }
+
+    pub fn set_nursery_size(&mut self, value: &str) -> Result<(), String> {
+        self.nursery_size = parse_size(value)?;
+        Ok(())
+    }
+
+    pub fn set_max_heap(&mut self, value: &str) -> Result<(), String> {
+        self.max_heap = Some(parse_size(value)?);
+        Ok(())
+    }
+}
+
+fn parse_switch(value: &str) -> bool {
+    !matches!(value.trim(), "" | "0" | "false")
+}
+
+/// Parses a byte size such as `4096`, `512k`, `16m` or `1g`.
+pub fn parse_size(value: &str) -> Result<usize, String> {
+    let trimmed = value.trim().to_ascii_lowercase();
+    let (digits, multiplier) = match trimmed.chars().last() {
+        Some('k') => (&trimmed[..trimmed.len() - 1], 1024),
+        Some('m') => (&trimmed[..trimmed.len() - 1], 1024 * 1024),
+        Some('g') => (&trimmed[..trimmed.len() - 1], 1024 * 1024 * 1024),
+        _ => (&trimmed[..], 1),
+    };
+    match digits.parse::<usize>() {
+        Ok(size) if size > 0 => size
+            .checked_mul(multiplier)
+            .ok_or_else(|| format!("Heap size '{value}' is too large.")),
+        _ => Err(format!("Invalid heap size '{value}': expected a positive number of bytes.")),
+    }
+}
+
+/// A generational mark & sweep collector.
+///
+/// Objects are allocated into the nursery (the `young` list). A minor collection treats every old
+/// object as live, traces from the roots and the remembered set, frees unreachable young objects and
+/// promotes the survivors. A major collection traces and sweeps both generations.
+///
+/// Any store of a reference into an old object must go through [`Gc::write_barrier`], so that the
+/// old object is traced during the next minor collection.
+#[derive(Debug)]
 pub struct Gc {
+    config: GcConfig,
+    stats: RefCell<GcStats>,
     next_gc: usize,
-    first: Option<NonNull<GcObject>>,
+    nursery_bytes: Cell<usize>,
+    young: Option<NonNull<GcObject>>,
+    old: Option<NonNull<GcObject>>,
     strings: Table,
     grey_stack: Vec<NonNull<GcObject>>,
+    remembered: RefCell<Vec<NonNull<GcObject>>>,
+    collecting: Collection,
+    collection_start: Option<Instant>,
+    /// Whether the heap was over `max_heap` when it was last checked
+    over_limit: Cell<bool>,
+    /// Collected objects, which are only released when the collector is dropped so that any use of
+    /// a stale reference can be caught
+    #[cfg(feature = "checked")]
+    quarantine: Vec<NonNull<GcObject>>,
 }
 
 impl Default for Gc {
     fn default() -> Self {
+        Self::new(GcConfig::default())
+    }
+}
+
+impl Gc {
+    pub fn new(config: GcConfig) -> Self {
         Self {
-            next_gc: 1024 * 1024,
-            first: None,
+            config,
+            stats: RefCell::new(GcStats::default()),
+            next_gc: config.initial_heap,
+            nursery_bytes: Cell::new(0),
+            young: None,
+            old: None,
             strings: Table::default(),
             grey_stack: Vec::new(),
+            remembered: RefCell::new(Vec::new()),
+            collecting: Collection::Major,
+            collection_start: None,
+            over_limit: Cell::new(false),
+            #[cfg(feature = "checked")]
+            quarantine: Vec::new(),
         }
     }
-}
 
-impl Gc {
-    const HEAP_GROW_FACTOR: usize = 2;
+    pub fn config(&self) -> &GcConfig {
+        &self.config
+    }
 
-    pub fn alloc<T: Debug + 'static>(&mut self, object: T) -> GcRef<T> {
+    pub fn stats(&self) -> Ref<'_, GcStats> {
+        self.stats.borrow()
+    }
+
+    /// Bytes currently taken up by the heap
+    pub fn heap_size(&self) -> usize {
+        self.stats.borrow().bytes_live()
+    }
+
+    pub fn alloc<T: Debug + HeapSize + 'static>(&mut self, object: T) -> GcRef<T> {
         unsafe {
+            let size = object.size();
             let boxed = Box::new(object);
             let pointer = NonNull::new_unchecked(Box::into_raw(boxed));
-            let mut header: NonNull<GcObject> = mem::transmute(pointer.as_ref());
-            header.as_mut().next = self.first.take();
-            self.first = Some(header);
+            let mut header: NonNull<GcObject> = pointer.cast();
+            header.as_mut().next = self.young.take();
+            header.as_mut().size = size;
+            self.young = Some(header);
+            self.nursery_bytes.set(self.nursery_bytes.get() + size);
+            self.stats.get_mut().record_alloc(header.as_ref().obj_type, size);
+            self.check_limit();
 
             GcRef { pointer }
         }
     }
 
+    /// Updates the size accounted to `object` after one of its buffers grew or shrank, e.g. after
+    /// pushing to an array or inserting into a table.
+    pub fn resize<T: Debug + HeapSize>(&self, object: GcRef<T>) {
+        let size = object.size();
+        let mut header = object.object();
+        let header = unsafe { header.as_mut() };
+        let mut stats = self.stats.borrow_mut();
+        if size > header.size {
+            let grown = size - header.size;
+            stats.bytes_allocated += grown;
+            if header.generation == Generation::Young {
+                self.nursery_bytes.set(self.nursery_bytes.get() + grown);
+            }
+        } else {
+            stats.bytes_freed += header.size - size;
+        }
+        header.size = size;
+        drop(stats);
+        self.check_limit();
+    }
+
+    /// Whether the heap had grown past `max_heap` when it was last checked, which happens on every
+    /// allocation. Some of it may be garbage, so the vm collects it before giving up.
+    pub fn over_limit(&self) -> bool {
+        self.over_limit.get()
+    }
+
+    /// Checks the size of the heap against `max_heap` again, returning whether it is over it.
+    pub fn check_limit(&self) -> bool {
+        let over = self.config.max_heap.is_some_and(|max| self.heap_size() > max);
+        self.over_limit.set(over);
+        over
+    }
+
     pub fn intern(&mut self, s: impl Into<String>) -> GcRef<Str> {
         let ls = Str::from_string(s.into());
         if let Some(value) = self.strings.find_string(&ls.s, ls.hash) {
@@ -121,11 +420,100 @@ impl Gc {
         }
     }
 
+    /// Records that `object` was mutated to hold a new reference. Old objects are added to the
+    /// remembered set so that anything young they now point to survives the next minor collection.
+    pub fn write_barrier<T: Debug>(&self, object: GcRef<T>) {
+        unsafe {
+            let mut header = object.object();
+            let header_ref = header.as_mut();
+            if header_ref.generation == Generation::Old && !header_ref.remembered {
+                header_ref.remembered = true;
+                self.remembered.borrow_mut().push(header);
+            }
+        }
+    }
+
+    /// Returns the kind of collection that is due, if any. In stress mode a collection is due on
+    /// every allocation, alternating between minor and major collections.
+    pub fn should_gc(&self) -> Option<Collection> {
+        if self.config.stress {
+            match self.stats.borrow().collections() % 2 {
+                0 => Some(Collection::Minor),
+                _ => Some(Collection::Major),
+            }
+        } else if self.heap_size() > self.next_gc {
+            Some(Collection::Major)
+        } else if self.nursery_bytes.get() > self.config.nursery_size {
+            Some(Collection::Minor)
+        } else {
+            None
+        }
+    }
+
+    /// Starts a collection. The roots must be marked after this and before [`Gc::collect_garbage`].
+    pub fn begin_collection(&mut self, collection: Collection) {
+        self.collecting = collection;
+        // wasm32-unknown-unknown has no clock, so pauses aren't timed there
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.collection_start = Some(Instant::now());
+        }
+        if collection == Collection::Major {
+            // Every young survivor is promoted at the end of this collection, so nothing needs to
+            // be remembered any more
+            self.forget_remembered();
+        }
+    }
+
     pub fn collect_garbage(&mut self) {
+        let bytes_before = self.heap_size();
+        let freed_before = self.stats.get_mut().bytes_freed;
+
+        if self.collecting == Collection::Minor {
+            let remembered = self.remembered.borrow().clone();
+            for object in remembered {
+                self.blacken_object(object);
+            }
+        }
         self.trace_references();
         self.remove_white_strings();
-        self.sweep();
-        self.next_gc = GLOBAL.bytes_allocated() * Gc::HEAP_GROW_FACTOR;
+        if self.collecting == Collection::Major {
+            let old = self.old.take();
+            self.old = self.sweep(old);
+        }
+        let young = self.young.take();
+        self.young = self.sweep(young);
+        if self.collecting == Collection::Major {
+            let next_gc = self.heap_size() as f64 * self.config.grow_factor;
+            self.next_gc = (next_gc as usize).max(self.config.initial_heap);
+        }
+        self.forget_remembered();
+        self.nursery_bytes.set(0);
+
+        let pause = self.collection_start.take().map(|start| start.elapsed()).unwrap_or_default();
+        let stats = self.stats.get_mut();
+        stats.record_pause(pause);
+        match self.collecting {
+            Collection::Minor => stats.minor_collections += 1,
+            Collection::Major => stats.major_collections += 1,
+        }
+        if self.config.log {
+            eprintln!(
+                "[gc] {:?} collection #{}: {} -> {} bytes ({} freed) in {:?}",
+                self.collecting,
+                stats.collections(),
+                bytes_before,
+                stats.bytes_live(),
+                stats.bytes_freed - freed_before,
+                pause
+            );
+        }
+    }
+
+    fn forget_remembered(&mut self) {
+        for mut object in self.remembered.get_mut().drain(..) {
+            unsafe { object.as_mut().remembered = false };
+        }
     }
 
     fn trace_references(&mut self) {
@@ -135,77 +523,113 @@ impl Gc {
     }
 
     fn blacken_object(&mut self, pointer: NonNull<GcObject>) {
+        let collecting = self.collecting;
+        let grey_stack = &mut self.grey_stack;
+        Gc::trace(pointer, &mut |child| Gc::mark(collecting, grey_stack, child));
+    }
+
+    /// Calls `visit` with every object directly referenced by `pointer`.
+    fn trace(pointer: NonNull<GcObject>, visit: &mut impl FnMut(NonNull<GcObject>)) {
+        let visit_value = |value: Value, visit: &mut dyn FnMut(NonNull<GcObject>)| {
+            if let Some(object) = value.object() {
+                visit(object)
+            }
+        };
         let object_type = unsafe { &pointer.as_ref().obj_type };
 
         match object_type {
             ObjectType::Array => {
-                let _: &Array = unsafe { mem::transmute(pointer.as_ref()) };
+                let array: &Array = unsafe { pointer.cast().as_ref() };
+                for &item in &array.items {
+                    visit_value(item, visit);
+                }
             }
             ObjectType::Function => {
-                let function: &Function = unsafe { mem::transmute(pointer.as_ref()) };
-                self.mark_object(function.name);
+                let function: &Function = unsafe { pointer.cast().as_ref() };
+                visit(function.name.object());
                 for &constant in &function.chunk.constants {
-                    self.mark_value(constant);
+                    visit_value(constant, visit);
                 }
             }
             ObjectType::Closure => {
-                let closure: &Closure = unsafe { mem::transmute(pointer.as_ref()) };
-                self.mark_object(closure.function);
+                let closure: &Closure = unsafe { pointer.cast().as_ref() };
+                visit(closure.function.object());
                 for &upvalue in &closure.upvalues {
-                    self.mark_object(upvalue);
+                    visit(upvalue.object());
                 }
             }
+            ObjectType::Foreign => {
+                let foreign: &Foreign = unsafe { pointer.cast().as_ref() };
+                visit(foreign.class.object());
+            }
+            ObjectType::Native => {
+                let native: &Native = unsafe { pointer.cast().as_ref() };
+                visit(native.name.object());
+            }
             ObjectType::Str => {}
             ObjectType::Upvalue => {
-                let upvalue: &Upvalue = unsafe { mem::transmute(pointer.as_ref()) };
+                let upvalue: &Upvalue = unsafe { pointer.cast().as_ref() };
                 if let Some(obj) = upvalue.closed {
-                    self.mark_value(obj)
+                    visit_value(obj, visit)
                 }
             }
             ObjectType::Class => {
-                let class: &Class = unsafe { mem::transmute(pointer.as_ref()) };
-                self.mark_object(class.name);
-                self.mark_table(&class.methods);
+                let class: &Class = unsafe { pointer.cast().as_ref() };
+                visit(class.name.object());
+                Gc::trace_table(&class.methods, visit);
+                Gc::trace_table(&class.fields, visit);
             }
             ObjectType::Instance => {
-                let instance: &Instance = unsafe { mem::transmute(pointer.as_ref()) };
-                self.mark_object(instance.class);
-                self.mark_table(&instance.fields);
+                let instance: &Instance = unsafe { pointer.cast().as_ref() };
+                visit(instance.class.object());
+                Gc::trace_table(&instance.fields, visit);
             }
             ObjectType::BoundMethod => {
-                let method: &BoundMethod = unsafe { mem::transmute(pointer.as_ref()) };
-                self.mark_value(method.receiver);
-                self.mark_object(method.method);
+                let method: &BoundMethod = unsafe { pointer.cast().as_ref() };
+                visit_value(method.receiver, visit);
+                visit(method.method.object());
             }
             ObjectType::Role => {
-                let role: &RoleImpl = unsafe { mem::transmute(pointer.as_ref()) };
-                self.mark_object(role.class);
-                self.mark_table(&role.methods);
+                let role: &RoleImpl = unsafe { pointer.cast().as_ref() };
+                visit(role.class.object());
+                Gc::trace_table(&role.methods, visit);
             }
         }
     }
 
-    pub fn mark_value(&mut self, value: Value) {
-        match value {
-            Value::Array(value) => self.mark_object(value),
-            Value::BoundMethod(value) => self.mark_object(value),
-            Value::Class(value) => self.mark_object(value),
-            Value::Closure(value) => self.mark_object(value),
-            Value::Function(value) => self.mark_object(value),
-            Value::Instance(value) => self.mark_object(value),
-            Value::String(value) => self.mark_object(value),
-            _ => (),
+    fn trace_table(table: &Table, visit: &mut impl FnMut(NonNull<GcObject>)) {
+        for (k, v) in table.iter() {
+            visit(k.object());
+            if let Some(object) = v.object() {
+                visit(object);
+            }
         }
     }
 
-    pub fn mark_object<T: 'static + Debug>(&mut self, mut reference: GcRef<T>) {
-        unsafe {
-            let mut header: NonNull<GcObject> = mem::transmute(reference.pointer.as_mut());
-            header.as_mut().marked = true;
-            self.grey_stack.push(header);
+    fn mark(
+        collecting: Collection,
+        grey_stack: &mut Vec<NonNull<GcObject>>,
+        mut object: NonNull<GcObject>,
+    ) {
+        let header = unsafe { object.as_mut() };
+        let skip = header.marked
+            || (collecting == Collection::Minor && header.generation == Generation::Old);
+        if !skip {
+            header.marked = true;
+            grey_stack.push(object);
+        }
+    }
+
+    pub fn mark_value(&mut self, value: Value) {
+        if let Some(object) = value.object() {
+            Gc::mark(self.collecting, &mut self.grey_stack, object);
         }
     }
 
+    pub fn mark_object<T: 'static + Debug>(&mut self, reference: GcRef<T>) {
+        Gc::mark(self.collecting, &mut self.grey_stack, reference.object());
+    }
+
     pub fn mark_table(&mut self, table: &Table) {
         for (k, v) in table.iter() {
             self.mark_object(k);
@@ -213,64 +637,368 @@ impl Gc {
         }
     }
 
-    pub fn should_gc(&self) -> bool {
-        GLOBAL.bytes_allocated() > self.next_gc
-    }
-
-    fn sweep(&mut self) {
+    /// Frees every unmarked object of the list starting at `first` and returns the new head of the
+    /// list. Young survivors are promoted onto the old list instead.
+    fn sweep(&mut self, first: Option<NonNull<GcObject>>) -> Option<NonNull<GcObject>> {
+        let mut head: Option<NonNull<GcObject>> = None;
         let mut previous: Option<NonNull<GcObject>> = None;
-        let mut current: Option<NonNull<GcObject>> = self.first;
+        let mut current: Option<NonNull<GcObject>> = first;
         while let Some(mut object) = current {
             unsafe {
                 let object_ptr = object.as_mut();
                 current = object_ptr.next;
-                if object_ptr.marked {
-                    object_ptr.marked = false;
-                    previous = Some(object);
-                } else if let Some(mut previous) = previous {
-                    previous.as_mut().next = object_ptr.next
-                } else {
-                    self.first = object_ptr.next
+                if !object_ptr.marked {
+                    self.stats.get_mut().record_free(object_ptr.obj_type, object_ptr.size);
+                    self.release(object);
+                    continue;
+                }
+                object_ptr.marked = false;
+                if object_ptr.generation == Generation::Young {
+                    object_ptr.generation = Generation::Old;
+                    object_ptr.next = self.old.take();
+                    self.old = Some(object);
+                    continue;
+                }
+                object_ptr.next = None;
+                match previous {
+                    Some(mut previous) => previous.as_mut().next = Some(object),
+                    None => head = Some(object),
                 }
+                previous = Some(object);
+            }
+        }
+        head
+    }
+
+    #[cfg(not(feature = "checked"))]
+    unsafe fn release(&mut self, object: NonNull<GcObject>) {
+        Gc::free(object);
+    }
+
+    #[cfg(feature = "checked")]
+    unsafe fn release(&mut self, mut object: NonNull<GcObject>) {
+        // The object outlives its collection here, but its finalizer must still run with it
+        if object.as_ref().obj_type == ObjectType::Foreign {
+            object.cast::<Foreign>().as_mut().finalize();
+        }
+        object.as_mut().freed = true;
+        object.as_mut().next = None;
+        self.quarantine.push(object);
+    }
+
+    unsafe fn free(object: NonNull<GcObject>) {
+        unsafe fn free_as<T>(object: NonNull<GcObject>) {
+            drop(Box::from_raw(object.as_ptr() as *mut T));
+        }
+        match object.as_ref().obj_type {
+            ObjectType::Array => free_as::<Array>(object),
+            ObjectType::BoundMethod => free_as::<BoundMethod>(object),
+            ObjectType::Class => free_as::<Class>(object),
+            ObjectType::Closure => free_as::<Closure>(object),
+            ObjectType::Foreign => free_as::<Foreign>(object),
+            ObjectType::Function => free_as::<Function>(object),
+            ObjectType::Instance => free_as::<Instance>(object),
+            ObjectType::Native => free_as::<Native>(object),
+            ObjectType::Role => free_as::<RoleImpl>(object),
+            ObjectType::Str => free_as::<Str>(object),
+            ObjectType::Upvalue => free_as::<Upvalue>(object),
+        }
+    }
+
+    /// The current size of the object, recomputed from its buffers
+    fn measure(object: NonNull<GcObject>) -> usize {
+        unsafe fn measure_as<T: HeapSize>(object: NonNull<GcObject>) -> usize {
+            (*(object.as_ptr() as *const T)).size()
+        }
+        unsafe {
+            match object.as_ref().obj_type {
+                ObjectType::Array => measure_as::<Array>(object),
+                ObjectType::BoundMethod => measure_as::<BoundMethod>(object),
+                ObjectType::Class => measure_as::<Class>(object),
+                ObjectType::Closure => measure_as::<Closure>(object),
+                ObjectType::Foreign => measure_as::<Foreign>(object),
+                ObjectType::Function => measure_as::<Function>(object),
+                ObjectType::Instance => measure_as::<Instance>(object),
+                ObjectType::Native => measure_as::<Native>(object),
+                ObjectType::Role => measure_as::<RoleImpl>(object),
+                ObjectType::Str => measure_as::<Str>(object),
+                ObjectType::Upvalue => measure_as::<Upvalue>(object),
             }
         }
     }
 
     fn remove_white_strings(&mut self) {
         for (k, _v) in self.strings.iter() {
-            let header: &GcObject = unsafe { mem::transmute(k.pointer.as_ref()) };
-            if !header.marked {
+            let header = k.header();
+            let collected =
+                self.collecting == Collection::Major || header.generation == Generation::Young;
+            if collected && !header.marked {
                 self.strings.delete(k);
             }
         }
     }
-}
 
-struct GlobalAllocator {
-    bytes_allocated: AtomicUsize,
+    fn objects(&self) -> impl Iterator<Item = NonNull<GcObject>> {
+        let list = |first: Option<NonNull<GcObject>>| {
+            std::iter::successors(first, |object| unsafe { object.as_ref().next })
+        };
+        list(self.young).chain(list(self.old))
+    }
+
+    /// Checks the heap after a collection, panicking if anything is inconsistent: every root, every
+    /// interned string and every reference held by a live object must point at a live object, and
+    /// every object's accounted size must be up to date.
+    pub fn verify_heap(&self, roots: &[NonNull<GcObject>]) {
+        let mut live = HashSet::new();
+        for object in self.objects() {
+            let header = unsafe { object.as_ref() };
+            if !live.insert(object) {
+                panic!("GC heap verification failed: {:?} is linked twice", header.obj_type);
+            }
+            if header.marked || header.remembered {
+                panic!("GC heap verification failed: {:?} was left marked", header.obj_type);
+            }
+            if header.size != Gc::measure(object) {
+                panic!(
+                    "GC heap verification failed: {:?} was resized without updating its size",
+                    header.obj_type
+                );
+            }
+        }
+        if let Some(young) = self.young {
+            let obj_type = unsafe { young.as_ref().obj_type };
+            panic!("GC heap verification failed: {:?} was not promoted", obj_type);
+        }
+        let check = |object: NonNull<GcObject>, from: &dyn Fn() -> String| {
+            if !live.contains(&object) {
+                panic!("GC heap verification failed: {} refers to a freed object", from());
+            }
+        };
+        for &root in roots {
+            check(root, &|| "a root".to_owned());
+        }
+        for (k, _v) in self.strings.iter() {
+            check(k.object(), &|| "the string table".to_owned());
+        }
+        for object in self.objects() {
+            let obj_type = unsafe { object.as_ref().obj_type };
+            Gc::trace(object, &mut |child| check(child, &|| format!("a live {obj_type:?}")));
+        }
+    }
 }
 
-impl GlobalAllocator {
-    fn bytes_allocated(&self) -> usize {
-        self.bytes_allocated.load(std::sync::atomic::Ordering::Relaxed)
+impl Drop for Gc {
+    fn drop(&mut self) {
+        let objects: Vec<NonNull<GcObject>> = self.objects().collect();
+        for object in objects {
+            unsafe { Gc::free(object) };
+        }
+        #[cfg(feature = "checked")]
+        for object in self.quarantine.drain(..) {
+            unsafe { Gc::free(object) };
+        }
     }
 }
 
-unsafe impl alloc::GlobalAlloc for GlobalAllocator {
-    unsafe fn alloc(&self, layout: alloc::Layout) -> *mut u8 {
-        self.bytes_allocated
-            .fetch_add(layout.size(), std::sync::atomic::Ordering::Relaxed);
-        mimalloc::MiMalloc.alloc(layout)
+#[cfg(test)]
+mod tests {
+    use std::ptr::NonNull;
+
+    use super::{parse_size, Collection, Gc, GcConfig, GcObject, Generation};
+    use crate::{
+        objects::{Array, ObjectType, Str},
+        value::Value,
+    };
+
+    fn count(mut current: Option<NonNull<GcObject>>) -> usize {
+        let mut count = 0;
+        while let Some(object) = current {
+            count += 1;
+            current = unsafe { object.as_ref().next };
+        }
+        count
     }
 
-    unsafe fn dealloc(&self, ptr: *mut u8, layout: alloc::Layout) {
-        mimalloc::MiMalloc.dealloc(ptr, layout);
-        self.bytes_allocated
-            .fetch_sub(layout.size(), std::sync::atomic::Ordering::Relaxed);
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("512k"), Ok(512 * 1024));
+        assert_eq!(parse_size("16M"), Ok(16 * 1024 * 1024));
+        assert_eq!(parse_size("1g"), Ok(1024 * 1024 * 1024));
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("lots").is_err());
     }
-}
 
-#[global_allocator]
-static GLOBAL: GlobalAllocator = GlobalAllocator {
-    bytes_allocated: AtomicUsize::new(0),
-};
+    #[test]
+    fn grow_factor() {
+        let mut config = GcConfig::default();
+        assert!(config.set_grow_factor("1.5").is_ok());
+        assert_eq!(config.grow_factor, 1.5);
+        assert!(config.set_grow_factor("1").is_err());
+        assert!(config.set_grow_factor("fast").is_err());
+    }
+
+    #[test]
+    fn flags_the_heap_over_its_limit() {
+        let mut gc = Gc::new(GcConfig {
+            max_heap: Some(1024),
+            ..GcConfig::default()
+        });
+        gc.alloc(Str::from_string("small".to_owned()));
+        assert!(!gc.over_limit());
+        gc.alloc(Str::from_string("large".repeat(300)));
+        assert!(gc.over_limit());
+
+        // Nothing is rooted, so a major collection brings it back under
+        gc.begin_collection(Collection::Major);
+        gc.collect_garbage();
+        assert!(!gc.check_limit());
+        assert!(!gc.over_limit());
+    }
+
+    #[test]
+    fn minor_collection_promotes_survivors() {
+        let mut gc = Gc::default();
+        let live = gc.alloc(Str::from_string("live".to_owned()));
+        gc.alloc(Str::from_string("dead".to_owned()));
+        assert_eq!(count(gc.young), 2);
+
+        gc.begin_collection(Collection::Minor);
+        gc.mark_object(live);
+        gc.collect_garbage();
+
+        assert_eq!(count(gc.young), 0);
+        assert_eq!(count(gc.old), 1);
+        assert_eq!(live.header().generation(), Generation::Old);
+        assert_eq!(live.s, "live");
+    }
+
+    #[test]
+    fn write_barrier_keeps_young_values() {
+        let mut gc = Gc::default();
+        let array = gc.alloc(Array::new(vec![]));
+        gc.begin_collection(Collection::Minor);
+        gc.mark_object(array);
+        gc.collect_garbage();
+
+        let mut old = array;
+        let young = gc.alloc(Str::from_string("young".to_owned()));
+        old.items.push(Value::String(young));
+        gc.write_barrier(old);
+
+        // The old array is not a root, but it is remembered
+        gc.begin_collection(Collection::Minor);
+        gc.collect_garbage();
+
+        assert_eq!(count(gc.old), 2);
+        assert_eq!(young.header().generation(), Generation::Old);
+        assert_eq!(young.s, "young");
+    }
+
+    #[test]
+    fn major_collection_frees_old_objects() {
+        let mut gc = Gc::default();
+        let live = gc.alloc(Array::new(vec![]));
+        gc.alloc(Array::new(vec![]));
+        gc.begin_collection(Collection::Minor);
+        gc.mark_object(live);
+        gc.collect_garbage();
+        assert_eq!(count(gc.old), 1);
+
+        gc.begin_collection(Collection::Major);
+        gc.collect_garbage();
+        assert_eq!(count(gc.old), 0);
+    }
+
+    #[test]
+    fn stats() {
+        let mut gc = Gc::default();
+        let live = gc.alloc(Array::new(vec![]));
+        gc.intern("dead");
+        gc.begin_collection(Collection::Minor);
+        gc.mark_object(live);
+        gc.collect_garbage();
+
+        let stats = gc.stats();
+        assert_eq!(stats.minor_collections, 1);
+        assert_eq!(stats.major_collections, 0);
+        assert_eq!(stats.object_count(ObjectType::Array).live(), 1);
+        assert_eq!(stats.object_count(ObjectType::Str).freed, 1);
+        assert_eq!(stats.bytes_live(), std::mem::size_of::<Array>());
+    }
+
+    #[test]
+    fn verify_heap() {
+        let mut gc = Gc::default();
+        let string = gc.intern("string");
+        let array = gc.alloc(Array::new(vec![Value::String(string)]));
+        gc.begin_collection(Collection::Major);
+        gc.mark_object(array);
+        gc.collect_garbage();
+        gc.verify_heap(&[array.object()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "GC heap verification failed")]
+    fn verify_heap_finds_freed_roots() {
+        let mut gc = Gc::default();
+        let live = gc.alloc(Array::new(vec![]));
+        let dead = gc.alloc(Array::new(vec![]));
+        gc.begin_collection(Collection::Major);
+        gc.mark_object(live);
+        gc.collect_garbage();
+        gc.verify_heap(&[live.object(), dead.object()]);
+    }
+
+    #[test]
+    fn resize() {
+        let mut gc = Gc::default();
+        let mut array = gc.alloc(Array::new(vec![]));
+        assert_eq!(gc.heap_size(), std::mem::size_of::<Array>());
+
+        for i in 0..10 {
+            array.items.push(Value::Int(i));
+        }
+        gc.resize(array);
+        let expected =
+            std::mem::size_of::<Array>() + array.items.capacity() * std::mem::size_of::<Value>();
+        assert_eq!(array.header().size(), expected);
+        assert_eq!(gc.heap_size(), expected);
+
+        array.items.clear();
+        array.items.shrink_to_fit();
+        gc.resize(array);
+        assert_eq!(gc.heap_size(), std::mem::size_of::<Array>());
+    }
+
+    #[test]
+    #[should_panic(expected = "without updating its size")]
+    fn verify_heap_finds_stale_sizes() {
+        let mut gc = Gc::default();
+        let mut array = gc.alloc(Array::new(vec![]));
+        gc.begin_collection(Collection::Major);
+        gc.mark_object(array);
+        gc.collect_garbage();
+        array.items.push(Value::Nil);
+        gc.verify_heap(&[array.object()]);
+    }
+
+    #[test]
+    fn drop_frees_objects() {
+        let mut gc = Gc::default();
+        let strings: Vec<_> = (0..10).map(|i| gc.intern(format!("string {}", i))).collect();
+        gc.alloc(Array::new(strings.into_iter().map(Value::String).collect()));
+        drop(gc);
+    }
+
+    #[cfg(feature = "checked")]
+    #[test]
+    #[should_panic(expected = "Use of a freed Str object")]
+    fn checked_use_after_free() {
+        let mut gc = Gc::default();
+        let string = gc.alloc(Str::from_string("freed".to_owned()));
+        gc.begin_collection(Collection::Major);
+        gc.collect_garbage();
+        assert_eq!(string.s, "freed");
+    }
+}
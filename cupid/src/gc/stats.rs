@@ -0,0 +1,103 @@
+use std::{fmt, time::Duration};
+
+use crate::objects::ObjectType;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct ObjectCount {
+    pub allocated: usize,
+    pub freed: usize,
+}
+
+impl ObjectCount {
+    pub fn live(&self) -> usize {
+        self.allocated - self.freed
+    }
+}
+
+/// Running totals kept by the collector, printed with `--gc-stats`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct GcStats {
+    pub minor_collections: usize,
+    pub major_collections: usize,
+    pub bytes_allocated: usize,
+    pub bytes_freed: usize,
+    pub objects: [ObjectCount; ObjectType::COUNT],
+    pub total_pause: Duration,
+    pub max_pause: Duration,
+}
+
+impl GcStats {
+    pub fn collections(&self) -> usize {
+        self.minor_collections + self.major_collections
+    }
+
+    pub fn bytes_live(&self) -> usize {
+        self.bytes_allocated - self.bytes_freed
+    }
+
+    pub fn object_count(&self, obj_type: ObjectType) -> ObjectCount {
+        self.objects[obj_type as usize]
+    }
+
+    pub(super) fn record_alloc(&mut self, obj_type: ObjectType, bytes: usize) {
+        self.objects[obj_type as usize].allocated += 1;
+        self.bytes_allocated += bytes;
+    }
+
+    pub(super) fn record_free(&mut self, obj_type: ObjectType, bytes: usize) {
+        self.objects[obj_type as usize].freed += 1;
+        self.bytes_freed += bytes;
+    }
+
+    pub(super) fn record_pause(&mut self, pause: Duration) {
+        self.total_pause += pause;
+        self.max_pause = self.max_pause.max(pause);
+    }
+
+    pub fn mean_pause(&self) -> Duration {
+        match self.collections() {
+            0 => Duration::ZERO,
+            n => self.total_pause / n as u32,
+        }
+    }
+}
+
+impl fmt::Display for GcStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "-- gc stats --")?;
+        writeln!(
+            f,
+            "collections: {} ({} minor, {} major)",
+            self.collections(),
+            self.minor_collections,
+            self.major_collections
+        )?;
+        writeln!(
+            f,
+            "bytes: {} allocated, {} freed, {} live",
+            self.bytes_allocated,
+            self.bytes_freed,
+            self.bytes_live()
+        )?;
+        writeln!(
+            f,
+            "pauses: {:?} total, {:?} mean, {:?} max",
+            self.total_pause,
+            self.mean_pause(),
+            self.max_pause
+        )?;
+        writeln!(f, "{:<12} {:>10} {:>10} {:>10}", "object", "allocated", "freed", "live")?;
+        for obj_type in ObjectType::ALL {
+            let count = self.object_count(obj_type);
+            writeln!(
+                f,
+                "{:<12} {:>10} {:>10} {:>10}",
+                format!("{obj_type:?}"),
+                count.allocated,
+                count.freed,
+                count.live()
+            )?;
+        }
+        Ok(())
+    }
+}
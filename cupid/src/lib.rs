@@ -0,0 +1,38 @@
+pub mod analyze;
+pub mod arena;
+pub mod ast;
+pub mod chunk;
+pub mod cli;
+pub mod code;
+pub mod compiler;
+pub mod cst;
+pub mod diagnostic;
+pub mod doc;
+pub mod emit;
+pub mod engine;
+pub mod error;
+pub mod expose;
+pub mod format;
+pub mod gc;
+pub mod objects;
+pub mod parse;
+pub mod pointer;
+pub mod repl;
+pub mod run;
+pub mod scanner;
+pub mod scope;
+pub mod signal;
+pub mod span;
+pub mod suggest;
+pub mod table;
+pub mod testing;
+pub mod token;
+pub mod ty;
+pub mod value;
+pub mod vm;
+
+extern crate cupid_fmt;
+#[cfg(unix)]
+extern crate libc;
+extern crate serde;
+extern crate serde_json;
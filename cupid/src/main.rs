@@ -1,38 +1,43 @@
-pub mod analyze;
-pub mod arena;
-pub mod ast;
-pub mod chunk;
-pub mod compiler;
-pub mod cst;
-pub mod error;
-pub mod expose;
-pub mod gc;
-pub mod objects;
-pub mod parse;
-pub mod pointer;
-pub mod repl;
-pub mod run;
-pub mod scanner;
-pub mod scope;
-pub mod span;
-pub mod table;
-pub mod token;
-pub mod ty;
-pub mod value;
-pub mod vm;
+extern crate cupid;
 
-use std::env;
-use std::process;
+use cupid::{
+    analyze::lint,
+    cli::{self, Command},
+    code, doc, emit, format, repl, run, signal, testing, vm,
+};
 
-extern crate cupid_fmt;
+// The allocator is the binary's choice, so hosts embedding the library keep their own. Miri can't
+// call into mimalloc and it isn't built for wasm, so both fall back to the system allocator.
+#[cfg(not(any(miri, target_arch = "wasm32")))]
+#[global_allocator]
+static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let mut vm = vm::Vm::default();
+    let options = cli::Options::from_args();
+    match (&options.command, &options.path) {
+        (Command::Fmt { check, width }, Some(path)) => {
+            return format::format_file(path, *width, *check)
+        }
+        (Command::Doc { format }, Some(path)) => return doc::document_file(path, *format),
+        (Command::Lint { config }, Some(path)) => {
+            return lint::lint_file(path, config, options.vm.error_format)
+        }
+        (Command::Run, Some(path)) if options.emit.is_some() => {
+            return emit::emit_file(path, options.emit.unwrap(), options.vm.error_format)
+        }
+        (Command::Explain, code) => return code::explain_command(code.as_deref()),
+        (Command::Test, path) => {
+            return testing::test_command(path.as_deref().unwrap_or("."), options.vm)
+        }
+        _ => (),
+    }
+    let mut vm = vm::Vm::with_config(options.vm);
     vm.initialize();
-    match args.len() {
-        1 => repl::repl(&mut vm),
-        2 => run::run_file(&mut vm, &args[1]),
-        _ => process::exit(64),
+    match options.path {
+        None => repl::repl(&mut vm),
+        Some(path) => {
+            signal::interrupt_on_ctrl_c(&vm.interrupt);
+            run::run_file(&mut vm, &path)
+        }
     }
 }
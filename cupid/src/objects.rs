@@ -7,6 +7,9 @@ pub use self::class::Class;
 pub mod closure;
 pub use self::closure::Closure;
 
+pub mod foreign;
+pub use self::foreign::Foreign;
+
 pub mod function;
 pub use self::function::{Function, NativeFunction};
 
@@ -16,6 +19,9 @@ pub use self::instance::Instance;
 pub mod method;
 pub use self::method::BoundMethod;
 
+pub mod native;
+pub use self::native::{Native, NativeFn};
+
 pub mod string;
 pub use self::string::Str;
 
@@ -25,15 +31,34 @@ pub use self::role::RoleImpl;
 pub mod upvalue;
 pub use self::upvalue::{FunctionUpvalue, Upvalue};
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ObjectType {
     Array,
     BoundMethod,
     Class,
     Closure,
+    Foreign,
     Function,
     Instance,
+    Native,
     Role,
     Str,
     Upvalue,
 }
+
+impl ObjectType {
+    pub const COUNT: usize = 11;
+    pub const ALL: [ObjectType; ObjectType::COUNT] = [
+        ObjectType::Array,
+        ObjectType::BoundMethod,
+        ObjectType::Class,
+        ObjectType::Closure,
+        ObjectType::Foreign,
+        ObjectType::Function,
+        ObjectType::Instance,
+        ObjectType::Native,
+        ObjectType::Role,
+        ObjectType::Str,
+        ObjectType::Upvalue,
+    ];
+}
@@ -1,6 +1,10 @@
-use std::fmt;
+use std::{fmt, mem};
 
-use crate::{gc::GcObject, objects::ObjectType, value::Value};
+use crate::{
+    gc::{GcObject, HeapSize},
+    objects::ObjectType,
+    value::Value,
+};
 
 #[repr(C)]
 #[derive(Debug, Clone)]
@@ -31,3 +35,9 @@ impl fmt::Display for Array {
         )
     }
 }
+
+impl HeapSize for Array {
+    fn heap_size(&self) -> usize {
+        self.items.capacity() * mem::size_of::<Value>()
+    }
+}
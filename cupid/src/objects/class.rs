@@ -1,7 +1,7 @@
 use std::{fmt, ops::Deref};
 
 use crate::{
-    gc::{GcObject, GcRef},
+    gc::{GcObject, GcRef, HeapSize},
     objects::{ObjectType, Str},
     table::Table,
 };
@@ -12,6 +12,9 @@ pub struct Class {
     pub header: GcObject,
     pub name: GcRef<Str>,
     pub methods: Table,
+    /// The fields declared in the class body and in those of its superclasses, by name, which
+    /// undefined properties are compared with.
+    pub fields: Table,
 }
 
 impl Class {
@@ -20,6 +23,7 @@ impl Class {
             header: GcObject::new(ObjectType::Class),
             name,
             methods: Table::default(),
+            fields: Table::default(),
         }
     }
 }
@@ -29,3 +33,9 @@ impl fmt::Display for Class {
         write!(f, "{}", self.name.deref())
     }
 }
+
+impl HeapSize for Class {
+    fn heap_size(&self) -> usize {
+        self.methods.heap_size() + self.fields.heap_size()
+    }
+}
@@ -1,7 +1,7 @@
-use std::{fmt, ops::Deref};
+use std::{fmt, mem, ops::Deref};
 
 use crate::{
-    gc::{GcObject, GcRef},
+    gc::{GcObject, GcRef, HeapSize},
     objects::{Function, ObjectType, Upvalue},
 };
 
@@ -28,3 +28,9 @@ impl fmt::Display for Closure {
         write!(f, "{}", self.function.deref())
     }
 }
+
+impl HeapSize for Closure {
+    fn heap_size(&self) -> usize {
+        self.upvalues.capacity() * mem::size_of::<GcRef<Upvalue>>()
+    }
+}
@@ -0,0 +1,79 @@
+use std::{any::Any, fmt, mem, ops::Deref};
+
+use crate::{
+    gc::{GcObject, GcRef, HeapSize},
+    objects::{Class, ObjectType},
+};
+
+type Finalizer = Box<dyn FnOnce(&mut dyn Any)>;
+
+/// A value of the host, owned by the heap. Its class holds the natives that Cupid can call as its
+/// methods, and its finalizer, if it has one, runs when it is collected.
+#[repr(C)]
+pub struct Foreign {
+    pub header: GcObject,
+    pub class: GcRef<Class>,
+    pub value: Box<dyn Any>,
+    finalizer: Option<Finalizer>,
+}
+
+impl Foreign {
+    pub fn new<T: Any>(class: GcRef<Class>, value: T) -> Self {
+        Foreign {
+            header: GcObject::new(ObjectType::Foreign),
+            class,
+            value: Box::new(value),
+            finalizer: None,
+        }
+    }
+
+    /// Runs `finalize` on the value once the object is collected, or when the heap is dropped
+    /// with the object still in it.
+    pub fn with_finalizer<T: Any>(mut self, finalize: impl FnOnce(&mut T) + 'static) -> Self {
+        self.finalizer = Some(Box::new(move |value: &mut dyn Any| {
+            if let Some(value) = value.downcast_mut::<T>() {
+                finalize(value)
+            }
+        }));
+        self
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref()
+    }
+
+    pub fn downcast_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.value.downcast_mut()
+    }
+
+    /// Runs the finalizer, if it hasn't run yet.
+    pub fn finalize(&mut self) {
+        if let Some(finalize) = self.finalizer.take() {
+            finalize(&mut *self.value)
+        }
+    }
+}
+
+impl Drop for Foreign {
+    fn drop(&mut self) {
+        self.finalize()
+    }
+}
+
+impl fmt::Debug for Foreign {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Foreign").field("class", &self.class.name.deref().s).finish()
+    }
+}
+
+impl fmt::Display for Foreign {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} object", self.class.name.deref())
+    }
+}
+
+impl HeapSize for Foreign {
+    fn heap_size(&self) -> usize {
+        mem::size_of_val(&*self.value)
+    }
+}
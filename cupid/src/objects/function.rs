@@ -1,8 +1,8 @@
-use std::{fmt, ops::Deref};
+use std::{fmt, mem, ops::Deref};
 
 use crate::{
     chunk::Chunk,
-    gc::{GcObject, GcRef},
+    gc::{GcObject, GcRef, HeapSize},
     objects::{FunctionUpvalue, ObjectType, Str},
     value::Value,
     vm::Vm,
@@ -42,6 +42,18 @@ impl Function {
             upvalues: Vec::new(),
         }
     }
+
+    /// The instructions of this function, followed by those of the functions it defines.
+    pub fn disassemble(&self) -> String {
+        let mut text = format!("== {self} ==\n{}", self.chunk.disassemble());
+        for constant in &self.chunk.constants {
+            if let Value::Function(function) = constant {
+                text.push('\n');
+                text.push_str(&function.disassemble());
+            }
+        }
+        text
+    }
 }
 
 impl fmt::Debug for Function {
@@ -65,3 +77,9 @@ impl fmt::Display for Function {
         }
     }
 }
+
+impl HeapSize for Function {
+    fn heap_size(&self) -> usize {
+        self.chunk.heap_size() + self.upvalues.capacity() * mem::size_of::<FunctionUpvalue>()
+    }
+}
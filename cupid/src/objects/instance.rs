@@ -1,7 +1,7 @@
 use std::{fmt, ops::Deref};
 
 use crate::{
-    gc::{GcObject, GcRef},
+    gc::{GcObject, GcRef, HeapSize},
     objects::{Class, ObjectType},
     table::Table,
 };
@@ -29,3 +29,9 @@ impl fmt::Display for Instance {
         write!(f, "{}", self.class.name.deref())
     }
 }
+
+impl HeapSize for Instance {
+    fn heap_size(&self) -> usize {
+        self.fields.heap_size()
+    }
+}
@@ -1,7 +1,7 @@
 use std::{fmt, ops::Deref};
 
 use crate::{
-    gc::{GcObject, GcRef},
+    gc::{GcObject, GcRef, HeapSize},
     objects::{Closure, ObjectType},
     value::Value,
 };
@@ -29,3 +29,9 @@ impl fmt::Display for BoundMethod {
         write!(f, "{}", self.method.function.deref())
     }
 }
+
+impl HeapSize for BoundMethod {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
@@ -0,0 +1,55 @@
+use std::{fmt, mem, ops::Deref};
+
+use crate::{
+    error::CupidErr,
+    gc::{GcObject, GcRef, HeapSize},
+    objects::{ObjectType, Str},
+    value::Value,
+    vm::Vm,
+};
+
+/// A function defined by the host. Unlike a [`NativeFunction`](super::NativeFunction), it may
+/// capture state of its own, call back into the vm with [`Vm::call`] and fail with a runtime error.
+pub type NativeFn = dyn Fn(&mut Vm, &[Value]) -> Result<Value, CupidErr>;
+
+/// A native living in the heap, called with exactly `arity` arguments. As a method, it is also
+/// given its receiver, first.
+#[repr(C)]
+pub struct Native {
+    pub header: GcObject,
+    pub name: GcRef<Str>,
+    pub arity: usize,
+    pub function: Box<NativeFn>,
+}
+
+impl Native {
+    pub fn new(name: GcRef<Str>, arity: usize, function: Box<NativeFn>) -> Self {
+        Native {
+            header: GcObject::new(ObjectType::Native),
+            name,
+            arity,
+            function,
+        }
+    }
+}
+
+impl fmt::Debug for Native {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Native")
+            .field("name", &self.name.deref().s)
+            .field("arity", &self.arity)
+            .finish()
+    }
+}
+
+impl fmt::Display for Native {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fun {}>", self.name.deref())
+    }
+}
+
+impl HeapSize for Native {
+    fn heap_size(&self) -> usize {
+        mem::size_of_val(&*self.function)
+    }
+}
@@ -1,7 +1,7 @@
 use std::{fmt, ops::Deref};
 
 use crate::{
-    gc::{GcObject, GcRef},
+    gc::{GcObject, GcRef, HeapSize},
     objects::{Class, ObjectType, Str},
     table::Table,
 };
@@ -31,3 +31,9 @@ impl fmt::Display for RoleImpl {
         write!(f, "{}", self.class.deref())
     }
 }
+
+impl HeapSize for RoleImpl {
+    fn heap_size(&self) -> usize {
+        self.methods.heap_size()
+    }
+}
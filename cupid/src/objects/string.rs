@@ -1,6 +1,9 @@
 use std::fmt;
 
-use crate::{gc::GcObject, objects::ObjectType};
+use crate::{
+    gc::{GcObject, HeapSize},
+    objects::ObjectType,
+};
 
 #[repr(C)]
 #[derive(Debug)]
@@ -35,3 +38,9 @@ impl fmt::Display for Str {
         write!(f, "{}", self.s)
     }
 }
+
+impl HeapSize for Str {
+    fn heap_size(&self) -> usize {
+        self.s.capacity()
+    }
+}
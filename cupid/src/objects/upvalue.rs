@@ -1,6 +1,10 @@
 use std::fmt;
 
-use crate::{gc::GcObject, objects::ObjectType, value::Value};
+use crate::{
+    gc::{GcObject, HeapSize},
+    objects::ObjectType,
+    value::Value,
+};
 
 #[repr(C)]
 #[derive(Debug)]
@@ -31,3 +35,9 @@ pub struct FunctionUpvalue {
     pub index: u8,
     pub is_local: bool,
 }
+
+impl HeapSize for Upvalue {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
@@ -1,7 +1,10 @@
 use crate::{
     arena::{EntryId, ExprArena, UseArena},
     chunk::Instruction,
-    compiler::{ClassCompiler, Compiler, FunctionType, Local},
+    code::Code,
+    compiler::{ClassCompiler, Compiler, FunctionType, Local, TryBody},
+    cst::SourceId,
+    error::{CupidError, Kind},
     gc::{Gc, GcRef},
     objects::Function,
     token::TokenType,
@@ -11,12 +14,15 @@ use std::convert::TryFrom;
 
 use ast::{
     Array, BinOp, Block, Break, Call, Class, Constant, Define, Expr, Fun, Get, GetProperty,
-    GetSuper, If, Invoke, InvokeSuper, Loop, Method, Return, Set, SetProperty, UnOp,
+    GetSuper, Header, If, Invoke, InvokeSuper, Loop, Method, Propagate, Return, Set, SetProperty,
+    Throw, Try, UnOp,
 };
 
 #[derive(Default)]
 pub struct Errors {
-    resolver: Vec<&'static str>,
+    resolver: Vec<(Code, &'static str)>,
+    /// The first error found, which the whole compilation fails with.
+    compile: Option<CupidError>,
 }
 
 pub struct BytecodeCompiler<'src> {
@@ -27,6 +33,12 @@ pub struct BytecodeCompiler<'src> {
     pub gc: &'src mut Gc,
     pub errors: Errors,
     pub loop_jumps: Vec<Vec<usize>>,
+    /// How many try and catch bodies each loop is in.
+    pub loop_handlers: Vec<usize>,
+    /// The source line of the expression being compiled, which runtime errors report.
+    pub line: usize,
+    /// The source of the expression being compiled, which compile errors point at.
+    pub source: Option<SourceId>,
 }
 
 impl<'src> BytecodeCompiler<'src> {
@@ -37,24 +49,102 @@ impl<'src> BytecodeCompiler<'src> {
             arena,
             errors: Errors::default(),
             loop_jumps: vec![],
+            loop_handlers: vec![],
+            line: 0,
+            source: None,
             compiler: Compiler::new(function_name, FunctionType::Script),
             class_compiler: None,
             gc,
         }
     }
 
-    pub fn compile(mut self) -> GcRef<Function> {
+    pub fn compile(mut self) -> Result<GcRef<Function>, CupidError> {
         let exprs = std::mem::take(&mut self.expr);
         for expr in exprs {
             expr.compile(&mut self);
         }
         self.write_return();
-        self.gc.alloc(self.compiler.function)
+        self.finish()
+    }
+
+    /// Compiles the code like [`BytecodeCompiler::compile`], except that the script returns the
+    /// value of its last expression instead of nil, unless that is a statement like a definition.
+    pub fn compile_returning_last(mut self) -> Result<GcRef<Function>, CupidError> {
+        let mut exprs = std::mem::take(&mut self.expr);
+        let last = exprs.pop();
+        for expr in exprs {
+            expr.compile(&mut self);
+        }
+        match last {
+            Some(last) if self.leaves_value(&last) => {
+                last.compile(&mut self);
+                self.write(Instruction::Return);
+            }
+            Some(last) => {
+                last.compile(&mut self);
+                self.write_return();
+            }
+            None => {
+                self.write_return();
+            }
+        }
+        self.finish()
+    }
+
+    fn finish(self) -> Result<GcRef<Function>, CupidError> {
+        match self.errors.compile {
+            Some(error) => Err(error),
+            None => Ok(self.gc.alloc(self.compiler.function)),
+        }
+    }
+
+    /// Records an error at the expression being compiled. Compiling carries on so that nothing
+    /// has to unwind, but only the first error is kept and no function is returned.
+    fn error(&mut self, kind: Kind, code: Code, msg: &str) {
+        if self.errors.compile.is_some() {
+            return;
+        }
+        let mut error = CupidError::compile_error(kind, code, msg);
+        if let Some(source) = self.source {
+            error = error.at_source(source).locate(&self.arena);
+        }
+        self.errors.compile = Some(error);
+    }
+
+    /// Records the errors found while resolving a name.
+    fn resolver_errors(&mut self) {
+        while let Some((code, msg)) = self.errors.resolver.pop() {
+            let kind = match code {
+                Code::LocalInOwnInitializer => Kind::Name,
+                _ => Kind::Syntax,
+            };
+            self.error(kind, code, msg);
+        }
+    }
+
+    /// Whether a top level expression leaves its value on the stack, which definitions, control
+    /// flow and `log` don't.
+    fn leaves_value(&self, expr: &Expr<'src>) -> bool {
+        match expr {
+            Expr::Array(_)
+            | Expr::BinOp(_)
+            | Expr::Constant(_)
+            | Expr::Get(_)
+            | Expr::GetProperty(_)
+            | Expr::Invoke(_)
+            | Expr::Set(_)
+            | Expr::SetProperty(_)
+            | Expr::UnOp(_) => true,
+            Expr::Call(call) => {
+                !matches!(self.arena.expect(call.callee), Expr::Get(get) if get.name == "log")
+            }
+            Expr::Fun(fun) => fun.name.is_none(),
+            _ => false,
+        }
     }
 
     fn write(&mut self, instruction: Instruction) -> usize {
-        let approx_line = self.compiler.function.chunk.code.len(); // TODO replace with actual line
-        self.compiler.function.chunk.write(instruction, approx_line)
+        self.compiler.function.chunk.write(instruction, self.line)
     }
 
     // fn write_line(&mut self, instruction: Instruction, line: usize) -> usize {
@@ -93,7 +183,10 @@ impl<'src> BytecodeCompiler<'src> {
         let index = self.compiler.function.chunk.add_constant(value);
         match u8::try_from(index) {
             Ok(index) => index,
-            Err(_) => panic!("Too many constants in one chunk."),
+            Err(_) => {
+                self.error(Kind::Syntax, Code::LimitExceeded, "Too many constants in one chunk.");
+                0
+            }
         }
     }
 
@@ -108,7 +201,8 @@ impl<'src> BytecodeCompiler<'src> {
             return;
         }
         if self.compiler.is_local_declared(name) {
-            panic!("Already variable with self name in self scope.")
+            let msg = "Already variable with self name in self scope.";
+            self.error(Kind::Name, Code::DuplicateLocal, msg);
         }
         self.add_local(name)
     }
@@ -139,7 +233,9 @@ impl<'src> BytecodeCompiler<'src> {
 
     fn add_local(&mut self, name: &'src str) {
         if self.compiler.locals.len() == Compiler::LOCAL_COUNT {
-            panic!("Too many local variables in function.")
+            let msg = "Too many local variables in function.";
+            self.error(Kind::Syntax, Code::LimitExceeded, msg);
+            return;
         }
         let local = Local::new(name, -1);
         self.compiler.locals.push(local);
@@ -149,11 +245,16 @@ impl<'src> BytecodeCompiler<'src> {
         let offset = self.compiler.function.chunk.code.len() - 1 - pos;
         let offset = match u16::try_from(offset) {
             Ok(offset) => offset,
-            Err(_) => panic!("Too much code to jump over."),
+            Err(_) => {
+                self.error(Kind::Syntax, Code::LimitExceeded, "Too much code to jump over.");
+                0
+            }
         };
         match self.compiler.function.chunk.code[pos] {
             Instruction::JumpIfFalse(ref mut o) => *o = offset,
             Instruction::Jump(ref mut o) => *o = offset,
+            Instruction::PushHandler(ref mut o, _) => *o = offset,
+            Instruction::Propagate(ref mut o) => *o = offset,
             _ => panic!("Instruction at position is not jump"),
         }
     }
@@ -163,6 +264,72 @@ impl<'src> BytecodeCompiler<'src> {
             self.patch_jump(jump);
         }
         self.loop_jumps.pop();
+        self.loop_handlers.pop();
+    }
+
+    /// Starts a try body whose errors are caught with the stack cut back to `locals` locals, the
+    /// catch body being patched in later like a jump.
+    fn write_handler(&mut self, locals: usize) -> usize {
+        let locals = match u8::try_from(locals) {
+            Ok(locals) => locals,
+            Err(_) => {
+                let msg = "Too many local variables in function.";
+                self.error(Kind::Syntax, Code::LimitExceeded, msg);
+                0
+            }
+        };
+        self.write(Instruction::PushHandler(0xffff, locals))
+    }
+
+    /// Leaves the try and catch bodies opened after the first `depth`, innermost first, popping
+    /// their handlers and running their finally bodies.
+    fn write_exits(&mut self, depth: usize) {
+        let mut left = vec![];
+        while self.compiler.handlers.len() > depth {
+            // A `return` or `break` in a finally body only leaves the bodies around it
+            let body = self.compiler.handlers.pop().unwrap();
+            self.write(Instruction::PopHandler);
+            if let Some(finally_body) = body.finally_body {
+                // The locals declared since the try are still on the stack, but out of sight
+                let locals = &mut self.compiler.locals[body.locals..];
+                let names: Vec<_> =
+                    locals.iter_mut().map(|local| std::mem::take(&mut local.name)).collect();
+                finally_body.compile(self);
+                for (local, name) in self.compiler.locals[body.locals..].iter_mut().zip(names) {
+                    local.name = name;
+                }
+            }
+            left.push(body);
+        }
+        self.compiler.handlers.extend(left.into_iter().rev());
+    }
+
+    /// Returns the value on top of the stack, keeping it in a local while the finally bodies of
+    /// any try and catch bodies being left run.
+    fn write_value_return(&mut self) {
+        if self.compiler.handlers.is_empty() {
+            self.write(Instruction::Return);
+            return;
+        }
+        // Values left by the statements before it can sit below the value, so it is copied down.
+        // A `?` in a local's initializer returns from the slot that local's value would go in.
+        let locals = &self.compiler.locals;
+        let slot = locals.iter().rposition(|local| local.depth != -1).map_or(0, |i| i + 1);
+        self.write(Instruction::SetLocal(slot as u8));
+        self.begin_scope();
+        if slot == self.compiler.locals.len() {
+            self.add_initialized_local("");
+        }
+        self.write_exits(0);
+        self.write(Instruction::GetLocal(slot as u8));
+        self.write(Instruction::Return);
+        self.end_scope();
+    }
+
+    /// Declares a local for a value the vm has already put on the stack.
+    fn add_initialized_local(&mut self, name: &'src str) {
+        self.add_local(name);
+        self.mark_initialized();
     }
 
     fn start_loop(&self) -> usize {
@@ -173,7 +340,10 @@ impl<'src> BytecodeCompiler<'src> {
         let offset = self.compiler.function.chunk.code.len() - start_pos;
         let offset = match u16::try_from(offset) {
             Ok(o) => o,
-            Err(_) => panic!("Loop body too large."),
+            Err(_) => {
+                self.error(Kind::Syntax, Code::LimitExceeded, "Loop body too large.");
+                0
+            }
         };
         self.write(Instruction::Loop(offset));
     }
@@ -222,17 +392,13 @@ impl<'src> BytecodeCompiler<'src> {
 
     fn resolve_local(&mut self, name: &str) -> Option<u8> {
         let result = self.compiler.resolve_local(name, &mut self.errors.resolver);
-        while let Some(e) = self.errors.resolver.pop() {
-            panic!("{}", e);
-        }
+        self.resolver_errors();
         result
     }
 
     fn resolve_upvalue(&mut self, name: &'src str) -> Option<u8> {
         let result = self.compiler.resolve_upvalue(name, &mut self.errors.resolver);
-        while let Some(e) = self.errors.resolver.pop() {
-            panic!("{}", e);
-        }
+        self.resolver_errors();
         result
     }
 
@@ -248,14 +414,17 @@ impl<'src> BytecodeCompiler<'src> {
         self.write(Instruction::Return)
     }
 
-    fn expect_class_compiler(&mut self) {
-        match self.class_compiler.as_ref() {
+    /// Whether `super` can be used here, recording an error if not.
+    fn expect_class_compiler(&mut self) -> bool {
+        let msg = match self.class_compiler.as_ref() {
             Some(current_class) if !current_class.has_superclass => {
-                panic!("Can't use 'super' in a class with no superclass.");
+                "Can't use 'super' in a class with no superclass."
             }
-            None => panic!("Can't use 'super' outside of a class."),
-            _ => (),
-        }
+            None => "Can't use 'super' outside of a class.",
+            _ => return true,
+        };
+        self.error(Kind::Syntax, Code::MisplacedKeyword, msg);
+        false
     }
 }
 
@@ -281,6 +450,11 @@ impl<'src> ToBytecode<'src> for EntryId {
 
 impl<'src> ToBytecode<'src> for Expr<'src> {
     fn compile(&self, compiler: &mut BytecodeCompiler<'src>) {
+        let line = compiler.line;
+        let source = compiler.source.replace(self.header().source);
+        if let Some(span) = compiler.arena.expect_source(self.header().source).span() {
+            compiler.line = span.start.line;
+        }
         match self {
             Self::Array(array) => array.compile(compiler),
             Self::BinOp(binop) => binop.compile(compiler),
@@ -298,11 +472,16 @@ impl<'src> ToBytecode<'src> for Expr<'src> {
             Self::Invoke(invoke) => invoke.compile(compiler),
             Self::InvokeSuper(invoke) => invoke.compile(compiler),
             Self::Loop(value) => value.compile(compiler),
+            Self::Propagate(propagate) => propagate.compile(compiler),
             Self::Return(stmt) => stmt.compile(compiler),
             Self::Set(set) => set.compile(compiler),
             Self::SetProperty(set) => set.compile(compiler),
+            Self::Throw(throw) => throw.compile(compiler),
+            Self::Try(stmt) => stmt.compile(compiler),
             Self::UnOp(unop) => unop.compile(compiler),
         }
+        compiler.line = line;
+        compiler.source = source;
     }
 }
 
@@ -327,8 +506,9 @@ impl<'src> ToBytecode<'src> for BinOp<'src> {
             TokenType::EqualEqual => compiler.write(Instruction::Equal),
             TokenType::BangEqual => compiler.write(Instruction::Not),
             kind => {
-                println!("Unimplemented binary operation: {kind:#?}");
-                todo!()
+                let msg = format!("Unsupported binary operator: {kind:?}.");
+                compiler.error(Kind::Syntax, Code::UnsupportedOperator, &msg);
+                0
             }
         };
     }
@@ -347,12 +527,18 @@ impl<'src> ToBytecode<'src> for Break<'src> {
         if let Some(value) = &self.value {
             value.compile(compiler);
         }
-        let break_id = compiler.write(Instruction::Jump(0xffff));
-        let loop_jump = match compiler.loop_jumps.last_mut() {
-            Some(loop_jump) => loop_jump,
-            None => panic!("Can't break outside of a loop."),
+        let loop_handlers = match compiler.loop_handlers.last() {
+            Some(loop_handlers) => *loop_handlers,
+            None => {
+                let msg = "Can't break outside of a loop.";
+                return compiler.error(Kind::Syntax, Code::MisplacedKeyword, msg);
+            }
         };
-        loop_jump.push(break_id);
+        compiler.write_exits(loop_handlers);
+        let break_id = compiler.write(Instruction::Jump(0xffff));
+        if let Some(loop_jump) = compiler.loop_jumps.last_mut() {
+            loop_jump.push(break_id);
+        }
     }
 }
 
@@ -395,6 +581,10 @@ impl<'src> ToBytecode<'src> for Class<'src> {
         }
 
         compiler.get_name(self.name);
+        for field in &self.fields {
+            let name = compiler.ident_constant(field.name);
+            compiler.write(Instruction::Field(name));
+        }
         self.methods.compile(compiler);
         compiler.write_pop();
 
@@ -439,7 +629,8 @@ impl<'src> ToBytecode<'src> for Fun<'src> {
         for param in &self.params {
             compiler.compiler.function.arity += 1;
             if compiler.compiler.function.arity > 255 {
-                panic!("Can't have more than 255 parameters.");
+                let msg = "Can't have more than 255 parameters.";
+                compiler.error(Kind::Syntax, Code::LimitExceeded, msg);
             }
             let param = compiler.declare_constant(param.name);
             compiler.define(param);
@@ -474,7 +665,9 @@ impl<'src> ToBytecode<'src> for GetProperty<'src> {
 
 impl<'src> ToBytecode<'src> for GetSuper<'src> {
     fn compile(&self, compiler: &mut BytecodeCompiler<'src>) {
-        compiler.expect_class_compiler();
+        if !compiler.expect_class_compiler() {
+            return;
+        }
         let name = compiler.ident_constant(self.name);
         compiler.get_name("self");
         compiler.get_name("super");
@@ -511,7 +704,9 @@ impl<'src> ToBytecode<'src> for Invoke<'src> {
 
 impl<'src> ToBytecode<'src> for InvokeSuper<'src> {
     fn compile(&self, compiler: &mut BytecodeCompiler<'src>) {
-        compiler.expect_class_compiler();
+        if !compiler.expect_class_compiler() {
+            return;
+        }
         let name = compiler.ident_constant(self.name);
         compiler.get_name("self");
         self.args.compile(compiler);
@@ -524,6 +719,7 @@ impl<'src> ToBytecode<'src> for Loop<'src> {
     fn compile(&self, compiler: &mut BytecodeCompiler<'src>) {
         let loop_start = compiler.start_loop();
         compiler.loop_jumps.push(vec![]);
+        compiler.loop_handlers.push(compiler.compiler.handlers.len());
         self.body.compile(compiler);
         compiler.write_loop(loop_start);
 
@@ -541,21 +737,34 @@ impl<'src> ToBytecode<'src> for Method<'src> {
     }
 }
 
+impl<'src> ToBytecode<'src> for Propagate<'src> {
+    fn compile(&self, compiler: &mut BytecodeCompiler<'src>) {
+        self.value.compile(compiler);
+        let unwrap_jump = compiler.write(Instruction::Propagate(0xffff));
+        compiler.write_value_return();
+        compiler.patch_jump(unwrap_jump);
+    }
+}
+
 impl<'src> ToBytecode<'src> for Return<'src> {
     fn compile(&self, compiler: &mut BytecodeCompiler<'src>) {
-        match compiler.compiler.function_type {
-            FunctionType::Script => panic!("Can't return from top-level code."),
-            FunctionType::Initializer => panic!("Can't return a value from an initializer."),
+        let msg = match compiler.compiler.function_type {
+            FunctionType::Script => "Can't return from top-level code.",
+            FunctionType::Initializer => "Can't return a value from an initializer.",
             _ => match &self.value {
                 Some(value) => {
                     value.compile(compiler);
-                    compiler.write(Instruction::Return);
+                    compiler.write_value_return();
+                    return;
                 }
                 None => {
+                    compiler.write_exits(0);
                     compiler.write_return();
+                    return;
                 }
             },
-        }
+        };
+        compiler.error(Kind::Syntax, Code::MisplacedKeyword, msg);
     }
 }
 
@@ -575,6 +784,68 @@ impl<'src> ToBytecode<'src> for SetProperty<'src> {
     }
 }
 
+impl<'src> ToBytecode<'src> for Throw<'src> {
+    fn compile(&self, compiler: &mut BytecodeCompiler<'src>) {
+        self.value.compile(compiler);
+        compiler.write(Instruction::Throw);
+    }
+}
+
+impl<'src> ToBytecode<'src> for Try<'src> {
+    fn compile(&self, compiler: &mut BytecodeCompiler<'src>) {
+        let locals = compiler.compiler.locals.len();
+        let catch_jump = compiler.write_handler(locals);
+        compiler.compiler.handlers.push(TryBody {
+            finally_body: self.finally_body,
+            locals,
+        });
+        self.body.compile(compiler);
+        compiler.compiler.handlers.pop();
+        compiler.write(Instruction::PopHandler);
+        let end_jump = compiler.write(Instruction::Jump(0xffff));
+
+        // The vm puts the error right after the locals, where the catch body's own local goes
+        compiler.patch_jump(catch_jump);
+        compiler.begin_scope();
+        compiler.add_initialized_local(self.catch_param.name);
+        let finally_body = match &self.finally_body {
+            Some(finally_body) => finally_body,
+            None => {
+                self.catch_body.compile(compiler);
+                compiler.end_scope();
+                compiler.patch_jump(end_jump);
+                return;
+            }
+        };
+
+        // An error thrown by the catch body runs the finally body before it is thrown on
+        let rethrow_jump = compiler.write_handler(locals + 1);
+        compiler.compiler.handlers.push(TryBody {
+            finally_body: self.finally_body,
+            locals,
+        });
+        self.catch_body.compile(compiler);
+        compiler.compiler.handlers.pop();
+        compiler.write(Instruction::PopHandler);
+        compiler.end_scope();
+        let finally_jump = compiler.write(Instruction::Jump(0xffff));
+
+        compiler.patch_jump(rethrow_jump);
+        compiler.begin_scope();
+        // The caught error, then the one thrown by the catch body, neither of them named
+        compiler.add_initialized_local("");
+        compiler.add_initialized_local("");
+        finally_body.compile(compiler);
+        compiler.write(Instruction::GetLocal((locals + 1) as u8));
+        compiler.write(Instruction::Throw);
+        compiler.end_scope();
+
+        compiler.patch_jump(end_jump);
+        compiler.patch_jump(finally_jump);
+        finally_body.compile(compiler);
+    }
+}
+
 impl<'src> ToBytecode<'src> for UnOp<'src> {
     fn compile(&self, compiler: &mut BytecodeCompiler<'src>) {
         self.expr.compile(compiler);
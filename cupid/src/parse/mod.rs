@@ -13,12 +13,15 @@ use crate::{
         fun::FunSource,
         get::GetSource,
         get_super::GetSuperSource,
+        group::GroupSource,
         invoke_super::InvokeSuperSource,
         r#break::BreakSource,
         r#if::IfSource,
-        r#loop::LoopSource,
+        r#loop::{ForLoopSource, PlainLoopSource, WhileLoopSource},
         r#return::ReturnSource,
+        r#try::TrySource,
         set::SetSource,
+        throw::ThrowSource,
         unop::UnOpSource,
         SourceId,
     },
@@ -61,7 +64,9 @@ where
 }
 
 fn terminate<'src>(parser: &mut Parser<'src>) {
-    parser.matches_any(&[TokenType::Semicolon, TokenType::NewLine, TokenType::Error]);
+    if let Some(token) = parser.matches_any(&[TokenType::Semicolon, TokenType::NewLine]) {
+        parser.discard(token);
+    }
 }
 
 macro_rules! try_parse {
@@ -92,6 +97,8 @@ fn parse_expr<'src>(
         Loop::parse_expr(parser, gc)?,
         Break::parse_expr(parser, gc)?,
         Return::parse_expr(parser, gc)?,
+        Throw::parse_expr(parser, gc)?,
+        Try::parse_expr(parser, gc)?,
         Define::parse_expr(parser, gc)?,
         BinOp::parse_expr(parser, gc)?
     };
@@ -172,16 +179,20 @@ impl<'src> ParseExpr<'src> for Break<'src> {
         };
         let value: Option<Expr<'src>> =
             match parser.matches_any(&[TokenType::Semicolon, TokenType::NewLine, TokenType::Eof]) {
-                Some(_) => None,
+                Some(token) => {
+                    parser.discard(token);
+                    None
+                }
                 None => parse_expr(parser, gc)?,
             };
+        let value_src = value.as_ref().map(|value| value.header().source);
         let value: Option<EntryId> = match value {
             Some(value) => Some(parser.arena.insert(value)),
             None => None,
         };
         let break_source_id = parser.insert_source(BreakSource {
             break_kw,
-            value_src: None,
+            value_src,
         });
         Ok(Some(
             Break {
@@ -230,10 +241,12 @@ impl<'src> ParseExpr<'src> for Class<'src> {
             Class {
                 header: parser.header(source_id),
                 name: name.lexeme,
-                super_class: super_class.map(|s| s.lexeme),
+                doc: DocComment::from_trivia(class_kw.leading),
+                super_class: super_class_name.map(|s| s.lexeme),
                 fields,
                 methods,
                 class_scope,
+                symbol: None,
             }
             .into(),
         ))
@@ -281,7 +294,9 @@ fn parse_fun<'src>(
         params.push(Define {
             header: parser.header(define_source),
             name: name.lexeme,
+            doc: None,
             value: None,
+            symbol: None,
         });
         match parser.matches(TokenType::Comma) {
             Some(token) => commas.push(token),
@@ -303,12 +318,19 @@ fn parse_fun<'src>(
         close_paren,
         commas,
     });
+    // Methods have no `fun` keyword, and start with their name.
+    let first = match kw.span.is_synthetic() {
+        true => name.unwrap_or(open_paren),
+        false => kw,
+    };
     Ok(Fun {
         header: parser.header(source_id),
         kind: function_type,
         name: name.map(|n| n.lexeme),
+        doc: DocComment::from_trivia(first.leading),
         params,
         body,
+        symbol: None,
     })
 }
 
@@ -397,14 +419,15 @@ impl<'src> ParseExpr<'src> for GetSuper<'src> {
             Some(name) => name,
             None => return Ok(None),
         };
+        let open_paren = parser.curr;
         match parse_args(parser, gc) {
-            Ok(Some(args)) => {
+            Ok(Some((args, commas))) => {
                 let source_id = parser.insert_source(InvokeSuperSource {
                     name,
-                    args: vec![],
-                    open_paren: Token::synthetic("("),
-                    close_paren: Token::synthetic(")"),
-                    commas: vec![],
+                    args: args.iter().map(|arg| arg.source_id(&parser.arena)).collect(),
+                    open_paren,
+                    close_paren: parser.prev,
+                    commas,
                 });
                 Ok(Some(
                     InvokeSuper {
@@ -488,7 +511,7 @@ impl<'src> ParseExpr<'src> for Loop<'src> {
         let body_src = body.header.source;
         let body = parser.arena.insert(Expr::from(body));
         parser.end_scope();
-        let source_id = parser.insert_source(LoopSource { loop_kw, body_src });
+        let source_id = parser.insert_source(PlainLoopSource { loop_kw, body_src });
         Ok(Some(
             Loop {
                 header: parser.header(source_id),
@@ -541,6 +564,7 @@ impl<'src> Parse<'src> for Method<'src> {
             header: parser.header(source_id),
             name: name.lexeme,
             fun: fun.into(),
+            symbol: None,
         })
     }
 }
@@ -555,7 +579,10 @@ impl<'src> ParseExpr<'src> for Return<'src> {
             None => return Ok(None),
         };
         let value = match parser.matches_any(&[TokenType::NewLine, TokenType::Semicolon]) {
-            Some(_) => None,
+            Some(token) => {
+                parser.discard(token);
+                None
+            }
             None => {
                 let expr = parse_expr(parser, gc)?;
                 expr.map(|expr| parser.arena.insert(expr))
@@ -576,6 +603,101 @@ impl<'src> ParseExpr<'src> for Return<'src> {
     }
 }
 
+impl<'src> ParseExpr<'src> for Throw<'src> {
+    fn parse_expr(
+        parser: &mut Parser<'src>,
+        gc: &mut Gc,
+    ) -> Result<Option<Expr<'src>>, CupidError> {
+        let throw_kw = match parser.matches(TokenType::Throw) {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+        let value = parse_expect_expr(parser, gc, "Expect a value to throw.")?;
+        let value_src = value.header().source;
+        let value = parser.arena.insert(value);
+        let source_id = parser.insert_source(ThrowSource {
+            throw_kw,
+            value_src,
+        });
+        Ok(Some(
+            Throw {
+                header: parser.header(source_id),
+                value,
+            }
+            .into(),
+        ))
+    }
+}
+
+impl<'src> ParseExpr<'src> for Try<'src> {
+    fn parse_expr(
+        parser: &mut Parser<'src>,
+        gc: &mut Gc,
+    ) -> Result<Option<Expr<'src>>, CupidError> {
+        let try_kw = match parser.matches(TokenType::Try) {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+        let body = Block::parse(parser, gc)?;
+        let body_src = body.header.source;
+        let body = parser.arena.insert(Expr::from(body));
+        let catch_kw = parser.expect(TokenType::Catch, "Expect 'catch' after try body.")?;
+        let open_paren = parser.expect(TokenType::LeftParen, "Expect '(' after 'catch'.")?;
+        // The error is only in scope in the catch body
+        parser.begin_scope(ScopeContext::Block);
+        let name = parser.expect(TokenType::Identifier, "Expect error name.")?;
+        let catch_param_src = parser.insert_source(DefineSource {
+            name,
+            let_kw: None,
+            equal: None,
+            value_src: None,
+        });
+        let catch_param = Define {
+            header: parser.header(catch_param_src),
+            name: name.lexeme,
+            doc: None,
+            value: None,
+            symbol: None,
+        };
+        let close_paren = parser.expect(TokenType::RightParen, "Expect ')' after error name.")?;
+        let catch_body = Block::parse(parser, gc)?;
+        let catch_body_src = catch_body.header.source;
+        let catch_body = parser.arena.insert(Expr::from(catch_body));
+        parser.end_scope();
+        let (finally_kw, finally_body, finally_body_src) = match parser.matches(TokenType::Finally)
+        {
+            Some(token) => {
+                let finally_body = Block::parse(parser, gc)?;
+                let finally_body_src = finally_body.header.source;
+                let finally_body = parser.arena.insert(Expr::from(finally_body));
+                (Some(token), Some(finally_body), Some(finally_body_src))
+            }
+            None => (None, None, None),
+        };
+        let source_id = parser.insert_source(TrySource {
+            try_kw,
+            body_src,
+            catch_kw,
+            open_paren,
+            catch_param_src,
+            close_paren,
+            catch_body_src,
+            finally_kw,
+            finally_body_src,
+        });
+        Ok(Some(
+            Try {
+                header: parser.header(source_id),
+                body,
+                catch_param,
+                catch_body,
+                finally_body,
+            }
+            .into(),
+        ))
+    }
+}
+
 impl<'src> ParseExpr<'src> for Set<'src> {
     fn parse_expr(
         parser: &mut Parser<'src>,
@@ -728,7 +850,9 @@ impl<'src> ParseExpr<'src> for Define<'src> {
                     Define {
                         header: parser.header(source_id),
                         name: name.lexeme,
+                        doc: DocComment::from_trivia(let_kw.leading),
                         value: Some(value),
+                        symbol: None,
                     }
                     .into(),
                 ))
@@ -744,7 +868,9 @@ impl<'src> ParseExpr<'src> for Define<'src> {
                     Define {
                         header: parser.header(source_id),
                         name: name.lexeme,
+                        doc: DocComment::from_trivia(let_kw.leading),
                         value: None,
+                        symbol: None,
                     }
                     .into(),
                 ))
@@ -757,25 +883,26 @@ fn parse_for_loop<'src>(
     parser: &mut Parser<'src>,
     gc: &mut Gc,
 ) -> Result<Option<Expr<'src>>, CupidError> {
-    let kw = match parser.matches(TokenType::For) {
+    let for_kw = match parser.matches(TokenType::For) {
         Some(token) => token,
         None => return Ok(None),
     };
     parser.begin_scope(ScopeContext::Loop);
 
-    parser.expect(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+    let open_paren = parser.expect(TokenType::LeftParen, "Expect '(' after 'for'.")?;
 
     // Variable declaration
     let def = Define::parse_expect_expr(parser, gc, "Expect for loop definition.")?;
+    let init_src = def.header().source;
     let def = parser.arena.insert(def);
 
     // Break condition
     let cond = BinOp::parse_expect_expr(parser, gc, "Expect for loop condition.")?;
-    let cond_src = cond.header().source;
+    let condition_src = cond.header().source;
     let cond = parser.arena.insert(cond);
     let cond_source_id = parser.insert_source(UnOpSource {
-        expr_src: cond_src,
-        op: Token::synthetic("todo"),
+        expr_src: condition_src,
+        op: Token::synthetic("!"),
     });
     let cond = UnOp {
         header: parser.header(cond_source_id),
@@ -783,22 +910,33 @@ fn parse_for_loop<'src>(
         op: TokenType::Bang,
     }
     .into();
-    parser.expect(TokenType::Semicolon, "Expect ';'.")?;
+    let condition_semicolon = parser.expect(TokenType::Semicolon, "Expect ';'.")?;
 
     // Increment
     let increment = BinOp::parse_expect_expr(parser, gc, "Expect increment.")?;
+    let increment_src = increment.header().source;
     let increment = parser.arena.insert(increment);
 
-    parser.expect(TokenType::RightParen, "Expect ')' after 'for'.")?;
+    let close_paren = parser.expect(TokenType::RightParen, "Expect ')' after 'for'.")?;
     let mut body = Block::parse(parser, gc)?;
-    let body_source = body.header.source;
+    let body_src = body.header.source;
     body.body.push(increment);
 
-    let loop_expr: Expr<'src> = make_condition_loop(kw, parser, cond, body).into();
+    let source_id = parser.insert_source(ForLoopSource {
+        for_kw,
+        open_paren,
+        init_src,
+        condition_src,
+        condition_semicolon,
+        increment_src,
+        close_paren,
+        body_src,
+    });
+    let loop_expr: Expr<'src> = make_condition_loop(parser, cond, body, source_id).into();
     let loop_expr: EntryId = parser.arena.insert(loop_expr);
 
     let block = Block {
-        header: parser.header(body_source),
+        header: parser.header(source_id),
         body: vec![def, loop_expr],
     };
 
@@ -810,17 +948,17 @@ fn parse_while_loop<'src>(
     parser: &mut Parser<'src>,
     gc: &mut Gc,
 ) -> Result<Option<Expr<'src>>, CupidError> {
-    let kw = match parser.matches(TokenType::While) {
+    let while_kw = match parser.matches(TokenType::While) {
         Some(token) => token,
         None => return Ok(None),
     };
     parser.begin_scope(ScopeContext::Loop);
     let cond = BinOp::parse_expect_expr(parser, gc, "Expected condition after 'while'.")?;
-    let cond_src = cond.header().source;
+    let condition_src = cond.header().source;
     let cond = parser.arena.insert(cond);
     let cond_source_id = parser.insert_source(UnOpSource {
-        expr_src: cond_src,
-        op: Token::synthetic("todo"),
+        expr_src: condition_src,
+        op: Token::synthetic("!"),
     });
     let cond = UnOp {
         header: parser.header(cond_source_id),
@@ -829,14 +967,20 @@ fn parse_while_loop<'src>(
     };
     let body = Block::parse(parser, gc)?;
     parser.end_scope();
-    Ok(Some(make_condition_loop(kw, parser, cond.into(), body).into()))
+    let source_id = parser.insert_source(WhileLoopSource {
+        while_kw,
+        condition_src,
+        body_src: body.header.source,
+    });
+    Ok(Some(make_condition_loop(parser, cond.into(), body, source_id).into()))
 }
 
+/// Makes a loop that starts by breaking out when `cond` holds, and then runs `loop_body`.
 fn make_condition_loop<'src>(
-    kw: Token<'src>,
     parser: &mut Parser<'src>,
     cond: Expr<'src>,
     mut loop_body: Block<'src>,
+    source_id: SourceId,
 ) -> Loop<'src> {
     let cond_src = cond.header().source;
     let cond = parser.arena.insert(cond);
@@ -866,48 +1010,41 @@ fn make_condition_loop<'src>(
         else_body: None,
     };
     let if_stmt = parser.arena.insert(Expr::from(if_stmt));
-    let mut loop_body_src: Vec<SourceId> =
-        loop_body.body.iter().map(|b| b.source_id(&parser.arena)).collect();
-    let mut block_source = BraceBlockSource {
-        open_brace: Token::synthetic("{"),
-        close_brace: Token::synthetic("}"),
-        body_src: vec![if_stmt_source_id.into()],
-    };
-    block_source.body_src.append(&mut loop_body_src);
-    let block_source_id = parser.insert_source(block_source);
     let mut block = Block {
-        header: parser.header(if_stmt_source_id),
+        header: parser.header(loop_body.header.source),
         body: vec![if_stmt],
     };
     block.body.append(&mut loop_body.body);
     let body = parser.arena.insert(Expr::from(block));
-    let loop_source_id = parser.insert_source(LoopSource {
-        loop_kw: kw,
-        body_src: block_source_id.into(),
-    });
     Loop {
-        header: parser.header(loop_source_id),
+        header: parser.header(source_id),
         body,
     }
 }
 
+/// The arguments of a call, and the commas between them.
+type Args<'src> = (Vec<EntryId>, Vec<Token<'src>>);
+
+/// Parses the arguments of a call, along with the commas between them.
 fn parse_args<'src>(
     parser: &mut Parser<'src>,
     gc: &mut Gc,
-) -> Result<Option<Vec<EntryId>>, CupidError> {
+) -> Result<Option<Args<'src>>, CupidError> {
     if parser.matches(TokenType::LeftParen).is_some() {
         let mut args = vec![];
+        let mut commas = vec![];
         while !parser.check(TokenType::RightParen) {
             match parse_expr(parser, gc)? {
                 Some(arg) => args.push(parser.arena.insert(Expr::from(arg))),
                 None => break,
             };
-            if parser.matches(TokenType::Comma).is_none() {
-                break;
+            match parser.matches(TokenType::Comma) {
+                Some(comma) => commas.push(comma),
+                None => break,
             }
         }
         parser.expect(TokenType::RightParen, "Expect ')' after arguments.")?;
-        return Ok(Some(args));
+        return Ok(Some((args, commas)));
     }
     Ok(None)
 }
@@ -916,11 +1053,21 @@ fn parse_group<'src>(
     parser: &mut Parser<'src>,
     gc: &mut Gc,
 ) -> Result<Option<Expr<'src>>, CupidError> {
-    if let None = parser.matches(TokenType::LeftParen) {
-        return Ok(None);
-    }
+    let open_paren = match parser.matches(TokenType::LeftParen) {
+        Some(token) => token,
+        None => return Ok(None),
+    };
     let inner = parse_expr(parser, gc)?;
-    parser.expect(TokenType::RightParen, "Expect ')' after group.")?;
+    let close_paren = parser.expect(TokenType::RightParen, "Expect ')' after group.")?;
+    if let Some(inner) = &inner {
+        parser.arena.source.group(
+            inner.header().source,
+            GroupSource {
+                open_paren,
+                close_paren,
+            },
+        );
+    }
     Ok(inner)
 }
 
@@ -1,6 +1,6 @@
 use crate::{
     arena::{ExprArena, UseArena},
-    cst::{expr::ExprSource, SourceId},
+    cst::{error::ErrorSource, expr::ExprSource, SourceFile, SourceId},
     error::CupidError,
     gc::Gc,
     pointer::Pointer,
@@ -10,10 +10,15 @@ use crate::{
     ty::Type,
 };
 
-use super::{iter::Iter, parse_expr, recompose::Recompose, Expr, ExprHeader};
+use super::{iter::Iter, parse_expr, recompose::Recompose, Expr, ExprHeader, Header};
 
 pub struct Parser<'src> {
+    code: &'src str,
     scanner: Scanner<'src>,
+    /// Where the trivia leading the next token starts.
+    trivia_start: usize,
+    /// The error of the last token the scanner couldn't make sense of.
+    scan_error: Option<CupidError>,
     pub arena: ExprArena<'src>,
     pub curr: Token<'src>,
     pub prev: Token<'src>,
@@ -26,8 +31,12 @@ impl<'src> Parser<'src> {
         let scope = Pointer::<Scope>::global();
         let mut arena = ExprArena::default();
         scope.borrow_mut().initialize(&mut arena);
+        let code = code.into();
         Self {
-            scanner: Scanner::new(code.into()),
+            code,
+            scanner: Scanner::new(code),
+            trivia_start: 0,
+            scan_error: None,
             curr: Token::synthetic(""),
             prev: Token::synthetic(""),
             depth: 0,
@@ -37,15 +46,95 @@ impl<'src> Parser<'src> {
     }
 
     pub fn parse(&mut self, gc: &mut Gc) -> Result<Vec<Expr<'src>>, CupidError> {
+        match self.parse_exprs(gc) {
+            (exprs, None) => exprs.recompose(&mut self.arena),
+            (_, Some((_, error))) => Err(error),
+        }
+    }
+
+    /// Parses the code without recomposing it, so that the sources of the top level expressions
+    /// are the ones written in the code: assignments and property accesses are still binary
+    /// operations, for example.
+    pub fn parse_source(&mut self, gc: &mut Gc) -> Result<SourceFile<'src>, CupidError> {
+        match self.parse_lossless(gc) {
+            (file, None) => Ok(file),
+            (_, Some(error)) => Err(error),
+        }
+    }
+
+    /// Parses the code like [`Parser::parse_source`], except that the code from the first top
+    /// level expression that doesn't parse on is kept in the file as an [`ErrorSource`], so that
+    /// the file always prints back exactly. The error that stopped the parse comes along with it.
+    pub fn parse_lossless(&mut self, gc: &mut Gc) -> (SourceFile<'src>, Option<CupidError>) {
+        let (exprs, error) = self.parse_exprs(gc);
+        let exprs = exprs.iter().map(|expr| expr.header().source).collect();
+        match error {
+            None => (
+                SourceFile {
+                    exprs,
+                    error: None,
+                    eof: self.prev,
+                },
+                None,
+            ),
+            Some((start, error)) => {
+                let unparsed = self.rescan(start);
+                let file = SourceFile {
+                    exprs,
+                    error: Some(unparsed),
+                    eof: self.curr,
+                };
+                (file, Some(error))
+            }
+        }
+    }
+
+    /// Parses the top level expressions, up to the first one that doesn't parse. That one's first
+    /// token is returned with the error.
+    fn parse_exprs(&mut self, gc: &mut Gc) -> (Vec<Expr<'src>>, Option<(Token<'src>, CupidError)>) {
         let mut exprs = vec![];
+        // Read the first token.
+        self.advance();
         while self.matches(TokenType::Eof).is_none() {
-            match parse_expr(self, gc) {
-                Ok(Some(expr)) => exprs.push(expr),
-                Ok(None) => (),
-                Err(err) => return Err(err),
-            }
+            let start = self.curr;
+            let error = match parse_expr(self, gc) {
+                Ok(Some(expr)) => {
+                    exprs.push(expr);
+                    continue;
+                }
+                Ok(None) => self.err("Expect expression."),
+                Err(err) => err,
+            };
+            // The parser stops at code the scanner couldn't read, which is the actual problem
+            let error = match (self.curr.kind, self.scan_error.take()) {
+                (TokenType::Error, Some(scan_error)) => scan_error,
+                _ => error,
+            };
+            return (exprs, Some((start, error)));
+        }
+        (exprs, None)
+    }
+
+    /// Scans the code again from `start` to the end of the file, for the tokens of the code that
+    /// doesn't parse. The end of file token is left in `curr`.
+    fn rescan(&mut self, start: Token<'src>) -> ErrorSource<'src> {
+        self.scanner.rewind(start.span.start);
+        self.trivia_start = start.span.start.index - start.leading.len();
+        self.curr = Token::synthetic("");
+        let mut tokens = vec![];
+        self.advance();
+        while !self.check(TokenType::Eof) {
+            tokens.push(self.curr);
+            self.advance();
         }
-        exprs.recompose(&mut self.arena)
+        ErrorSource { tokens }
+    }
+
+    /// Leaves `token`, which has just been consumed, out of the syntax tree: it becomes trivia
+    /// leading the current token.
+    pub fn discard(&mut self, token: Token<'src>) {
+        let start = token.span.start.index - token.leading.len();
+        self.curr.leading = &self.code[start..self.curr.span.start.index];
     }
 
     pub fn err(&self, msg: impl ToString) -> CupidError {
@@ -99,13 +188,19 @@ impl<'src> Iter<'src> for Parser<'src> {
 
     fn advance(&mut self) -> Token<'src> {
         self.prev = self.curr;
-        loop {
-            self.curr = self.next();
-            match self.curr.kind {
-                TokenType::Error => todo!(),
-                _ => break,
-            }
+        self.curr = self.next();
+        if self.curr.kind == TokenType::Error {
+            // The scanner puts the message in place of the lexeme, which gets the code back so
+            // that the token still prints as it was written
+            let message = self.curr.lexeme;
+            let span = self.curr.span;
+            self.curr.lexeme = &self.code[span.start.index..span.end.index];
+            let error = CupidError::parse_error(message, Some(self.curr.to_static()));
+            self.scan_error = Some(error);
         }
+        // The line breaks skipped over are part of the trivia.
+        self.curr.leading = &self.code[self.trivia_start..self.curr.span.start.index];
+        self.trivia_start = self.curr.span.end.index + self.curr.trailing.len();
         self.prev
     }
 
@@ -1,13 +1,18 @@
 use crate::{
     arena::UseArena,
     ast::{expr::GetSource, BinOp},
-    cst::{binop::BinOpSource, expr::ExprSource, unop::UnOpSource},
+    cst::{
+        binop::BinOpSource, call::CallSource, expr::ExprSource, propagate::PropagateSource,
+        unop::UnOpSource,
+    },
     error::CupidError,
     gc::Gc,
     token::{TokenType, INFIX_OPS, POSTFIX_OPS, PREFIX_OPS},
 };
 
-use super::{iter::Iter, parse_args, parse_unit, parser::Parser, Call, Expr, Header, UnOp};
+use super::{
+    iter::Iter, parse_args, parse_unit, parser::Parser, Call, Expr, Header, Propagate, UnOp,
+};
 
 pub fn parse_precedence<'src>(
     parser: &mut Parser<'src>,
@@ -16,19 +21,19 @@ pub fn parse_precedence<'src>(
 ) -> Result<Option<Expr<'src>>, CupidError> {
     let mut lhs = match PREFIX_OPS.contains(&parser.curr.kind) {
         true => {
-            parser.advance();
-            let ((), r_bp) = prefix_binding_power(parser.curr.kind);
+            let op = parser.advance();
+            let ((), r_bp) = prefix_binding_power(op.kind);
             let rhs = parse_precedence(parser, r_bp, gc)?.unwrap();
             let rhs = parser.arena.insert(rhs);
             let unop_source = UnOpSource {
                 expr_src: rhs.source_id(&parser.arena),
-                op: parser.curr,
+                op,
             };
             let unop_source_id = parser.arena.insert(ExprSource::from(unop_source));
             UnOp {
                 header: parser.header(unop_source_id),
                 expr: rhs,
-                op: parser.curr.kind,
+                op: op.kind,
             }
             .into()
         }
@@ -51,16 +56,38 @@ pub fn parse_precedence<'src>(
             }
             match op.kind {
                 TokenType::LeftParen => {
-                    let args = parse_args(parser, gc)?.unwrap();
-                    let header = lhs.header().clone();
+                    let (args, commas) = parse_args(parser, gc)?.unwrap();
+                    let call_source = CallSource {
+                        open_paren: op,
+                        close_paren: parser.prev,
+                        callee_src: lhs.header().source,
+                        args_src: args.iter().map(|arg| arg.source_id(&parser.arena)).collect(),
+                        commas,
+                    };
+                    let call_source_id = parser.arena.insert(ExprSource::from(call_source));
                     let callee = parser.arena.insert(lhs);
                     lhs = Call {
-                        header,
+                        header: parser.header(call_source_id),
                         callee,
                         args,
                     }
                     .into();
                 }
+                TokenType::Question => {
+                    let question = parser.advance();
+                    let propagate_source = PropagateSource {
+                        value_src: lhs.header().source,
+                        question,
+                    };
+                    let propagate_source_id =
+                        parser.arena.insert(ExprSource::from(propagate_source));
+                    let value = parser.arena.insert(lhs);
+                    lhs = Propagate {
+                        header: parser.header(propagate_source_id),
+                        value,
+                    }
+                    .into();
+                }
                 _ => (),
             }
             continue;
@@ -95,14 +122,14 @@ pub fn parse_precedence<'src>(
     Ok(Some(lhs))
 }
 
-fn prefix_binding_power(op: TokenType) -> ((), u8) {
+pub fn prefix_binding_power(op: TokenType) -> ((), u8) {
     match op {
         TokenType::Minus | TokenType::Bang => ((), 6),
         _ => panic!("bad op: {:?}", op),
     }
 }
 
-fn infix_binding_power(op: TokenType) -> (u8, u8) {
+pub fn infix_binding_power(op: TokenType) -> (u8, u8) {
     use self::TokenType as T;
     match op {
         T::Equal => (1, 2),
@@ -120,9 +147,11 @@ fn infix_binding_power(op: TokenType) -> (u8, u8) {
     }
 }
 
-fn postfix_binding_power(op: TokenType) -> Option<(u8, ())> {
+pub fn postfix_binding_power(op: TokenType) -> Option<(u8, ())> {
     let res = match op {
         TokenType::LeftParen => (8, ()),
+        // Below `.`, so that `a.b?` unwraps the property rather than `b`
+        TokenType::Question => (6, ()),
         _ => return None,
     };
     Some(res)
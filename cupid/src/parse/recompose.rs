@@ -1,12 +1,22 @@
 use crate::{
     arena::{EntryId, ExprArena, UseArena},
+    ast::expr::GetSource,
+    cst::{
+        call::CallSource,
+        expr::{ExprSource, UnwrapEnum},
+        get_property::GetPropertySource,
+        invoke::InvokeSource,
+        set::SetSource,
+        set_property::SetPropertySource,
+        SourceId,
+    },
     error::CupidError,
-    token::TokenType,
+    token::{Token, TokenType},
 };
 
 use super::{
-    Array, BinOp, Block, Break, Call, Class, Define, Expr, Fun, GetProperty, Header, If, Invoke,
-    InvokeSuper, Loop, Method, Return, Set, SetProperty, UnOp,
+    Array, BinOp, Block, Break, Call, Class, Define, Expr, ExprHeader, Fun, GetProperty, Header,
+    If, Invoke, InvokeSuper, Loop, Method, Propagate, Return, Set, SetProperty, Throw, Try, UnOp,
 };
 
 /// `Recompose` trait converts parsed instructions into other instructions.
@@ -64,7 +74,10 @@ impl<'src> Recompose<'src> for Expr<'src> {
             Expr::If(stmt) => Ok(Expr::If(stmt.recompose(arena)?)),
             Expr::Fun(fun) => Ok(Expr::Fun(fun.recompose(arena)?)),
             Expr::Loop(inner) => Ok(Expr::Loop(inner.recompose(arena)?)),
+            Expr::Propagate(propagate) => Ok(Expr::Propagate(propagate.recompose(arena)?)),
             Expr::Return(ret) => Ok(Expr::Return(ret.recompose(arena)?)),
+            Expr::Throw(throw) => Ok(Expr::Throw(throw.recompose(arena)?)),
+            Expr::Try(stmt) => Ok(Expr::Try(stmt.recompose(arena)?)),
             Expr::UnOp(unop) => Ok(Expr::UnOp(unop.recompose(arena)?)),
             _ => Ok(self),
         }
@@ -86,45 +99,112 @@ impl<'src> Recompose<'src> for BinOp<'src> {
     fn recompose(self, arena: &mut ExprArena<'src>) -> Result<Expr<'src>, CupidError> {
         let left = self.left.recompose(arena)?;
         let right = self.right.recompose(arena)?;
-        let left_ref = arena.expect(left);
-        let right_ref = arena.expect(right);
+        let left_ref = arena.expect_expr(left).clone();
+        let right_ref = arena.expect_expr(right).clone();
+        let source = match arena.expect_source(self.header.source) {
+            ExprSource::BinOp(source) => source.clone(),
+            _ => unreachable!("Binary operations always have a binary operation source."),
+        };
         match self.op {
             TokenType::Equal => match left_ref {
-                Expr::GetProperty(get) => Ok(SetProperty {
-                    header: get.header.clone(),
-                    receiver: get.receiver.to_owned(),
-                    property: get.property,
-                    value: right,
-                    symbol: None,
+                Expr::GetProperty(get) => {
+                    let get_source: &GetPropertySource = get.source(arena).unwrapped();
+                    let source_id = insert_source(
+                        arena,
+                        SetPropertySource {
+                            receiver: get_source.receiver,
+                            dot: get_source.dot,
+                            property: get_source.property,
+                            equal: source.op,
+                            value: source.right_src,
+                        },
+                    );
+                    Ok(SetProperty {
+                        header: ExprHeader {
+                            source: source_id,
+                            ..get.header.clone()
+                        },
+                        receiver: get.receiver.to_owned(),
+                        property: get.property,
+                        value: right,
+                        symbol: None,
+                    }
+                    .into())
                 }
-                .into()),
-                _ => Ok(Set {
-                    header: left_ref.header().clone(),
-                    name: extract_name(&left_ref)?,
-                    value: right,
-                    symbol: None,
+                _ => {
+                    let name = extract_name(&left_ref)?;
+                    let source_id = insert_source(
+                        arena,
+                        SetSource {
+                            name: extract_token(source.left_src, arena)?,
+                            value_src: source.right_src,
+                            equal: source.op,
+                        },
+                    );
+                    Ok(Set {
+                        header: ExprHeader {
+                            source: source_id,
+                            ..left_ref.header().clone()
+                        },
+                        name,
+                        value: right,
+                        symbol: None,
+                    }
+                    .into())
                 }
-                .into()),
             },
             TokenType::Dot => match right_ref {
-                Expr::Call(call) => Ok(Invoke {
-                    header: call.header.clone(),
-                    receiver: left,
-                    callee: extract_entry_token(call.callee, arena)?,
-                    args: call.args.to_owned().recompose(arena)?,
-                    symbol: None,
+                Expr::Call(call) => {
+                    let callee = extract_entry_token(call.callee, arena)?;
+                    let call_source: &CallSource = call.source(arena).unwrapped();
+                    let source_id = insert_source(
+                        arena,
+                        InvokeSource {
+                            receiver: source.left_src,
+                            dot: source.op,
+                            callee: extract_token(call_source.callee_src, arena)?,
+                            open_paren: call_source.open_paren,
+                            close_paren: call_source.close_paren,
+                            commas: call_source.commas.clone(),
+                            args: call_source.args_src.clone(),
+                        },
+                    );
+                    Ok(Invoke {
+                        header: ExprHeader {
+                            source: source_id,
+                            ..call.header.clone()
+                        },
+                        receiver: left,
+                        callee,
+                        args: call.args.to_owned().recompose(arena)?,
+                        symbol: None,
+                    }
+                    .into())
                 }
-                .into()),
-                _ => Ok(GetProperty {
-                    header: left_ref.header().clone(),
-                    receiver: left,
-                    property: extract_name(right_ref)?,
-                    symbol: None,
+                _ => {
+                    let property = extract_name(&right_ref)?;
+                    let source_id = insert_source(
+                        arena,
+                        GetPropertySource {
+                            receiver: source.left_src,
+                            property: extract_token(source.right_src, arena)?,
+                            dot: source.op,
+                        },
+                    );
+                    Ok(GetProperty {
+                        header: ExprHeader {
+                            source: source_id,
+                            ..left_ref.header().clone()
+                        },
+                        receiver: left,
+                        property,
+                        symbol: None,
+                    }
+                    .into())
                 }
-                .into()),
             },
             _ => Ok(BinOp {
-                header: left_ref.header().clone(),
+                header: self.header,
                 left,
                 op: self.op,
                 right,
@@ -245,6 +325,16 @@ impl<'src> Recompose<'src> for Fun<'src> {
     }
 }
 
+impl<'src> Recompose<'src> for Propagate<'src> {
+    type Output = Self;
+    fn recompose(self, arena: &mut ExprArena<'src>) -> Result<Self::Output, CupidError> {
+        Ok(Propagate {
+            value: self.value.recompose(arena)?,
+            ..self
+        })
+    }
+}
+
 impl<'src> Recompose<'src> for Return<'src> {
     type Output = Self;
     fn recompose(self, arena: &mut ExprArena<'src>) -> Result<Self::Output, CupidError> {
@@ -258,6 +348,28 @@ impl<'src> Recompose<'src> for Return<'src> {
     }
 }
 
+impl<'src> Recompose<'src> for Throw<'src> {
+    type Output = Self;
+    fn recompose(self, arena: &mut ExprArena<'src>) -> Result<Self::Output, CupidError> {
+        Ok(Throw {
+            value: self.value.recompose(arena)?,
+            ..self
+        })
+    }
+}
+
+impl<'src> Recompose<'src> for Try<'src> {
+    type Output = Self;
+    fn recompose(self, arena: &mut ExprArena<'src>) -> Result<Self::Output, CupidError> {
+        Ok(Try {
+            body: self.body.recompose(arena)?,
+            catch_body: self.catch_body.recompose(arena)?,
+            finally_body: self.finally_body.recompose(arena)?,
+            ..self
+        })
+    }
+}
+
 impl<'src> Recompose<'src> for UnOp<'src> {
     type Output = Self;
     fn recompose(self, arena: &mut ExprArena<'src>) -> Result<Self::Output, CupidError> {
@@ -268,6 +380,20 @@ impl<'src> Recompose<'src> for UnOp<'src> {
     }
 }
 
+fn insert_source<'src>(
+    arena: &mut ExprArena<'src>,
+    source: impl Into<ExprSource<'src>>,
+) -> SourceId {
+    arena.insert(source.into()).into()
+}
+
+fn extract_token<'src>(id: SourceId, arena: &ExprArena<'src>) -> Result<Token<'src>, CupidError> {
+    match arena.expect_source(id) {
+        ExprSource::Get(get) => Ok(get.name),
+        source => Err(CupidError::parse_error(format!("Expected token: {:#?}", source), None)),
+    }
+}
+
 fn extract_entry_token<'src>(
     id: EntryId,
     arena: &ExprArena<'src>,
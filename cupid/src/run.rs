@@ -3,19 +3,20 @@ use crate::{
     analyze::{infer::Infer, pretty::PrettyPrint, resolve::Resolve},
     arena::ExprArena,
     ast::expr::Expr,
+    diagnostic,
     error::{CupidErr, CupidError},
     parse::{bytecode::BytecodeCompiler, parser::Parser},
     vm::Vm,
 };
 #[allow(unused_imports)]
 use cupid_fmt::reindent::{Multiline, Reindent};
-use std::{fs, process};
+use std::{fs, io::Write, process};
 
 /// 1. Resolve symbols (classes, functions, local variables, etc.)
 /// 2. Infer types
 /// 3. Resolve properties & methods
 /// 4. Infer properties & methods
-fn do_passes<'src>(
+pub fn do_passes<'src>(
     expr: Vec<Expr<'src>>,
     arena: &mut ExprArena<'src>,
 ) -> Result<Vec<Expr<'src>>, CupidError> {
@@ -30,6 +31,7 @@ pub fn run_file(vm: &mut Vm, path: &str) {
             process::exit(74);
         }
     };
+    vm.path = Some(path.to_string());
     let mut parser = Parser::new(&*code);
     let expr = parser.parse(&mut vm.gc);
 
@@ -37,19 +39,36 @@ pub fn run_file(vm: &mut Vm, path: &str) {
         Ok(expr) => {
             let expr = match do_passes(expr, &mut parser.arena) {
                 Ok(expr) => expr,
-                Err(err) => panic!("{}", err),
+                Err(err) => {
+                    diagnostic::report(&err.locate(&parser.arena), vm.error_format, path);
+                    process::exit(65);
+                }
             };
             // println!("{expr:#?}");
             // println!("{}", expr.pretty_print(&parser.arena).multiline(40).reindent(3));
             let compiler = BytecodeCompiler::new(expr, parser.arena, &mut vm.gc);
-            let function = compiler.compile();
-            match vm.interpret_function(function) {
+            let function = match compiler.compile() {
+                Ok(function) => function,
+                Err(err) => {
+                    diagnostic::report(&err, vm.error_format, path);
+                    process::exit(65);
+                }
+            };
+            let result = vm.interpret_function(function);
+            let streams = vm.streams.get_mut();
+            if vm.gc.config().stats {
+                write!(streams.stderr, "{}", vm.gc.stats()).ok();
+            }
+            match result {
                 Err(CupidErr::CompileError) => process::exit(65),
                 Err(CupidErr::RuntimeError) => process::exit(70),
-                Ok(_) => println!("Process exited successfully."),
+                Ok(_) => {}
             }
         }
-        Err(error) => eprintln!("{error}"),
+        Err(error) => {
+            diagnostic::report(&error, vm.error_format, path);
+            process::exit(65);
+        }
     }
 
     // if let Err(error) = vm.interpret(&code) {
@@ -29,9 +29,11 @@ impl<'src> Scanner<'src> {
         let keywords: HashMap<&str, TokenType> = keyword_map!(17, {
                "and": TokenType::And,
              "break": TokenType::Break,
+             "catch": TokenType::Catch,
              "class": TokenType::Class,
               "else": TokenType::Else,
              "false": TokenType::False,
+           "finally": TokenType::Finally,
                "for": TokenType::For,
                "fun": TokenType::Fun,
                 "if": TokenType::If,
@@ -44,7 +46,9 @@ impl<'src> Scanner<'src> {
             "return": TokenType::Return,
              "super": TokenType::Super,
               "self": TokenType::This,
+             "throw": TokenType::Throw,
               "true": TokenType::True,
+               "try": TokenType::Try,
                "let": TokenType::Let,
              "while": TokenType::While,
              "trait": TokenType::Role,
@@ -58,6 +62,16 @@ impl<'src> Scanner<'src> {
         }
     }
 
+    pub fn keywords(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.keywords.keys().copied()
+    }
+
+    /// Goes back to `position`, to scan the code from there again.
+    pub fn rewind(&mut self, position: Position) {
+        self.start = position;
+        self.position = position;
+    }
+
     pub fn peek_token(&mut self) -> Token<'src> {
         let start = self.start;
         let position = self.position;
@@ -68,7 +82,20 @@ impl<'src> Scanner<'src> {
     }
 
     pub fn scan_token(&mut self) -> Token<'src> {
+        let leading_start = self.position.index;
         self.skip_whitespace();
+        let mut token = self.lex_token();
+        token.leading = &self.code[leading_start..self.start.index];
+        // What follows a line break is indented and belongs to the next line.
+        if token.kind != TokenType::NewLine {
+            let trailing_start = self.position.index;
+            self.skip_whitespace();
+            token.trailing = &self.code[trailing_start..self.position.index];
+        }
+        token
+    }
+
+    fn lex_token(&mut self) -> Token<'src> {
         self.start = self.position;
         if self.is_at_end() {
             return self.make_token(TokenType::Eof);
@@ -89,6 +116,7 @@ impl<'src> Scanner<'src> {
             b':' => self.make_token(TokenType::Colon),
             b',' => self.make_token(TokenType::Comma),
             b'.' => self.make_token(TokenType::Dot),
+            b'?' => self.make_token(TokenType::Question),
             b'-' => self.make_token(TokenType::Minus),
             b'+' => self.make_token(TokenType::Plus),
             b'/' => self.make_token(TokenType::Slash),
@@ -125,6 +153,8 @@ impl<'src> Scanner<'src> {
                 start: self.start,
                 end: self.position,
             },
+            leading: "",
+            trailing: "",
         }
     }
 
@@ -144,14 +174,18 @@ impl<'src> Scanner<'src> {
         }
     }
 
+    /// A token for code that can't be scanned, with the message in place of the lexeme and the
+    /// span of the code.
     fn error_token(&self, message: &'static str) -> Token<'static> {
         Token {
             kind: TokenType::Error,
             lexeme: message,
             span: Span {
-                start: self.position,
+                start: self.start,
                 end: self.position,
             },
+            leading: "",
+            trailing: "",
         }
     }
 
@@ -176,7 +210,8 @@ impl<'src> Scanner<'src> {
                 b' ' | b'\r' | b'\t' => {
                     self.advance();
                 }
-                b'-' if self.peek_next() == b'-' => {
+                // `--` comments, and `##` doc comments.
+                b'-' | b'#' if self.peek_next() == self.peek() => {
                     while self.peek() != b'\n' && !self.is_at_end() {
                         self.advance();
                     }
@@ -188,10 +223,9 @@ impl<'src> Scanner<'src> {
 
     fn string(&mut self) -> Token<'src> {
         while self.peek() != b'\'' && !self.is_at_end() {
-            if self.peek() == b'\n' {
+            if self.advance() == b'\n' {
                 self.position.increment_line();
             }
-            self.advance();
         }
 
         if self.is_at_end() {
@@ -2,8 +2,11 @@ use std::{collections::HashMap, fmt};
 
 use crate::{
     arena::{EntryId, ExprArena, UseArena},
+    code::Code,
     error::CupidError,
     pointer::Pointer,
+    scanner::Scanner,
+    suggest::did_you_mean,
     ty::Type,
 };
 
@@ -18,6 +21,10 @@ pub struct Scope<'src> {
     pub symbols: HashMap<&'src str, Pointer<Symbol<'src>>>,
     pub classes: HashMap<ClassId<'src>, ClassTable<'src>>,
     pub depth: usize,
+    /// In the scope of a function: the types of the values it returns, and of the options and
+    /// results that `?` may return early, checked against each other once the function is inferred.
+    pub returns: Vec<Type<'src>>,
+    pub propagates: Vec<Type<'src>>,
 }
 
 pub trait Lookup<'src, K, V> {
@@ -74,15 +81,64 @@ impl<'src> Scope<'src> {
         let class_table = match receiver_ty {
             Type::Class(class_name) => self.lookup(class_name),
             Type::Instance(instance_class_name) => self.lookup(instance_class_name),
-            _ => return Err(CupidError::type_error("Only classes have properties.", "")),
+            _ => {
+                return Err(CupidError::type_error(
+                    Code::NotAnInstance,
+                    "Only classes have properties.",
+                    "",
+                ))
+            }
         };
-        match class_table {
-            Some(class) => match class.scope.borrow().lookup(prop) {
-                Some(value) => Ok(Some(value)),
-                None => Ok(None),
-            },
-            None => Err(CupidError::name_error(format!("Undefined: `{}`", prop), "")),
+        let mut class = match class_table {
+            Some(class) => class,
+            None => {
+                return Err(CupidError::name_error(
+                    Code::UndefinedProperty,
+                    format!("Undefined: `{}`", prop),
+                    "",
+                ))
+            }
+        };
+        // Members are looked up in the class and then its ancestors, never in enclosing scopes.
+        let mut seen = vec![];
+        loop {
+            if let Some(value) = class.scope.borrow().lookup_current(prop) {
+                return Ok(Some(value));
+            }
+            match class.super_class.filter(|super_class| !seen.contains(super_class)) {
+                Some(super_class) => {
+                    seen.push(super_class);
+                    match self.lookup(super_class) {
+                        Some(super_class) => class = super_class,
+                        None => return Ok(None),
+                    }
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// The names defined in this scope and the scopes around it.
+    pub fn visible_names(&self) -> Vec<&'src str> {
+        let mut names: Vec<&'src str> = self.symbols.keys().copied().collect();
+        if let Some(parent) = &self.parent {
+            names.extend(parent.borrow().visible_names());
         }
+        names
+    }
+
+    /// The error for a name that is not defined, suggesting a visible name or keyword close to it.
+    pub fn undefined_name(&self, name: &str) -> CupidError {
+        let keywords: Vec<&'static str> = Scanner::new("").keywords().collect();
+        let mut names = self.visible_names();
+        names.extend(keywords);
+        let help = did_you_mean(name, names);
+        CupidError::name_error(
+            Code::UndefinedVariable,
+            format!("Undefined: `{}`", name),
+            name.to_string(),
+        )
+        .with_help(help)
     }
 
     pub fn define(&mut self, name: &'src str) {
@@ -95,6 +151,25 @@ impl<'src> Scope<'src> {
         );
     }
 
+    /// Defines `name` with the symbol it was given by an earlier pass, if any, so that every
+    /// reference to it resolves to the same symbol no matter which pass resolved it.
+    pub fn define_symbol(
+        &mut self,
+        name: &'src str,
+        symbol: Option<Pointer<Symbol<'src>>>,
+    ) -> Pointer<Symbol<'src>> {
+        match symbol {
+            Some(symbol) => {
+                self.symbols.insert(name, symbol.clone());
+                symbol
+            }
+            None => {
+                self.define(name);
+                self.symbols[name].clone()
+            }
+        }
+    }
+
     pub fn annotate_ty(&mut self, name: &'src str, ty: Type<'src>) {
         self.symbols.entry(name).and_modify(|sym| sym.borrow_mut().ty = ty);
     }
@@ -111,8 +186,17 @@ impl<'src> Scope<'src> {
             .and_modify(|sym| sym.borrow_mut().value = SymbolValue::Class(ClassId(name)));
     }
 
-    pub fn insert_class(&mut self, name: &'src str, class_scope: Pointer<Scope<'src>>) {
-        self.classes.insert(ClassId(name), ClassTable { scope: class_scope });
+    pub fn insert_class(
+        &mut self,
+        name: &'src str,
+        class_scope: Pointer<Scope<'src>>,
+        super_class: Option<&'src str>,
+    ) {
+        let table = ClassTable {
+            scope: class_scope,
+            super_class: super_class.map(ClassId),
+        };
+        self.classes.insert(ClassId(name), table);
         self.annotate_class(name)
     }
 
@@ -148,9 +232,51 @@ impl<'src> Scope<'src> {
             },
         );
 
+        // Errors are instances, but the properties of `Error` are only known at runtime
+        self.define("Error");
+        self.annotate_ty(
+            "Error",
+            Type::Function {
+                returns: unknown_ty,
+            },
+        );
+
+        self.define("read_line");
+        self.annotate_ty(
+            "read_line",
+            Type::Function {
+                returns: unknown_ty,
+            },
+        );
+
         self.define("clock");
         let float_ty = arena.insert(Type::Float);
         self.annotate_ty("clock", Type::Function { returns: float_ty });
+
+        for name in ["assert", "assert_eq", "assert_err"] {
+            self.define(name);
+            self.annotate_ty(name, Type::Function { returns: nil_ty });
+        }
+
+        let array_ty = arena.insert(Type::Array(unknown_ty));
+        for name in ["map", "filter", "sort_by"] {
+            self.define(name);
+            self.annotate_ty(name, Type::Function { returns: array_ty });
+        }
+
+        // Calls narrow these down to the type of the argument
+        self.define("some");
+        let option_ty = arena.insert(Type::Option(unknown_ty));
+        self.annotate_ty("some", Type::Function { returns: option_ty });
+
+        let result_ty = arena.insert(Type::Result {
+            ok: unknown_ty,
+            err: unknown_ty,
+        });
+        for name in ["ok", "err"] {
+            self.define(name);
+            self.annotate_ty(name, Type::Function { returns: result_ty });
+        }
     }
 }
 
@@ -191,6 +317,14 @@ impl<'src> Pointer<Scope<'src>> {
     pub fn is_context(&self, context: ScopeContext) -> bool {
         self.borrow().context == context
     }
+
+    /// The closest scope with the given context, this one included.
+    pub fn enclosing(&self, context: ScopeContext) -> Option<Pointer<Scope<'src>>> {
+        match self.is_context(context) {
+            true => Some(self.clone()),
+            false => self.parent()?.enclosing(context),
+        }
+    }
 }
 
 impl fmt::Debug for Scope<'_> {
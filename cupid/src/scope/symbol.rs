@@ -23,6 +23,7 @@ pub struct ClassId<'src>(pub &'src str);
 #[derive(Debug, Clone)]
 pub struct ClassTable<'src> {
     pub scope: Pointer<Scope<'src>>,
+    pub super_class: Option<ClassId<'src>>,
 }
 
 impl<'src> GetTy<'src> for Symbol<'src> {
@@ -0,0 +1,37 @@
+use crate::vm::Interrupt;
+
+#[cfg(unix)]
+use std::sync::OnceLock;
+
+/// The flag set by Ctrl-C, which the handler can only reach through a static.
+#[cfg(unix)]
+static CTRL_C: OnceLock<Interrupt> = OnceLock::new();
+
+/// Makes Ctrl-C interrupt the running program with a catchable runtime error instead of killing
+/// the process. Pressing it again before the program has stopped exits right away, as the program
+/// may be stuck waiting for input. Elsewhere than on Unix, Ctrl-C keeps its default behavior.
+pub fn interrupt_on_ctrl_c(interrupt: &Interrupt) {
+    #[cfg(unix)]
+    {
+        if CTRL_C.set(interrupt.clone()).is_err() {
+            return;
+        }
+        let handler = on_ctrl_c as extern "C" fn(libc::c_int);
+        unsafe {
+            libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = interrupt;
+}
+
+/// Only touches atomics, as a signal handler may not allocate or take locks.
+#[cfg(unix)]
+extern "C" fn on_ctrl_c(_signal: libc::c_int) {
+    if let Some(interrupt) = CTRL_C.get() {
+        if interrupt.is_interrupted() {
+            unsafe { libc::_exit(130) }
+        }
+        interrupt.interrupt();
+    }
+}
@@ -1,10 +1,30 @@
-#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+use serde::Serialize;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub struct Span {
     pub start: Position,
     pub end: Position,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+impl Span {
+    /// Tokens made up by the parser (e.g. the `if` of a desugared `while` loop) have no location.
+    pub fn is_synthetic(&self) -> bool {
+        self.start.line == 0
+    }
+
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end,
+        }
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.start.index <= index && index <= self.end.index
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub struct Position {
     pub index: usize,
     pub line: usize,
@@ -35,8 +55,9 @@ impl Position {
         self.col += 1;
     }
 
+    /// Moves to the start of the next line, once its newline has been consumed.
     pub fn increment_line(&mut self) {
         self.line += 1;
-        self.col = 0;
+        self.col = 1;
     }
 }
@@ -16,7 +16,9 @@ impl fmt::Debug for Entry {
     }
 }
 
-#[derive(Debug, Clone)]
+/// An open addressing hash table keyed by interned strings. With the `checked` feature enabled,
+/// the table validates its invariants after every mutation.
+#[derive(Debug)]
 pub struct Table {
     count: usize,
     capacity: usize,
@@ -55,6 +57,8 @@ impl Table {
             }
             (*entry).key = Some(key);
             (*entry).value = value;
+            #[cfg(feature = "checked")]
+            self.validate();
             is_new_key
         }
     }
@@ -84,10 +88,17 @@ impl Table {
             }
             (*entry).key = None;
             (*entry).value = Value::Bool(true);
+            #[cfg(feature = "checked")]
+            self.validate();
             true
         }
     }
 
+    /// Bytes taken up by the entries, which live outside the table itself
+    pub fn heap_size(&self) -> usize {
+        self.capacity * std::mem::size_of::<Entry>()
+    }
+
     pub fn iter(&self) -> IterTable {
         IterTable {
             ptr: self.entries,
@@ -160,31 +171,84 @@ impl Table {
 
     unsafe fn adjust_capacity(&mut self, capacity: usize) {
         let entries = alloc(Layout::array::<Entry>(capacity).unwrap()) as *mut Entry;
-        for i in 0..(capacity as isize) {
-            let entry = entries.offset(i);
-            (*entry).key = None;
-            (*entry).value = Value::Nil
+        for i in 0..capacity {
+            entries.add(i).write(Entry {
+                key: None,
+                value: Value::Nil,
+            });
         }
         self.count = 0;
-        for i in 0..(self.capacity as isize) {
-            let entry = self.entries.offset(i);
-            match (*entry).key {
-                Some(k) => {
-                    let dest = Table::find_entry(entries, capacity, k);
-                    (*dest).key = (*entry).key;
-                    (*dest).value = (*entry).value;
-                    self.count += 1;
-                }
-                None => continue,
-            }
-            if (*entry).key.is_none() {
-                continue;
+        for i in 0..self.capacity {
+            let entry = self.entries.add(i);
+            if let Some(k) = (*entry).key {
+                let dest = Table::find_entry(entries, capacity, k);
+                (*dest).key = (*entry).key;
+                (*dest).value = (*entry).value;
+                self.count += 1;
             }
         }
-        dealloc(self.entries.cast(), Layout::array::<Entry>(self.capacity).unwrap());
+        if !self.entries.is_null() {
+            dealloc(self.entries.cast(), Layout::array::<Entry>(self.capacity).unwrap());
+        }
         self.entries = entries;
         self.capacity = capacity;
     }
+
+    /// Panics if the table is inconsistent: the capacity must be a power of two within the load
+    /// factor, `count` must include every key and tombstone, and every key must be reachable from
+    /// the start of its probe sequence.
+    pub fn validate(&self) {
+        if self.capacity == 0 {
+            assert!(self.entries.is_null(), "Table without capacity has entries");
+            assert_eq!(self.count, 0, "Table without capacity has a count");
+            return;
+        }
+        assert!(
+            self.capacity.is_power_of_two(),
+            "Table capacity {} is not a power of two",
+            self.capacity
+        );
+        assert!(
+            self.count <= (self.capacity as f32 * Table::MAX_LOAD) as usize,
+            "Table count {} is over the load factor of capacity {}",
+            self.count,
+            self.capacity
+        );
+        let mut used = 0;
+        for i in 0..self.capacity {
+            let entry = unsafe { *self.entries.add(i) };
+            match entry.key {
+                Some(key) => {
+                    used += 1;
+                    let found = unsafe { Table::find_entry(self.entries, self.capacity, key) };
+                    assert!(
+                        std::ptr::eq(found, unsafe { self.entries.add(i) }),
+                        "Table key '{}' is not reachable from its probe sequence",
+                        key.s
+                    );
+                }
+                None if matches!(entry.value, Value::Nil) => (),
+                // Tombstone
+                None => used += 1,
+            }
+        }
+        assert_eq!(used, self.count, "Table count does not match its keys and tombstones");
+    }
+}
+
+impl Clone for Table {
+    fn clone(&self) -> Self {
+        let mut table = Table::default();
+        if self.capacity > 0 {
+            unsafe {
+                table.entries = alloc(Layout::array::<Entry>(self.capacity).unwrap()) as *mut Entry;
+                self.entries.copy_to_nonoverlapping(table.entries, self.capacity);
+            }
+            table.capacity = self.capacity;
+            table.count = self.count;
+        }
+        table
+    }
 }
 
 impl Drop for Table {
@@ -364,4 +428,47 @@ mod tests {
 
         assert!(numbers.is_empty())
     }
+
+    #[test]
+    fn validate() {
+        let mut gc = Gc::default();
+        let mut table = Table::default();
+        table.validate();
+        let keys: Vec<GcRef<Str>> =
+            (0..32).map(|i| gc.alloc(Str::from_string(format!("key {}", i)))).collect();
+
+        for &key in &keys {
+            table.set(key, Value::Nil);
+        }
+        for &key in keys.iter().step_by(3) {
+            table.delete(key);
+        }
+        table.validate();
+
+        // Deleted keys leave tombstones that are reused
+        for &key in keys.iter().step_by(3) {
+            table.set(key, Value::Bool(true));
+        }
+        table.validate();
+        assert_eq!(table.iter().count(), keys.len());
+    }
+
+    #[test]
+    fn clone() {
+        let mut gc = Gc::default();
+        let mut table = Table::default();
+        let foo = gc.alloc(Str::from_string("foo".to_owned()));
+        let bar = gc.alloc(Str::from_string("bar".to_owned()));
+        table.set(foo, Value::Int(1));
+
+        let mut cloned = table.clone();
+        cloned.set(foo, Value::Int(2));
+        cloned.set(bar, Value::Int(3));
+
+        assert!(matches!(table.get(foo), Some(Value::Int(1))));
+        assert!(table.get(bar).is_none());
+        assert!(matches!(cloned.get(foo), Some(Value::Int(2))));
+        assert!(matches!(cloned.get(bar), Some(Value::Int(3))));
+        assert!(Table::default().clone().get(foo).is_none());
+    }
 }
@@ -0,0 +1,279 @@
+use std::{
+    cell::RefCell,
+    fs, panic,
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    ast::Expr,
+    error::CupidError,
+    gc::Gc,
+    parse::{bytecode::BytecodeCompiler, parser::Parser},
+    run::do_passes,
+    value::Value,
+    vm::{Vm, VmConfig},
+};
+
+/// What ended a test.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Passed,
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestResult {
+    pub file: PathBuf,
+    pub name: String,
+    pub outcome: Outcome,
+    pub duration: Duration,
+}
+
+/// The tests of a file: its top level functions named `test_...` that take no parameters, declared
+/// either with `fun` or `let`, in the order they are declared.
+pub fn discover(code: &str) -> Result<Vec<String>, CupidError> {
+    let mut gc = Gc::default();
+    let mut parser = Parser::new(code);
+    let exprs = parser.parse(&mut gc)?;
+    let arena = &parser.arena;
+    let tests = exprs.iter().filter_map(|expr| match expr {
+        Expr::Fun(fun) if fun.params.is_empty() => fun.name,
+        Expr::Define(define) => match define.value.map(|value| arena.expect_expr(value)) {
+            Some(Expr::Fun(fun)) if fun.params.is_empty() => Some(define.name),
+            _ => None,
+        },
+        _ => None,
+    });
+    Ok(tests.filter(|name| name.starts_with("test_")).map(str::to_string).collect())
+}
+
+/// Runs the test `name` of `code` on a fresh vm: the top level of the code runs first, then the
+/// test function. The test passes if it returns, and fails with the error that stopped it if not.
+pub fn run_test(code: &str, name: &str, config: VmConfig) -> Outcome {
+    let mut vm = Vm::with_config(config);
+    vm.initialize();
+    // The error is kept for the report instead of being printed as it happens
+    vm.diagnostics = Some(RefCell::default());
+    match run_test_function(&mut vm, code, name) {
+        Ok(()) => Outcome::Passed,
+        Err(message) => Outcome::Failed(message),
+    }
+}
+
+fn run_test_function(vm: &mut Vm, code: &str, name: &str) -> Result<(), String> {
+    let mut parser = Parser::new(code);
+    let exprs = parser.parse(&mut vm.gc).map_err(|error| error.to_string())?;
+    let exprs = do_passes(exprs, &mut parser.arena)
+        .map_err(|error| error.locate(&parser.arena).to_string())?;
+    let function = BytecodeCompiler::new(exprs, parser.arena, &mut vm.gc)
+        .compile()
+        .map_err(|error| error.to_string())?;
+    vm.interpret_function(function)
+        .map_err(|_| format!("Runtime error in the top level code: {}", runtime_error(vm)))?;
+    let name = vm.intern(name.to_string());
+    match vm.globals.get(name) {
+        Some(test @ Value::Closure(_)) => {
+            vm.call(test, &[]).map(|_| ()).map_err(|_| runtime_error(vm))
+        }
+        _ => Err(format!("'{}' is not a function.", &name.s)),
+    }
+}
+
+/// The message of the runtime error the vm collected last.
+fn runtime_error(vm: &Vm) -> String {
+    let diagnostics = vm.diagnostics.as_ref().map(|diagnostics| diagnostics.borrow());
+    match diagnostics.as_ref().and_then(|diagnostics| diagnostics.last()) {
+        Some(diagnostic) => diagnostic.message.clone(),
+        None => "Runtime error.".to_string(),
+    }
+}
+
+/// The Cupid files at `path`: the file itself, or the files in the directory and the directories
+/// below it, sorted by path.
+fn files(path: &Path) -> Vec<PathBuf> {
+    if !path.is_dir() {
+        return vec![path.to_path_buf()];
+    }
+    let mut files = vec![];
+    let mut entries: Vec<PathBuf> = match fs::read_dir(path) {
+        Ok(entries) => entries.filter_map(|entry| Some(entry.ok()?.path())).collect(),
+        Err(_) => return files,
+    };
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            files.extend(self::files(&entry));
+        } else if entry.extension().is_some_and(|extension| extension == "cupid") {
+            files.push(entry);
+        }
+    }
+    files
+}
+
+/// Runs the tests of the Cupid files at `path`, each on a fresh vm, printing the outcome and time
+/// of each test followed by a summary. Exits with an error if any test fails.
+pub fn test_command(path: &str, config: VmConfig) {
+    let start = Instant::now();
+    // The failures are reported along with the summary.
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let mut results = vec![];
+    for file in files(Path::new(path)) {
+        let code = match fs::read_to_string(&file) {
+            Ok(code) => code,
+            Err(error) => {
+                eprintln!("Unable to read file {}: {}", file.display(), error);
+                process::exit(74);
+            }
+        };
+        let tests = match panic::catch_unwind(|| discover(&code)) {
+            Ok(Ok(tests)) => tests,
+            // Files that don't parse have no tests to run.
+            _ => continue,
+        };
+        if !tests.is_empty() {
+            println!("\nrunning {} tests in {}", tests.len(), file.display());
+        }
+        for name in tests {
+            let test_start = Instant::now();
+            let outcome = run_test(&code, &name, config);
+            let duration = test_start.elapsed();
+            let status = match outcome {
+                Outcome::Passed => "ok",
+                Outcome::Failed(_) => "FAILED",
+            };
+            println!("test {name} ... {status} ({duration:.2?})");
+            results.push(TestResult {
+                file: file.clone(),
+                name,
+                outcome,
+                duration,
+            });
+        }
+    }
+    panic::set_hook(hook);
+
+    let failures: Vec<&TestResult> =
+        results.iter().filter(|result| result.outcome != Outcome::Passed).collect();
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for failure in &failures {
+            if let Outcome::Failed(message) = &failure.outcome {
+                println!("\n---- {} in {} ----\n{}", failure.name, failure.file.display(), message);
+            }
+        }
+    }
+    let status = match failures.is_empty() {
+        true => "ok",
+        false => "FAILED",
+    };
+    println!(
+        "\ntest result: {}. {} passed; {} failed; finished in {:.2?}",
+        status,
+        results.len() - failures.len(),
+        failures.len(),
+        start.elapsed()
+    );
+    if !failures.is_empty() {
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{discover, run_test, Outcome};
+    use crate::vm::VmConfig;
+
+    const CODE: &str = "\
+fun add (a, b) {
+    return a + b
+}
+
+fun test_add () {
+    assert_eq(add(1, 2), 3)
+}
+
+fun test_add_fails () {
+    assert_eq(add(1, 2), 4)
+}
+
+fun test_assert () {
+    assert(add(1, 1) == 3)
+}
+
+fun test_undefined_property () {
+    class A {}
+    assert_err(fun () {
+        A().missing
+    })
+}
+
+fun test_no_error () {
+    assert_err(fun () {})
+}
+
+fun test_caught () {
+    try {
+        assert(false)
+    } catch (e) {
+        assert_eq(e.code, 'E0310')
+    }
+}
+
+let test_defined = fun () {
+    assert(true)
+}
+
+fun helper (a) {}
+";
+
+    fn failure(name: &str) -> String {
+        match run_test(CODE, name, VmConfig::default()) {
+            Outcome::Failed(message) => message,
+            Outcome::Passed => panic!("{} passed", name),
+        }
+    }
+
+    #[test]
+    fn discovers_test_functions() {
+        let tests = discover(CODE).unwrap();
+        assert_eq!(
+            tests,
+            [
+                "test_add",
+                "test_add_fails",
+                "test_assert",
+                "test_undefined_property",
+                "test_no_error",
+                "test_caught",
+                "test_defined"
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_both_values_of_failed_assertions() {
+        assert_eq!(run_test(CODE, "test_add", VmConfig::default()), Outcome::Passed);
+        assert_eq!(
+            failure("test_add_fails"),
+            "Assertion failed: values are not equal\n  left: 3\n right: 4"
+        );
+        assert_eq!(failure("test_assert"), "Assertion failed: expected true, got false.");
+    }
+
+    #[test]
+    fn expects_errors_from_assert_err() {
+        assert_eq!(run_test(CODE, "test_undefined_property", VmConfig::default()), Outcome::Passed);
+        assert_eq!(
+            failure("test_no_error"),
+            "Assertion failed: expected an error, but the function returned none."
+        );
+    }
+
+    #[test]
+    fn catches_failed_assertions() {
+        assert_eq!(run_test(CODE, "test_caught", VmConfig::default()), Outcome::Passed);
+    }
+}
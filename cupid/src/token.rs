@@ -1,6 +1,8 @@
+use serde::Serialize;
+
 use crate::span::{Position, Span};
 
-#[derive(Eq, PartialEq, Debug, Copy, Clone, Hash)]
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Hash, Serialize)]
 pub enum TokenType {
     LeftParen,
     RightParen,
@@ -28,6 +30,7 @@ pub enum TokenType {
     LessEqual,
     ThickArrow,
     Colon,
+    Question,
 
     // Literals.
     Identifier,
@@ -38,9 +41,11 @@ pub enum TokenType {
     // Keywords.
     And,
     Break,
+    Catch,
     Class,
     Else,
     False,
+    Finally,
     For,
     Fun,
     If,
@@ -53,7 +58,9 @@ pub enum TokenType {
     Return,
     Super,
     This,
+    Throw,
     True,
+    Try,
     Let,
     While,
     Role,
@@ -80,13 +87,20 @@ pub static INFIX_OPS: &[TokenType] = &[
 ];
 
 pub static PREFIX_OPS: &[TokenType] = &[TokenType::Minus, TokenType::Bang];
-pub static POSTFIX_OPS: &[TokenType] = &[TokenType::LeftParen];
+pub static POSTFIX_OPS: &[TokenType] = &[TokenType::LeftParen, TokenType::Question];
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+/// A token of the code, along with the trivia around it: the whitespace, comments and tokens the
+/// parser leaves out of the syntax tree (such as line breaks and the `;` ending a statement).
+/// Together, the trivia and lexemes of the tokens in the syntax tree make up the whole code.
+#[derive(Copy, Clone, PartialEq, Eq, Serialize)]
 pub struct Token<'src> {
     pub kind: TokenType,
     pub lexeme: &'src str,
     pub span: Span,
+    /// The trivia since the previous token.
+    pub leading: &'src str,
+    /// The whitespace and comment after the token on the same line.
+    pub trailing: &'src str,
 }
 
 impl<'src> Token<'src> {
@@ -98,6 +112,8 @@ impl<'src> Token<'src> {
                 start: Position::synthetic(),
                 end: Position::synthetic(),
             },
+            leading: "",
+            trailing: "",
         }
     }
 
@@ -109,6 +125,8 @@ impl<'src> Token<'src> {
                 start: Position::synthetic(),
                 end: Position::synthetic(),
             },
+            leading: "",
+            trailing: "",
         }
     }
 
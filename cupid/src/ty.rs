@@ -1,5 +1,7 @@
 use std::fmt;
 
+use serde::{Serialize, Serializer};
+
 use crate::{arena::EntryId, scope::symbol::ClassId};
 
 #[derive(PartialEq, Eq, Clone, Copy, Default, Debug)]
@@ -15,6 +17,13 @@ pub enum Type<'src> {
     Function {
         returns: EntryId,
     },
+    /// A some of the type, or none.
+    Option(EntryId),
+    /// An ok of the first type, or an err of the second.
+    Result {
+        ok: EntryId,
+        err: EntryId,
+    },
     #[default]
     Unknown,
     Unit,
@@ -36,3 +45,17 @@ impl fmt::Display for Type<'_> {
         }
     }
 }
+
+/// Serialized as the name of the type. Array, function, option and result types refer to the
+/// types in them by arena entry, which are left out.
+impl Serialize for Type<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Array(_) => serializer.serialize_str("Array"),
+            Self::Function { .. } => serializer.serialize_str("Function"),
+            Self::Option(_) => serializer.serialize_str("Option"),
+            Self::Result { .. } => serializer.serialize_str("Result"),
+            _ => serializer.collect_str(self),
+        }
+    }
+}
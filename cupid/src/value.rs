@@ -1,11 +1,13 @@
 use crate::{
-    gc::GcRef,
+    gc::{GcObject, GcRef},
     objects::{
-        Array, BoundMethod, Class, Closure, Function, Instance, NativeFunction, RoleImpl, Str,
+        Array, BoundMethod, Class, Closure, Foreign, Function, Instance, Native, NativeFunction,
+        RoleImpl, Str,
     },
     vm::Vm,
 };
-use std::{fmt, ops::Deref};
+use serde::{Serialize, Serializer};
+use std::{fmt, ops::Deref, ptr::NonNull};
 
 #[derive(Clone, Copy, PartialEq)]
 pub enum Value {
@@ -15,8 +17,10 @@ pub enum Value {
     Class(GcRef<Class>),
     RoleImpl(GcRef<RoleImpl>),
     Closure(GcRef<Closure>),
+    Foreign(GcRef<Foreign>),
     Function(GcRef<Function>),
     Instance(GcRef<Instance>),
+    Native(GcRef<Native>),
     NativeFunction(NativeFunction),
     Nil,
     Float(f64),
@@ -25,6 +29,27 @@ pub enum Value {
 }
 
 impl Value {
+    /// The heap object this value points at, if any
+    pub fn object(&self) -> Option<NonNull<GcObject>> {
+        match self {
+            Value::Array(value) => Some(value.object()),
+            Value::BoundMethod(value) => Some(value.object()),
+            Value::Class(value) => Some(value.object()),
+            Value::RoleImpl(value) => Some(value.object()),
+            Value::Closure(value) => Some(value.object()),
+            Value::Foreign(value) => Some(value.object()),
+            Value::Function(value) => Some(value.object()),
+            Value::Instance(value) => Some(value.object()),
+            Value::Native(value) => Some(value.object()),
+            Value::String(value) => Some(value.object()),
+            Value::Bool(_)
+            | Value::NativeFunction(_)
+            | Value::Nil
+            | Value::Float(_)
+            | Value::Int(_) => None,
+        }
+    }
+
     pub fn is_falsey(&self) -> bool {
         match self {
             Value::Nil => true,
@@ -106,6 +131,20 @@ impl fmt::Debug for Value {
     }
 }
 
+/// Constants are serialized as the JSON values they stand for, and any other value as it prints.
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Bool(value) => serializer.serialize_bool(*value),
+            Value::Int(value) => serializer.serialize_i32(*value),
+            Value::Float(value) => serializer.serialize_f64(*value),
+            Value::Nil => serializer.serialize_unit(),
+            Value::String(value) => serializer.serialize_str(&value.s),
+            _ => serializer.collect_str(self),
+        }
+    }
+}
+
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -114,8 +153,10 @@ impl fmt::Display for Value {
             Value::BoundMethod(value) => write!(f, "{}", value.method.function.deref()),
             Value::Class(value) => write!(f, "{}", value.name.deref()),
             Value::Closure(value) => write!(f, "{}", value.function.deref()),
+            Value::Foreign(value) => write!(f, "{}", value.deref()),
             Value::Function(value) => write!(f, "{}", value.name.deref()),
             Value::Instance(value) => write!(f, "{} instance", value.class.name.deref()),
+            Value::Native(value) => write!(f, "{}", value.deref()),
             Value::NativeFunction(_) => write!(f, "<native fun>"),
             Value::Nil => write!(f, "none"),
             Value::Float(value) => write!(f, "{value}"),
@@ -1,60 +1,291 @@
 use crate::{
     // chunk::Instruction,
+    code::Code,
+    diagnostic::{Diagnostic, ErrorFormat},
     error::CupidErr,
     expose,
-    gc::{Gc, GcRef},
-    objects::{BoundMethod, Class, Closure, Function, Instance, NativeFunction, Str, Upvalue},
+    gc::{Collection, Gc, GcConfig, GcObject, GcRef, HeapSize},
+    objects::{
+        Array, BoundMethod, Class, Closure, Foreign, Function, Instance, Native, NativeFn,
+        NativeFunction, Str, Upvalue,
+    },
+    suggest::did_you_mean,
     table::Table,
     value::Value,
 };
-use std::{fmt::Display, ops::Deref, time::SystemTime};
+use std::{
+    cell::RefCell,
+    env,
+    fmt::Display,
+    io::{BufRead, Write},
+    ops::Deref,
+    ptr::NonNull,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
 pub mod frame;
 pub use self::frame::*;
 
+pub mod io;
+pub use self::io::*;
+
 pub mod runtime;
 pub use self::runtime::*;
 
 pub mod stack;
 pub use self::stack::*;
 
-#[derive(Debug, Clone)]
+/// Settings for a [`Vm`], read from the command line or the environment (see [`GcConfig`] for the
+/// collector's settings):
+///
+/// | Setting          | Flag                 | Environment            | Default |
+/// | ---------------- | -------------------- | ---------------------- | ------- |
+/// | `max_call_depth` | `--max-call-depth=`  | `CUPID_MAX_CALL_DEPTH` | `1024`  |
+/// | `fuel`           | `--fuel=`            | `CUPID_FUEL`           | none    |
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct VmConfig {
+    pub gc: GcConfig,
+    /// Calls nested deeper than this are reported as a stack overflow
+    pub max_call_depth: usize,
+    /// The number of instructions a run may take before it is stopped
+    pub fuel: Option<u64>,
+    pub error_format: ErrorFormat,
+}
+
+impl Default for VmConfig {
+    fn default() -> Self {
+        Self {
+            gc: GcConfig::default(),
+            max_call_depth: Frames::DEFAULT_MAX_DEPTH,
+            fuel: None,
+            error_format: ErrorFormat::default(),
+        }
+    }
+}
+
+impl VmConfig {
+    pub const MAX_CALL_DEPTH_VAR: &'static str = "CUPID_MAX_CALL_DEPTH";
+    pub const FUEL_VAR: &'static str = "CUPID_FUEL";
+
+    /// Reads the defaults, overridden by any `CUPID_*` environment variables.
+    pub fn from_env() -> Result<Self, String> {
+        let mut config = Self {
+            gc: GcConfig::from_env()?,
+            ..Self::default()
+        };
+        if let Ok(value) = env::var(Self::MAX_CALL_DEPTH_VAR) {
+            config.set_max_call_depth(&value)?;
+        }
+        if let Ok(value) = env::var(Self::FUEL_VAR) {
+            config.set_fuel(&value)?;
+        }
+        if let Ok(value) = env::var(ErrorFormat::VAR) {
+            config.error_format = ErrorFormat::from_name(&value)?;
+        }
+        Ok(config)
+    }
+
+    pub fn set_max_call_depth(&mut self, value: &str) -> Result<(), String> {
+        match value.trim().parse::<usize>() {
+            Ok(depth) if depth > 0 => {
+                self.max_call_depth = depth;
+                Ok(())
+            }
+            _ => Err(format!("Invalid call depth '{value}': expected a positive number.")),
+        }
+    }
+
+    pub fn set_fuel(&mut self, value: &str) -> Result<(), String> {
+        match value.trim().parse::<u64>() {
+            Ok(fuel) => {
+                self.fuel = Some(fuel);
+                Ok(())
+            }
+            _ => Err(format!("Invalid fuel '{value}': expected a number of instructions.")),
+        }
+    }
+}
+
+/// A flag that stops the vm at its next call or loop iteration with a runtime error. Clones share
+/// the flag, so it can be set from another thread or from a signal handler.
+#[derive(Debug, Clone, Default)]
+pub struct Interrupt(Arc<AtomicBool>);
+
+impl Interrupt {
+    pub fn interrupt(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Whether the flag was set, clearing it so that the vm only stops once per interruption.
+    fn take(&self) -> bool {
+        self.0.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// Where `clock` reads the time from.
+#[derive(Debug, Copy, Clone)]
+pub enum Clock {
+    /// The seconds since the vm was created.
+    #[cfg(not(target_arch = "wasm32"))]
+    System(std::time::SystemTime),
+    /// The seconds given by the host, on targets like `wasm32-unknown-unknown` that have no clock
+    /// of their own.
+    Host(fn() -> f64),
+}
+
+impl Default for Clock {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn default() -> Self {
+        Clock::System(std::time::SystemTime::now())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn default() -> Self {
+        Clock::Host(|| 0.0)
+    }
+}
+
+impl Clock {
+    pub fn seconds(&self) -> f64 {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            Clock::System(start) => start.elapsed().unwrap_or_default().as_secs_f64(),
+            Clock::Host(now) => now(),
+        }
+    }
+}
+
+/// An error on its way to the try body that catches it.
+#[derive(Debug)]
+pub enum Thrown {
+    /// A value thrown by `throw`.
+    Value(Value),
+    /// A runtime error, which becomes an `Error` instance once it is caught.
+    Error {
+        code: Code,
+        message: String,
+        trace: Vec<String>,
+    },
+}
+
+#[derive(Debug)]
 pub struct Vm {
     pub gc: Gc,
     pub frames: Frames,
     pub stack: Stack<Value>,
     pub globals: Table,
     pub open_upvalues: Vec<GcRef<Upvalue>>,
+    /// The classes of the host's foreign objects, kept alive for as long as the vm.
+    pub foreign_classes: Vec<GcRef<Class>>,
     pub init_string: GcRef<Str>,
-    pub start_time: SystemTime,
+    /// The class of the errors that runtime errors are caught as, `Error` in the code.
+    pub error_class: GcRef<Class>,
+    /// The classes of the values made by `some`, `ok` and `err`, which keep what they wrap in
+    /// their `value` field. `none` is nil.
+    pub some_class: GcRef<Class>,
+    pub ok_class: GcRef<Class>,
+    pub err_class: GcRef<Class>,
+    /// What is being thrown, until a try body catches it.
+    pub thrown: RefCell<Option<Thrown>>,
+    pub clock: Clock,
+    /// The instructions left to run, if they are limited. Once they run out, every call and loop
+    /// iteration is an error until the host adds more.
+    pub fuel: Option<u64>,
+    pub interrupt: Interrupt,
+    pub error_format: ErrorFormat,
+    /// The file being run, named by the diagnostics of runtime errors.
+    pub path: Option<String>,
+    /// Borrowed by whatever writes or reads, including builtins that only get a shared vm.
+    pub streams: RefCell<Streams>,
+    /// When set, runtime errors are collected here instead of being printed.
+    pub diagnostics: Option<RefCell<Vec<Diagnostic>>>,
 }
 
 impl Default for Vm {
     fn default() -> Self {
-        let mut gc = Gc::default();
+        Self::with_config(VmConfig::default())
+    }
+}
+
+impl Vm {
+    pub fn with_config(config: VmConfig) -> Self {
+        let mut gc = Gc::new(config.gc);
         let init_string = gc.intern("init".to_owned());
+        let error_name = gc.intern("Error".to_owned());
+        let error_class = gc.alloc(Class::new(error_name));
+        let some_name = gc.intern("Some".to_owned());
+        let some_class = gc.alloc(Class::new(some_name));
+        let ok_name = gc.intern("Ok".to_owned());
+        let ok_class = gc.alloc(Class::new(ok_name));
+        let err_name = gc.intern("Err".to_owned());
+        let err_class = gc.alloc(Class::new(err_name));
 
         Self {
             gc,
-            frames: Frames::default(),
+            frames: Frames::new(config.max_call_depth),
             stack: Stack::default(),
             globals: Table::default(),
-            open_upvalues: Vec::with_capacity(stack::SIZE),
+            open_upvalues: Vec::new(),
+            foreign_classes: Vec::new(),
             init_string,
-            start_time: SystemTime::now(),
+            error_class,
+            some_class,
+            ok_class,
+            err_class,
+            thrown: RefCell::default(),
+            clock: Clock::default(),
+            fuel: config.fuel,
+            interrupt: Interrupt::default(),
+            error_format: config.error_format,
+            path: None,
+            streams: RefCell::default(),
+            diagnostics: None,
         }
     }
-}
 
-impl Vm {
+    /// Sends what `log` prints to `stdout`, such as a [`Buffer`] the host reads afterwards.
+    pub fn set_stdout(&mut self, stdout: impl Write + 'static) {
+        self.streams.get_mut().stdout = Box::new(stdout);
+    }
+
+    /// Sends the reports of runtime errors to `stderr`, unless they are collected as diagnostics.
+    pub fn set_stderr(&mut self, stderr: impl Write + 'static) {
+        self.streams.get_mut().stderr = Box::new(stderr);
+    }
+
+    /// Makes `read_line` read from `stdin`.
+    pub fn set_stdin(&mut self, stdin: impl BufRead + 'static) {
+        self.streams.get_mut().stdin = Box::new(stdin);
+    }
+
     pub fn initialize(&mut self) {
         self.define_native("clock", NativeFunction(expose::cupid_clock));
-        self.define_native("panic", NativeFunction(expose::cupid_panic));
-        self.define_native("push", NativeFunction(expose::cupid_push));
-        self.define_native("pop", NativeFunction(expose::cupid_pop));
-        self.define_native("len", NativeFunction(expose::cupid_len));
-        self.define_native("get", NativeFunction(expose::cupid_get));
-        self.stack.top = self.stack.stack.as_mut_ptr();
+        self.register_native("panic", 1, expose::cupid_panic);
+        self.register_native("push", 2, expose::cupid_push);
+        self.register_native("pop", 1, expose::cupid_pop);
+        self.register_native("len", 1, expose::cupid_len);
+        self.register_native("get", 2, expose::cupid_get);
+        self.register_native("read_line", 0, expose::cupid_read_line);
+        self.register_native("assert", 1, expose::cupid_assert);
+        self.register_native("assert_eq", 2, expose::cupid_assert_eq);
+        self.register_native("assert_err", 1, expose::cupid_assert_err);
+        self.register_native("map", 2, expose::cupid_map);
+        self.register_native("filter", 2, expose::cupid_filter);
+        self.register_native("sort_by", 2, expose::cupid_sort_by);
+        self.register_native("some", 1, expose::cupid_some);
+        self.register_native("ok", 1, expose::cupid_ok);
+        self.register_native("err", 1, expose::cupid_err);
+
+        let error_class = self.error_class;
+        self.globals.set(error_class.name, Value::Class(error_class));
+        self.register_method(error_class, "init", 1, expose::cupid_error_init);
     }
 
     pub fn interpret_function(&mut self, function: GcRef<Function>) -> Result<(), CupidErr> {
@@ -69,28 +300,106 @@ impl Vm {
         self.globals.set(name, Value::NativeFunction(native));
     }
 
+    /// Defines the global `name` as a native taking `arity` arguments, which may capture state.
+    pub fn register_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&mut Vm, &[Value]) -> Result<Value, CupidErr> + 'static,
+    ) {
+        let native = self.alloc_native(name, arity, Box::new(function));
+        self.globals.set(native.name, Value::Native(native));
+    }
+
+    /// A class for foreign objects, without any methods yet.
+    pub fn foreign_class(&mut self, name: &str) -> GcRef<Class> {
+        let name = self.intern(name.to_owned());
+        self.stack.push(Value::String(name));
+        let class = self.alloc(Class::new(name));
+        self.stack.pop();
+        self.foreign_classes.push(class);
+        class
+    }
+
+    /// Adds a method to a class of foreign objects. The native is given the receiver before the
+    /// `arity` arguments.
+    pub fn register_method(
+        &mut self,
+        mut class: GcRef<Class>,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&mut Vm, &[Value]) -> Result<Value, CupidErr> + 'static,
+    ) {
+        let native = self.alloc_native(name, arity, Box::new(function));
+        class.methods.set(native.name, Value::Native(native));
+        self.gc.write_barrier(class);
+        self.gc.resize(class);
+    }
+
+    /// Moves a foreign object into the heap.
+    pub fn foreign(&mut self, foreign: Foreign) -> Value {
+        Value::Foreign(self.alloc(foreign))
+    }
+
+    fn alloc_native(&mut self, name: &str, arity: usize, function: Box<NativeFn>) -> GcRef<Native> {
+        let name = self.intern(name.to_owned());
+        // Keeps the name alive while the native is allocated
+        self.stack.push(Value::String(name));
+        let native = self.alloc(Native::new(name, arity, function));
+        self.stack.pop();
+        native
+    }
+
+    /// Calls a native with the `arity` values on top of the stack, and the receiver below them for
+    /// a method, replacing them and the callee or receiver with its result.
+    fn call_native(
+        &mut self,
+        native: GcRef<Native>,
+        arg_count: usize,
+        receiver: bool,
+    ) -> Result<(), CupidErr> {
+        if arg_count != native.arity {
+            let msg = format!("Expected {} arguments but got {}.", native.arity, arg_count);
+            return self.runtime_err(Code::ArityMismatch, msg);
+        }
+        let callee = self.stack.len() - arg_count - 1;
+        let first = if receiver { callee } else { callee + 1 };
+        // Copied since the native may call back into the vm, while the originals stay on the stack
+        // to keep them alive
+        let args = self.stack.stack[first..].to_vec();
+        let result = (native.function)(self, &args)?;
+        self.stack.truncate(callee);
+        self.stack.push(result);
+        Ok(())
+    }
+
     pub fn call_value(&mut self, arg_count: usize) -> Result<(), CupidErr> {
         let callee = self.stack.peek(arg_count);
         match callee {
             Value::BoundMethod(bound) => {
                 self.stack.set_at(arg_count, bound.receiver);
-                self.call(bound.method, arg_count)
+                self.call_closure(bound.method, arg_count)
             }
             Value::Class(class) => {
                 let instance = self.alloc(Instance::new(class));
                 self.stack.set_at(arg_count, Value::Instance(instance));
                 if let Some(initializer) = class.methods.get(self.init_string) {
-                    if let Value::Closure(initializer) = initializer {
-                        return self.call(initializer, arg_count);
-                    }
-                    return self.runtime_err("Initializer is not closure");
+                    return match initializer {
+                        Value::Closure(initializer) => self.call_closure(initializer, arg_count),
+                        // Given the instance, which it returns
+                        Value::Native(initializer) => {
+                            self.call_native(initializer, arg_count, true)
+                        }
+                        _ => self.runtime_err(Code::Internal, "Initializer is not closure"),
+                    };
                 } else if arg_count != 0 {
                     let msg = format!("Expected 0 arguments but got {}.", arg_count);
-                    return self.runtime_err(msg);
+                    return self.runtime_err(Code::ArityMismatch, msg);
                 }
                 Ok(())
             }
-            Value::Closure(closure) => self.call(closure, arg_count),
+            Value::Closure(closure) => self.call_closure(closure, arg_count),
+            Value::Native(native) => self.call_native(native, arg_count, false),
             Value::NativeFunction(native) => {
                 let left = self.stack.len() - arg_count;
                 let result = native.0(self, &self.stack.stack[left..]);
@@ -99,19 +408,66 @@ impl Vm {
                 Ok(())
             }
             Value::Function(fun) if &fun.name.s == "script" => Ok(()),
-            _ => self.runtime_err("Can only call functions and classes."),
+            _ => self.runtime_err(Code::NotCallable, "Can only call functions and classes."),
+        }
+    }
+
+    /// Calls `callee` with `args` and runs it to completion, from a native or the host. The call
+    /// runs in a dispatch loop of its own, on top of whatever frames are already running, so the
+    /// stack trace of an error in it goes on through the code that called the native. After an
+    /// error, which has already been reported, the frames and values of the call are discarded.
+    pub fn call(&mut self, callee: Value, args: &[Value]) -> Result<Value, CupidErr> {
+        let (base, depth) = (self.stack.len(), self.frames.count());
+        // The arguments are kept alive on the stack for the length of the call
+        self.stack.push(callee);
+        for &arg in args {
+            self.stack.push(arg);
         }
+        let result = self.checkpoint(1).and_then(|()| self.call_value(args.len())).and_then(|()| {
+            // Natives and classes without an initializer return without pushing a frame
+            match self.frames.count() > depth {
+                true => self.run_until(depth),
+                false => Ok(()),
+            }
+        });
+        let value = result.map(|()| self.stack.peek(0));
+        self.frames.frames.truncate(depth);
+        self.close_upvalues(base);
+        self.stack.truncate(base);
+        value
     }
 
-    pub fn call(&mut self, closure: GcRef<Closure>, arg_count: usize) -> Result<(), CupidErr> {
+    /// Calls `callee` like [`Vm::call`], but catches what the call throws: a runtime error or a
+    /// thrown value comes back as the [`Thrown`] instead of being reported. Errors that can't be
+    /// caught, like running out of fuel, are still returned.
+    pub fn try_call(
+        &mut self,
+        callee: Value,
+        args: &[Value],
+    ) -> Result<Result<Value, Thrown>, CupidErr> {
+        self.frames.try_calls += 1;
+        let result = self.call(callee, args);
+        self.frames.try_calls -= 1;
+        match (result, self.thrown.get_mut().take()) {
+            (Ok(value), _) => Ok(Ok(value)),
+            (Err(_), Some(thrown)) => Ok(Err(thrown)),
+            (Err(error), None) => Err(error),
+        }
+    }
+
+    pub fn call_closure(
+        &mut self,
+        closure: GcRef<Closure>,
+        arg_count: usize,
+    ) -> Result<(), CupidErr> {
         let function = closure.function;
         if arg_count != function.arity {
-            self.runtime_err(format!(
-                "Expected {} arguments but got {}.",
-                function.arity, arg_count
-            ))
-        } else if self.frames.count == Frames::MAX {
-            self.runtime_err("Stack overflow.")
+            self.runtime_err(
+                Code::ArityMismatch,
+                format!("Expected {} arguments but got {}.", function.arity, arg_count),
+            )
+        } else if self.frames.is_full() {
+            self.runtime_err(Code::StackOverflow, "Stack overflow.")
         } else {
             let frame = CallFrame::new(closure, self.stack.len() - arg_count - 1);
             self.frames.increment(frame);
@@ -129,8 +485,16 @@ impl Vm {
                 let class = instance.class;
                 self.invoke_from_class(class, name, arg_count)
             }
+        } else if let Value::Foreign(foreign) = receiver {
+            match foreign.class.methods.get(name) {
+                Some(Value::Native(method)) => self.call_native(method, arg_count, true),
+                _ => self.undefined_property(receiver, foreign.class, name),
+            }
         } else {
-            self.runtime_err(&format!("Only instances have methods, not {}", name.deref()))
+            self.runtime_err(
+                Code::NotAnInstance,
+                &format!("Only instances have methods, not {}", name.deref()),
+            )
         }
     }
 
@@ -141,13 +505,13 @@ impl Vm {
         arg_count: usize,
     ) -> Result<(), CupidErr> {
         if let Some(method) = class.methods.get(name) {
-            if let Value::Closure(closure) = method {
-                self.call(closure, arg_count)
-            } else {
-                panic!("Got method that is not closure!")
+            match method {
+                Value::Closure(closure) => self.call_closure(closure, arg_count),
+                Value::Native(native) => self.call_native(native, arg_count, true),
+                _ => panic!("Got method that is not closure!"),
             }
         } else {
-            self.runtime_err(format!("Undefined property '{}'.", name.deref()))
+            self.undefined_property(self.stack.peek(arg_count), class, name)
         }
     }
 
@@ -163,10 +527,144 @@ impl Vm {
             self.stack.push(Value::BoundMethod(bound));
             Ok(())
         } else {
-            self.runtime_err(format!("Undefined property '{}'.", name.deref()))
+            self.undefined_property(self.stack.peek(0), class, name)
+        }
+    }
+
+    /// Reports an undefined global, suggesting a defined one with a close name.
+    pub(crate) fn undefined_variable(&self, name: GcRef<Str>) -> Result<(), CupidErr> {
+        let globals: Vec<GcRef<Str>> = self.globals.iter().map(|(global, _)| global).collect();
+        let help = did_you_mean(&name.s, globals.iter().map(|global| global.s.as_str()));
+        self.runtime_err_with_help(
+            Code::UndefinedVariable,
+            format!("Undefined variable '{}'.", name.deref()),
+            help,
+        )
+    }
+
+    /// Reports an undefined property, suggesting a field of `receiver`, or a method or declared
+    /// field of `class` (which holds the ones it inherits too), with a close name.
+    fn undefined_property(
+        &self,
+        receiver: Value,
+        class: GcRef<Class>,
+        name: GcRef<Str>,
+    ) -> Result<(), CupidErr> {
+        let mut members: Vec<GcRef<Str>> = class.methods.iter().map(|(method, _)| method).collect();
+        members.extend(class.fields.iter().map(|(field, _)| field));
+        if let Value::Instance(instance) = receiver {
+            members.extend(instance.fields.iter().map(|(field, _)| field));
+        }
+        let help = did_you_mean(&name.s, members.iter().map(|member| member.s.as_str()));
+        self.runtime_err_with_help(
+            Code::UndefinedProperty,
+            format!("Undefined property '{}'.", name.deref()),
+            help,
+        )
+    }
+
+    /// Throws `value`, to be caught by the innermost try body, or reports it when there is none.
+    pub fn throw(&mut self, value: Value) -> CupidErr {
+        if self.frames.catches() {
+            *self.thrown.get_mut() = Some(Thrown::Value(value));
+            return CupidErr::RuntimeError;
+        }
+        let message = match value {
+            Value::Instance(instance) => instance
+                .fields
+                .iter()
+                .find_map(|(name, field)| (name.s == "message").then(|| Self::describe(field))),
+            _ => None,
+        };
+        let message = message.unwrap_or_else(|| Self::describe(value));
+        self.as_runtime_err(Code::Uncaught, format!("Uncaught error: {message}"))
+    }
+
+    /// A value as a message: strings without their quotes.
+    fn describe(value: Value) -> String {
+        match value {
+            Value::String(string) => string.s.clone(),
+            value => value.to_string(),
         }
     }
 
+    /// The value of the error being thrown, as the catch body gets it.
+    pub(crate) fn caught(&mut self) -> Value {
+        match self.thrown.get_mut().take() {
+            Some(Thrown::Value(value)) => value,
+            Some(Thrown::Error {
+                code,
+                message,
+                trace,
+            }) => {
+                let instance = self.alloc(Instance::new(self.error_class));
+                self.stack.push(Value::Instance(instance));
+                let message = Value::String(self.intern(message));
+                self.init_error(instance, message, Some(code), &trace);
+                self.stack.pop()
+            }
+            None => Value::Nil,
+        }
+    }
+
+    /// Sets the `message`, `code` and `trace` fields of an error instance, which has to be on the
+    /// stack. The message is any value, kept alive by the instance once it is set.
+    pub(crate) fn init_error(
+        &mut self,
+        instance: GcRef<Instance>,
+        message: Value,
+        code: Option<Code>,
+        trace: &[String],
+    ) {
+        self.set_field(instance, "message", message);
+        let code = match code {
+            Some(code) => Value::String(self.intern(code.id().to_owned())),
+            None => Value::Nil,
+        };
+        self.set_field(instance, "code", code);
+        // The lines stay on the stack until the array is allocated
+        let first = self.stack.len();
+        for line in trace {
+            let line = self.intern(line.clone());
+            self.stack.push(Value::String(line));
+        }
+        let lines = self.stack.stack[first..].to_vec();
+        let trace = self.alloc(Array::new(lines));
+        self.stack.truncate(first);
+        self.set_field(instance, "trace", Value::Array(trace));
+    }
+
+    /// A some, ok or err wrapping `value`, depending on `class`.
+    pub(crate) fn wrap(&mut self, class: GcRef<Class>, value: Value) -> Value {
+        let instance = self.alloc(Instance::new(class));
+        self.stack.push(Value::Instance(instance));
+        self.set_field(instance, "value", value);
+        self.stack.pop()
+    }
+
+    /// What a some or ok wraps, or `None` for any other value.
+    pub(crate) fn unwrap(&self, value: Value) -> Option<Value> {
+        match value {
+            Value::Instance(instance)
+                if instance.class == self.some_class || instance.class == self.ok_class =>
+            {
+                let field = instance.fields.iter().find(|(name, _)| name.s == "value");
+                Some(field.map_or(Value::Nil, |(_, field)| field))
+            }
+            _ => None,
+        }
+    }
+
+    fn set_field(&mut self, mut instance: GcRef<Instance>, name: &str, value: Value) {
+        // Kept alive while the name is interned
+        self.stack.push(value);
+        let name = self.intern(name.to_owned());
+        instance.fields.set(name, value);
+        self.gc.write_barrier(instance);
+        self.gc.resize(instance);
+        self.stack.pop();
+    }
+
     pub fn capture_upvalue(&mut self, location: usize) -> GcRef<Upvalue> {
         for &upvalue in &self.open_upvalues {
             if upvalue.location == location {
@@ -188,6 +686,7 @@ impl Vm {
                 self.open_upvalues.remove(i);
                 let location = upvalue.location;
                 upvalue.closed = Some(self.stack.stack[location]);
+                self.gc.write_barrier(upvalue);
             } else {
                 i += 1;
             }
@@ -199,10 +698,14 @@ impl Vm {
         match self.stack.peek(1) {
             Value::Class(mut class) => {
                 class.methods.set(name, method);
+                self.gc.write_barrier(class);
+                self.gc.resize(class);
                 self.stack.pop();
             }
             Value::RoleImpl(mut role) => {
                 role.class.methods.set(name, method);
+                self.gc.write_barrier(role.class);
+                self.gc.resize(role.class);
                 self.stack.pop();
             }
             _ => panic!(
@@ -213,7 +716,10 @@ impl Vm {
         }
     }
 
-    pub fn alloc<T: Display + std::fmt::Debug + 'static>(&mut self, object: T) -> GcRef<T> {
+    pub fn alloc<T: Display + std::fmt::Debug + HeapSize + 'static>(
+        &mut self,
+        object: T,
+    ) -> GcRef<T> {
         self.mark_and_sweep();
         self.gc.alloc(object)
     }
@@ -224,10 +730,69 @@ impl Vm {
     }
 
     pub fn mark_and_sweep(&mut self) {
-        if self.gc.should_gc() {
-            self.mark_roots();
-            self.gc.collect_garbage();
+        if let Some(collection) = self.gc.should_gc() {
+            self.collect(collection);
+        }
+    }
+
+    fn collect(&mut self, collection: Collection) {
+        self.gc.begin_collection(collection);
+        self.mark_roots();
+        self.gc.collect_garbage();
+        if self.gc.config().verifies() {
+            self.gc.verify_heap(&self.roots());
+        }
+    }
+
+    /// Spends `spent` fuel, at a call or at the end of a loop iteration, and stops the run if it
+    /// has run out of fuel, was interrupted, or still has a heap over its limit after a major
+    /// collection. These are checked here rather than after every instruction, as any run that
+    /// goes on for long must call or loop. The heap can go over its limit in between, by as much
+    /// as the code allocates without calling or looping.
+    pub(crate) fn checkpoint(&mut self, spent: u64) -> Result<(), CupidErr> {
+        if let Some(fuel) = &mut self.fuel {
+            if *fuel < spent {
+                *fuel = 0;
+                return self.runtime_err(Code::OutOfFuel, "Ran out of fuel.");
+            }
+            *fuel -= spent;
+        }
+        if self.interrupt.take() {
+            return self.runtime_err(Code::Interrupted, "Interrupted.");
+        }
+        if self.gc.over_limit() {
+            self.collect(Collection::Major);
+            if self.gc.check_limit() {
+                let max = self.gc.config().max_heap.unwrap_or_default();
+                return self.runtime_err(
+                    Code::HeapLimit,
+                    format!("Exceeded the maximum heap size of {max} bytes."),
+                );
+            }
         }
+        Ok(())
+    }
+
+    /// Every object directly reachable from the vm, for verifying the heap
+    fn roots(&self) -> Vec<NonNull<GcObject>> {
+        let mut roots: Vec<NonNull<GcObject>> =
+            self.stack.stack[0..self.stack.len()].iter().filter_map(Value::object).collect();
+        roots.extend(self.frames.frames.iter().map(|frame| frame.closure.object()));
+        roots.extend(self.open_upvalues.iter().map(|upvalue| upvalue.object()));
+        roots.extend(self.foreign_classes.iter().map(|class| class.object()));
+        for (k, v) in self.globals.iter() {
+            roots.push(k.object());
+            roots.extend(v.object());
+        }
+        if let Some(Thrown::Value(value)) = &*self.thrown.borrow() {
+            roots.extend(value.object());
+        }
+        roots.push(self.init_string.object());
+        roots.push(self.error_class.object());
+        roots.push(self.some_class.object());
+        roots.push(self.ok_class.object());
+        roots.push(self.err_class.object());
+        roots
     }
 
     pub fn mark_roots(&mut self) {
@@ -235,7 +800,7 @@ impl Vm {
             self.gc.mark_value(value);
         }
 
-        for frame in &self.frames.frames[..self.frames.count] {
+        for frame in &self.frames.frames {
             self.gc.mark_object(frame.closure)
         }
 
@@ -243,7 +808,19 @@ impl Vm {
             self.gc.mark_object(upvalue);
         }
 
+        for &class in &self.foreign_classes {
+            self.gc.mark_object(class);
+        }
+
+        if let Some(Thrown::Value(value)) = *self.thrown.get_mut() {
+            self.gc.mark_value(value);
+        }
+
         self.gc.mark_table(&self.globals);
         self.gc.mark_object(self.init_string);
+        self.gc.mark_object(self.error_class);
+        self.gc.mark_object(self.some_class);
+        self.gc.mark_object(self.ok_class);
+        self.gc.mark_object(self.err_class);
     }
 }
@@ -1,5 +1,3 @@
-use std::ptr::null;
-
 pub struct FrameState<'frame, 'chunk> {
     pub frame: &'frame mut CallFrame,
     pub chunk: &'chunk Chunk,
@@ -26,34 +24,49 @@ use crate::{
     objects::Closure,
 };
 
+/// The call stack. Pushing a frame may move the others, so any [`FrameState`] must be re-acquired
+/// after a call or return.
 #[derive(Debug, Clone)]
 pub struct Frames {
-    pub frames: [CallFrame; Frames::MAX],
-    pub count: usize,
+    pub frames: Vec<CallFrame>,
+    pub max_depth: usize,
+    /// The calls made through [`Vm::try_call`](super::Vm::try_call) still running, which catch
+    /// errors like a try body does.
+    pub try_calls: usize,
 }
 
 impl Default for Frames {
     fn default() -> Self {
-        Self {
-            frames: [CallFrame {
-                closure: GcRef::dangling(),
-                ip: null(),
-                slot: 0,
-            }; Frames::MAX],
-            count: 0,
-        }
+        Self::new(Frames::DEFAULT_MAX_DEPTH)
     }
 }
 
 impl Frames {
-    pub const MAX: usize = 64;
+    pub const DEFAULT_MAX_DEPTH: usize = 1024;
+
+    pub fn new(max_depth: usize) -> Self {
+        Self {
+            frames: Vec::with_capacity(max_depth.min(64)),
+            max_depth,
+            try_calls: 0,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.count() >= self.max_depth
+    }
 
     pub fn frame(&self) -> &CallFrame {
-        &self.frames[self.count - 1]
+        self.frames.last().expect("No call frame")
     }
 
     pub fn current_frame<'frame>(&mut self) -> &'frame mut CallFrame {
-        unsafe { &mut *(&mut self.frames[self.count - 1] as *mut CallFrame) }
+        let frame = self.frames.last_mut().expect("No call frame");
+        unsafe { &mut *(frame as *mut CallFrame) }
     }
 
     pub fn current_chunk<'chunk>(&mut self) -> &'chunk Chunk {
@@ -67,17 +80,65 @@ impl Frames {
         }
     }
 
+    /// Whether any frame is running a try body, or a try call is running, which would catch an
+    /// error thrown now.
+    pub fn catches(&self) -> bool {
+        self.try_calls > 0 || self.frames.iter().any(|frame| !frame.handlers.is_empty())
+    }
+
     pub fn increment(&mut self, next: CallFrame) {
-        self.frames[self.count] = next;
-        self.count += 1;
+        self.frames.push(next);
+    }
+
+    pub fn decrement(&mut self) {
+        self.frames.pop();
+    }
+
+    /// The line and function name of every frame, innermost first, along with how many times in a
+    /// row they repeat: runs of identical frames (from direct recursion) are collapsed into one.
+    pub fn calls(&self) -> Vec<(usize, String, usize)> {
+        let mut calls: Vec<(usize, String, usize)> = vec![];
+        for frame in self.frames.iter().rev() {
+            let (line, name) = (frame.line(), frame.name());
+            match calls.last_mut() {
+                Some((last_line, last_name, count)) if *last_line == line && *last_name == name => {
+                    *count += 1
+                }
+                _ => calls.push((line, name, 1)),
+            }
+        }
+        calls
+    }
+
+    /// One line per frame, innermost first, e.g. `[line 3] in fib()`, with runs of identical lines
+    /// collapsed into one.
+    pub fn trace(&self) -> Vec<String> {
+        self.calls()
+            .into_iter()
+            .map(|(line, name, count)| match count {
+                1 => format!("[line {line}] in {name}"),
+                _ => format!("[line {line}] in {name} (repeated {count} times)"),
+            })
+            .collect()
     }
 }
 
+/// Where an error thrown in a try body goes: the start of its catch body, with the stack cut back
+/// to the locals around the try.
 #[derive(Clone, Copy, Debug)]
+pub struct Handler {
+    pub catch: *const Instruction,
+    pub stack_len: usize,
+}
+
+#[derive(Clone, Debug)]
 pub struct CallFrame {
     pub closure: GcRef<Closure>,
     pub ip: *const Instruction,
     pub slot: usize,
+    /// The try bodies running in the frame, innermost last. A `return` or `break` out of one pops
+    /// its handler before it leaves.
+    pub handlers: Vec<Handler>,
 }
 
 impl CallFrame {
@@ -86,6 +147,7 @@ impl CallFrame {
             closure,
             ip: closure.function.chunk.code.as_ptr(),
             slot,
+            handlers: Vec::new(),
         }
     }
 
@@ -100,4 +162,11 @@ impl CallFrame {
     pub fn line(&self) -> usize {
         self.closure.function.chunk.lines[self.offset() - 1]
     }
+
+    pub fn name(&self) -> String {
+        match &*self.closure.function.name.s {
+            "script" => "script".to_owned(),
+            name => format!("{name}()"),
+        }
+    }
 }
@@ -0,0 +1,79 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    io::{self, BufRead, BufReader, Write},
+    rc::Rc,
+};
+
+/// Where the vm writes what `log` prints and the runtime errors it reports, and where `read_line`
+/// reads from. They are the process's own streams unless the host replaces them.
+pub struct Streams {
+    pub stdout: Box<dyn Write>,
+    pub stderr: Box<dyn Write>,
+    pub stdin: Box<dyn BufRead>,
+}
+
+impl Default for Streams {
+    fn default() -> Self {
+        Self {
+            stdout: Box::new(io::stdout()),
+            stderr: Box::new(io::stderr()),
+            stdin: Box::new(BufReader::new(io::stdin())),
+        }
+    }
+}
+
+impl fmt::Debug for Streams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Streams").finish_non_exhaustive()
+    }
+}
+
+/// An in-memory stream that the host keeps a clone of, to read what the vm wrote to it.
+#[derive(Debug, Clone, Default)]
+pub struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+impl Buffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything written so far.
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+
+    /// Everything written so far, emptying the buffer.
+    pub fn take(&self) -> String {
+        let bytes = self.0.take();
+        String::from_utf8(bytes)
+            .unwrap_or_else(|error| String::from_utf8_lossy(error.as_bytes()).into_owned())
+    }
+}
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Buffer;
+    use std::io::Write;
+
+    #[test]
+    fn clones_share_their_contents() {
+        let buffer = Buffer::new();
+        let mut writer = buffer.clone();
+        writeln!(writer, "hello").unwrap();
+        assert_eq!(buffer.contents(), "hello\n");
+        assert_eq!(buffer.take(), "hello\n");
+        assert_eq!(buffer.contents(), "");
+    }
+}
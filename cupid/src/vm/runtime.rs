@@ -1,54 +1,153 @@
-use std::ops::Deref;
-
 use crate::{
     chunk::Instruction,
+    code::Code,
+    diagnostic::{Diagnostic, ErrorFormat},
     error::CupidErr,
     objects::{Array, Class, Closure, RoleImpl},
     table::Table,
     value::Value,
 };
 
-use super::Vm;
+use super::{Handler, Thrown, Vm};
+use std::{io::Write, mem};
 
 pub trait Runtime {
-    fn as_runtime_err(&self, msg: impl std::fmt::Display) -> CupidErr;
-    fn runtime_err(&self, msg: impl std::fmt::Display) -> Result<(), CupidErr>;
+    fn as_runtime_err(&self, code: Code, msg: impl std::fmt::Display) -> CupidErr;
+    fn runtime_err(&self, code: Code, msg: impl std::fmt::Display) -> Result<(), CupidErr>;
+    fn runtime_err_with_help(
+        &self,
+        code: Code,
+        msg: impl std::fmt::Display,
+        help: Option<String>,
+    ) -> Result<(), CupidErr>;
     fn run(&mut self) -> Result<(), CupidErr>;
+    fn run_until(&mut self, depth: usize) -> Result<(), CupidErr>;
 }
 
 impl Runtime for Vm {
-    fn as_runtime_err(&self, msg: impl std::fmt::Display) -> CupidErr {
-        self.runtime_err(msg).unwrap_err()
+    fn as_runtime_err(&self, code: Code, msg: impl std::fmt::Display) -> CupidErr {
+        self.runtime_err(code, msg).unwrap_err()
+    }
+
+    fn runtime_err(&self, code: Code, msg: impl std::fmt::Display) -> Result<(), CupidErr> {
+        self.runtime_err_with_help(code, msg, None)
     }
 
-    fn runtime_err(&self, msg: impl std::fmt::Display) -> Result<(), CupidErr> {
-        eprintln!("{}", msg);
-        eprintln!("[line {}] in script", self.frames.frame().line());
+    /// Reports the error along with the stack trace and then, if there is one, the help note,
+    /// followed by where to find the explanation of its code. In the JSON error format, or when the
+    /// vm collects its errors, all of it makes up a single diagnostic instead. An error in a try
+    /// body is thrown instead, and only reported if nothing catches it after all.
+    fn runtime_err_with_help(
+        &self,
+        code: Code,
+        msg: impl std::fmt::Display,
+        help: Option<String>,
+    ) -> Result<(), CupidErr> {
+        if catchable(code) && self.frames.catches() {
+            self.thrown.replace(Some(Thrown::Error {
+                code,
+                message: msg.to_string(),
+                trace: self.frames.trace(),
+            }));
+            return Err(CupidErr::RuntimeError);
+        }
+        if let Some(diagnostics) = &self.diagnostics {
+            let path = self.path.as_deref();
+            let diagnostic = Diagnostic::runtime(code, msg, help, &self.frames, path);
+            diagnostics.borrow_mut().push(diagnostic);
+            return Err(CupidErr::RuntimeError);
+        }
+        // A report that can't be written is lost, and the error is still returned
+        let mut streams = self.streams.borrow_mut();
+        let stderr = &mut streams.stderr;
+        if self.error_format == ErrorFormat::Json {
+            let path = self.path.as_deref();
+            let diagnostic = Diagnostic::runtime(code, msg, help, &self.frames, path);
+            writeln!(stderr, "{}", diagnostic.to_json()).ok();
+            return Err(CupidErr::RuntimeError);
+        }
+        writeln!(stderr, "{}", msg).ok();
+        for line in self.frames.trace() {
+            writeln!(stderr, "{line}").ok();
+        }
+        if let Some(help) = help {
+            writeln!(stderr, "help: {help}").ok();
+        }
+        writeln!(stderr, "{}", code.note()).ok();
         Err(CupidErr::RuntimeError)
     }
 
     fn run(&mut self) -> Result<(), CupidErr> {
+        self.run_until(0)
+    }
+
+    /// Runs the current frame until it returns to a call stack `depth` frames deep, which is zero
+    /// for a whole program and more for a call made from a native. Errors thrown in the frames
+    /// above `depth` are caught there, while the others are left to the run below.
+    fn run_until(&mut self, depth: usize) -> Result<(), CupidErr> {
+        loop {
+            match self.execute(depth) {
+                Err(error) if !self.catch(depth) => return Err(error),
+                Err(_) => (),
+                Ok(()) => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Whether the error can be caught: the ones that stop a runaway program can't.
+fn catchable(code: Code) -> bool {
+    !matches!(code, Code::OutOfFuel | Code::HeapLimit | Code::Interrupted | Code::Internal)
+}
+
+impl Vm {
+    /// Unwinds to the innermost try body running in the frames above `depth`, if there is one and
+    /// an error is being thrown, and starts its catch body with the error on top of the stack.
+    fn catch(&mut self, depth: usize) -> bool {
+        if self.thrown.get_mut().is_none() {
+            return false;
+        }
+        let frames = &self.frames.frames;
+        let index = match (depth..frames.len()).rev().find(|&i| !frames[i].handlers.is_empty()) {
+            Some(index) => index,
+            None => return false,
+        };
+        self.frames.frames.truncate(index + 1);
+        let frame = self.frames.current_frame();
+        let Handler { catch, stack_len } = frame.handlers.pop().expect("No handler");
+        frame.ip = catch;
+        self.close_upvalues(stack_len);
+        self.stack.truncate(stack_len);
+        let error = self.caught();
+        self.stack.push(error);
+        true
+    }
+
+    fn execute(&mut self, depth: usize) -> Result<(), CupidErr> {
         let mut state = self.frames.state();
+        // Instructions run since the last checkpoint, which pays for them with fuel
+        let mut spent: u64 = 0;
 
         loop {
             let instruction = state.instruction();
             // println!("{:?}", instruction);
             state.set_instruction(1);
+            spent += 1;
 
             match instruction {
                 Instruction::Add => {
                     let (b, a) = (self.stack.pop(), self.stack.pop());
-                    let result = a.add(b, self).map_err(|e| self.as_runtime_err(e))?;
+                    let result = a
+                        .add(b, self)
+                        .map_err(|e| self.as_runtime_err(Code::InvalidOperands, e))?;
                     self.stack.push(result);
                 }
                 Instruction::Array(item_count) => {
-                    let mut items = vec![];
-                    for _ in 0..item_count {
-                        let item = self.stack.pop();
-                        items.push(item);
-                    }
-                    items.reverse();
+                    // The items stay on the stack (and rooted) until the array is allocated
+                    let first = self.stack.len() - item_count as usize;
+                    let items = self.stack.stack[first..self.stack.len()].to_vec();
                     let array = self.alloc(Array::new(items));
+                    self.stack.truncate(first);
                     self.stack.push(Value::Array(array));
                 }
                 Instruction::Class(constant) => {
@@ -85,6 +184,7 @@ impl Runtime for Vm {
                     }
                 }
                 Instruction::Call(arg_count) => {
+                    self.checkpoint(mem::take(&mut spent))?;
                     self.call_value(arg_count as usize)?;
                     state = self.frames.state();
                 }
@@ -99,7 +199,9 @@ impl Runtime for Vm {
                 }
                 Instruction::Divide => {
                     let (b, a) = (self.stack.pop(), self.stack.pop());
-                    self.stack.push(a.divide(b).map_err(|e| self.as_runtime_err(e))?);
+                    self.stack.push(
+                        a.divide(b).map_err(|e| self.as_runtime_err(Code::InvalidOperands, e))?,
+                    );
                 }
                 Instruction::Equal => {
                     let (b, a) = (self.stack.pop(), self.stack.pop());
@@ -110,12 +212,7 @@ impl Runtime for Vm {
                     let global_name = state.chunk.read_string(constant);
                     match self.globals.get(global_name) {
                         Some(value) => self.stack.push(value),
-                        None => {
-                            return self.runtime_err(format!(
-                                "Undefined variable '{}'.",
-                                global_name.deref()
-                            ));
-                        }
+                        None => return self.undefined_variable(global_name),
                     }
                 }
                 Instruction::GetLocal(slot) => {
@@ -135,7 +232,8 @@ impl Runtime for Vm {
                             None => self.bind_method(class, property_name)?,
                         }
                     } else {
-                        return self.runtime_err("Only instances have properties.");
+                        return self
+                            .runtime_err(Code::NotAnInstance, "Only instances have properties.");
                     }
                 }
                 Instruction::GetSuper(constant) => {
@@ -143,7 +241,7 @@ impl Runtime for Vm {
                     if let Value::Class(superclass) = self.stack.pop() {
                         self.bind_method(superclass, method_name)?;
                     } else {
-                        return self.runtime_err("Super found no class");
+                        return self.runtime_err(Code::Internal, "Super found no class");
                     }
                 }
                 Instruction::GetUpvalue(slot) => {
@@ -159,20 +257,26 @@ impl Runtime for Vm {
                 }
                 Instruction::Greater => {
                     let (b, a) = (self.stack.pop(), self.stack.pop());
-                    self.stack.push(a.greater(b).map_err(|e| self.as_runtime_err(e))?);
+                    self.stack.push(
+                        a.greater(b).map_err(|e| self.as_runtime_err(Code::InvalidOperands, e))?,
+                    );
                 }
                 Instruction::Inherit => {
                     let pair = (self.stack.peek(0), self.stack.peek(1));
                     if let (Value::Class(mut subclass), Value::Class(superclass)) = pair {
                         subclass.methods = Table::default();
                         subclass.methods.add_all(&superclass.methods);
+                        subclass.fields.add_all(&superclass.fields);
+                        self.gc.write_barrier(subclass);
+                        self.gc.resize(subclass);
                         self.stack.pop();
                     } else {
-                        return self.runtime_err("Superclass must be a class.");
+                        return self.runtime_err(Code::NotAClass, "Superclass must be a class.");
                     }
                 }
                 Instruction::Invoke(constant, arg_count) => {
                     let name = state.chunk.read_string(constant);
+                    self.checkpoint(mem::take(&mut spent))?;
                     self.invoke(name, arg_count as usize)?;
                     state = self.frames.state();
                 }
@@ -186,18 +290,31 @@ impl Runtime for Vm {
                 }
                 Instruction::Less => {
                     let (b, a) = (self.stack.pop(), self.stack.pop());
-                    self.stack.push(a.lesser(b).map_err(|e| self.as_runtime_err(e))?);
+                    self.stack.push(
+                        a.lesser(b).map_err(|e| self.as_runtime_err(Code::InvalidOperands, e))?,
+                    );
                 }
                 Instruction::Loop(offset) => {
+                    self.checkpoint(mem::take(&mut spent))?;
                     state.set_instruction(-1 - (offset as isize));
                 }
+                Instruction::Field(constant) => {
+                    let field_name = state.chunk.read_string(constant);
+                    if let Value::Class(mut class) = self.stack.peek(0) {
+                        class.fields.set(field_name, Value::Nil);
+                        self.gc.write_barrier(class);
+                        self.gc.resize(class);
+                    }
+                }
                 Instruction::Method(constant) => {
                     let method_name = state.chunk.read_string(constant);
                     self.define_method(method_name);
                 }
                 Instruction::Multiply => {
                     let (b, a) = (self.stack.pop(), self.stack.pop());
-                    self.stack.push(a.multiply(b).map_err(|e| self.as_runtime_err(e))?);
+                    self.stack.push(
+                        a.multiply(b).map_err(|e| self.as_runtime_err(Code::InvalidOperands, e))?,
+                    );
                 }
                 Instruction::Negate => match self.stack.peek(0) {
                     Value::Float(value) => {
@@ -208,7 +325,9 @@ impl Runtime for Vm {
                         self.stack.pop();
                         self.stack.push(Value::Int(-value));
                     }
-                    _ => return self.runtime_err("Operand must be a number."),
+                    _ => {
+                        return self.runtime_err(Code::InvalidOperands, "Operand must be a number.")
+                    }
                 },
                 Instruction::Nil => self.stack.push(Value::Nil),
                 Instruction::Not => {
@@ -218,18 +337,54 @@ impl Runtime for Vm {
                 Instruction::Pop => {
                     self.stack.pop();
                 }
+                Instruction::PopHandler => {
+                    state.frame.handlers.pop();
+                }
+                Instruction::Propagate(offset) => {
+                    let value = self.stack.peek(0);
+                    match (value, self.unwrap(value)) {
+                        (_, Some(inner)) => {
+                            self.stack.pop();
+                            self.stack.push(inner);
+                            state.set_instruction(offset as isize);
+                        }
+                        // Left for the return after it
+                        (Value::Nil, None) => (),
+                        (Value::Instance(instance), None) if instance.class == self.err_class => (),
+                        _ => {
+                            return self.runtime_err(
+                                Code::InvalidOperands,
+                                "Operand of '?' must be an option or a result.",
+                            )
+                        }
+                    }
+                }
+                Instruction::PushHandler(offset, locals) => {
+                    let handler = Handler {
+                        catch: unsafe { state.frame.ip.offset(offset as isize) },
+                        stack_len: state.frame.slot + locals as usize,
+                    };
+                    state.frame.handlers.push(handler);
+                }
                 Instruction::Log => {
-                    println!("{}", self.stack.pop());
+                    let value = self.stack.pop();
+                    let stdout = &mut self.streams.get_mut().stdout;
+                    if writeln!(stdout, "{value}").is_err() {
+                        return self.runtime_err(Code::Io, "Unable to write to stdout.");
+                    }
                 }
                 Instruction::Return => {
-                    self.frames.count -= 1;
+                    let slot = state.frame.slot;
+                    self.frames.decrement();
                     let return_value = self.stack.pop();
-                    self.close_upvalues(state.frame.slot);
+                    self.close_upvalues(slot);
 
-                    if self.frames.count == 0 {
+                    if self.frames.count() == depth {
+                        // Left for whoever started the run, like a native calling a function
+                        self.stack.push(return_value);
                         return Ok(());
                     } else {
-                        self.stack.truncate(state.frame.slot);
+                        self.stack.truncate(slot);
                         self.stack.push(return_value);
                         state = self.frames.state();
                     }
@@ -241,7 +396,12 @@ impl Runtime for Vm {
                             let role_impl = self.alloc(RoleImpl::new(role, class));
                             self.stack.push(Value::RoleImpl(role_impl));
                         }
-                        _ => return self.runtime_err("Traits may only be implemented on classes."),
+                        _ => {
+                            return self.runtime_err(
+                                Code::NotAClass,
+                                "Traits may only be implemented on classes.",
+                            )
+                        }
                     }
                 }
                 Instruction::SetGlobal(constant) => {
@@ -249,8 +409,7 @@ impl Runtime for Vm {
                     let value = self.stack.peek(0);
                     if self.globals.set(global_name, value) {
                         self.globals.delete(global_name);
-                        return self
-                            .runtime_err(format!("Undefined variable '{}'.", global_name.deref()));
+                        return self.undefined_variable(global_name);
                     }
                 }
                 Instruction::SetLocal(slot) => {
@@ -263,10 +422,13 @@ impl Runtime for Vm {
                         let property_name = state.chunk.read_string(constant);
                         let value = self.stack.pop();
                         instance.fields.set(property_name, value);
+                        self.gc.write_barrier(instance);
+                        self.gc.resize(instance);
                         self.stack.pop();
                         self.stack.push(value);
                     } else {
-                        return self.runtime_err("Only instances have fields.");
+                        return self
+                            .runtime_err(Code::NotAnInstance, "Only instances have fields.");
                     }
                 }
                 Instruction::SetUpvalue(slot) => {
@@ -276,21 +438,29 @@ impl Runtime for Vm {
                         self.stack.stack[upvalue.location] = value;
                     } else {
                         upvalue.closed = Some(value);
+                        self.gc.write_barrier(upvalue);
                     }
                 }
                 Instruction::Subtract => {
                     let (b, a) = (self.stack.pop(), self.stack.pop());
-                    self.stack.push(a.subtract(b).map_err(|e| self.as_runtime_err(e))?);
+                    self.stack.push(
+                        a.subtract(b).map_err(|e| self.as_runtime_err(Code::InvalidOperands, e))?,
+                    );
                 }
                 Instruction::SuperInvoke(constant, arg_count) => {
                     let method_name = state.chunk.read_string(constant);
                     if let Value::Class(class) = self.stack.pop() {
+                        self.checkpoint(mem::take(&mut spent))?;
                         self.invoke_from_class(class, method_name, arg_count as usize)?;
                         state = self.frames.state();
                     } else {
                         panic!("super invoke with no class");
                     }
                 }
+                Instruction::Throw => {
+                    let value = self.stack.pop();
+                    return Err(self.throw(value));
+                }
                 Instruction::True => self.stack.push(Value::Bool(true)),
             };
         }
@@ -1,40 +1,39 @@
-use std::ptr::null_mut;
+use crate::value::Value;
 
-use crate::{value::Value, vm::Frames};
-
-#[derive(Debug, Clone, Copy)]
+/// The value stack, which lives on the heap and grows as needed. Popping is always bounds checked,
+/// while slots are accessed without bounds checks unless the `checked` feature is enabled, in which
+/// case every access is validated against the current top of the stack.
+#[derive(Debug, Clone)]
 pub struct Stack<T: Copy + std::fmt::Debug> {
-    pub stack: [T; SIZE],
-    pub top: *mut T,
+    pub stack: Vec<T>,
 }
 
-pub const SIZE: usize = Frames::MAX * (std::u8::MAX as usize) + 1;
+/// Slots reserved up front, enough for most programs to never grow the stack
+pub const INITIAL_SIZE: usize = 256;
 
 impl<T: Copy + std::fmt::Debug> Stack<T> {
     pub fn push(&mut self, v: T) {
-        unsafe {
-            *self.top = v;
-            self.top = self.top.offset(1);
-        }
+        self.stack.push(v);
     }
 
     pub fn pop(&mut self) -> T {
-        unsafe {
-            self.top = self.top.offset(-1);
-            *self.top
-        }
+        self.stack.pop().expect("Stack pop from an empty stack")
     }
 
     pub fn peek(&self, n: usize) -> T {
-        unsafe { *self.top.offset(-1 - n as isize) }
+        #[cfg(feature = "checked")]
+        assert!(n < self.len(), "Stack peek {} below the bottom of a stack of {}", n, self.len());
+        unsafe { *self.stack.get_unchecked(self.len() - 1 - n) }
     }
 
     pub fn truncate(&mut self, index: usize) {
-        unsafe { self.top = self.stack.as_mut_ptr().add(index) }
+        #[cfg(feature = "checked")]
+        assert!(index <= self.len(), "Stack truncated past its top ({} > {})", index, self.len());
+        self.stack.truncate(index);
     }
 
     pub fn len(&self) -> usize {
-        unsafe { self.top.offset_from(self.stack.as_ptr()) as usize }
+        self.stack.len()
     }
 
     pub fn is_empty(&self) -> bool {
@@ -42,18 +41,106 @@ impl<T: Copy + std::fmt::Debug> Stack<T> {
     }
 
     pub fn set_at(&mut self, n: usize, value: T) {
-        unsafe {
-            let pos = self.top.offset(-1 - (n as isize));
-            *pos = value
-        }
+        #[cfg(feature = "checked")]
+        assert!(n < self.len(), "Stack set {} below the bottom of a stack of {}", n, self.len());
+        let index = self.len() - 1 - n;
+        unsafe { *self.stack.get_unchecked_mut(index) = value }
     }
 }
 
 impl Default for Stack<Value> {
     fn default() -> Self {
         Self {
-            stack: [Value::Nil; SIZE],
-            top: null_mut(),
+            stack: Vec::with_capacity(INITIAL_SIZE),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Stack, INITIAL_SIZE};
+    use crate::value::Value;
+
+    #[test]
+    fn push_pop() {
+        let mut stack = Stack::default();
+        assert!(stack.is_empty());
+        stack.push(Value::Int(1));
+        stack.push(Value::Int(2));
+        assert_eq!(stack.len(), 2);
+        assert_eq!(stack.pop(), Value::Int(2));
+        assert_eq!(stack.pop(), Value::Int(1));
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn peek_and_set() {
+        let mut stack = Stack::default();
+        for i in 0..4 {
+            stack.push(Value::Int(i));
+        }
+        assert_eq!(stack.peek(0), Value::Int(3));
+        assert_eq!(stack.peek(3), Value::Int(0));
+        stack.set_at(1, Value::Bool(true));
+        assert_eq!(stack.peek(1), Value::Bool(true));
+        assert_eq!(stack.stack[2], Value::Bool(true));
+    }
+
+    #[test]
+    fn truncate() {
+        let mut stack = Stack::default();
+        for i in 0..10 {
+            stack.push(Value::Int(i));
+        }
+        stack.truncate(3);
+        assert_eq!(stack.len(), 3);
+        assert_eq!(stack.peek(0), Value::Int(2));
+    }
+
+    #[test]
+    fn moved_stack_stays_valid() {
+        let mut stack = Stack::default();
+        stack.push(Value::Int(1));
+        let mut moved = Box::new(stack.clone());
+        moved.push(Value::Int(2));
+        assert_eq!(moved.pop(), Value::Int(2));
+        assert_eq!(moved.pop(), Value::Int(1));
+        assert_eq!(stack.len(), 1);
+    }
+
+    #[test]
+    fn grow() {
+        let mut stack = Stack::default();
+        for i in 0..(INITIAL_SIZE * 4) {
+            stack.push(Value::Int(i as i32));
+        }
+        assert_eq!(stack.len(), INITIAL_SIZE * 4);
+        assert_eq!(stack.peek(0), Value::Int(INITIAL_SIZE as i32 * 4 - 1));
+        assert_eq!(stack.stack[0], Value::Int(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "empty stack")]
+    fn underflow() {
+        let mut stack = Stack::default();
+        stack.pop();
+    }
+
+    #[cfg(feature = "checked")]
+    #[test]
+    #[should_panic(expected = "past its top")]
+    fn checked_truncate() {
+        let mut stack = Stack::default();
+        stack.push(Value::Nil);
+        stack.truncate(2);
+    }
+
+    #[cfg(feature = "checked")]
+    #[test]
+    #[should_panic(expected = "below the bottom")]
+    fn checked_peek() {
+        let mut stack = Stack::default();
+        stack.push(Value::Nil);
+        stack.peek(1);
+    }
+}
@@ -0,0 +1,298 @@
+use std::{cell::Cell, env, fs, io::Cursor, process::Command, rc::Rc, thread, time::Duration};
+
+extern crate cupid;
+
+use cupid::{
+    engine::{Engine, FileError},
+    gc::GcConfig,
+    objects::Foreign,
+    value::Value,
+    vm::{Buffer, Thrown, VmConfig},
+};
+
+/// The freshly built binary, as in the integration tests.
+fn cupid_command() -> Command {
+    let mut path = env::current_exe().unwrap().parent().unwrap().parent().unwrap().to_owned();
+    path.push(env!("CARGO_PKG_NAME"));
+    path.set_extension(env::consts::EXE_EXTENSION);
+    Command::new(path.into_os_string())
+}
+
+/// Writes `code` to a file of its own under the target directory.
+fn write_file(name: &str, code: &str) -> String {
+    let mut path = env::current_exe().unwrap().parent().unwrap().to_owned();
+    path.push(format!("engine-{name}.cupid"));
+    fs::write(&path, code).unwrap();
+    path.display().to_string()
+}
+
+#[test]
+fn evaluates_code_against_the_same_globals() {
+    let mut engine = Engine::new();
+    engine
+        .eval("let greeting = 'hello'\nfun add (a, b) {\n    return a + b\n}\n")
+        .unwrap();
+    assert_eq!(engine.eval("add(1, 2)\n").unwrap(), Value::Int(3));
+    assert_eq!(engine.eval("greeting\n").unwrap().to_string(), "'hello'");
+    assert!(engine.globals().any(|(name, _)| name.s == "add"));
+    assert_eq!(engine.global("missing"), None);
+}
+
+#[test]
+fn evaluates_to_the_last_expression_only() {
+    let mut engine = Engine::new();
+    assert_eq!(engine.eval("1 + 1\nlet total = 3\n").unwrap(), Value::Nil);
+    assert_eq!(engine.eval("2\nlog(total)\n").unwrap(), Value::Nil);
+    assert_eq!(engine.eval("total = 4\n").unwrap(), Value::Int(4));
+    assert_eq!(engine.eval("").unwrap(), Value::Nil);
+}
+
+#[test]
+fn calls_globals_from_the_host() {
+    let mut engine = Engine::new();
+    engine.set_global("limit", Value::Int(10));
+    engine
+        .eval("fun clamp (n) {\n    if n > limit { return limit }\n    return n\n}\n")
+        .unwrap();
+    engine
+        .eval("class Point {\n    init (x) {\n        self.x = x\n    }\n}\n")
+        .unwrap();
+
+    assert_eq!(engine.call_global("clamp", &[Value::Int(42)]).unwrap(), Value::Int(10));
+    assert_eq!(engine.call_global("clamp", &[Value::Int(4)]).unwrap(), Value::Int(4));
+    let point = engine.call_global("Point", &[Value::Int(1)]).unwrap();
+    assert_eq!(point.to_string(), "Point instance");
+    let greeting = engine.string("hi");
+    engine.set_global("greeting", greeting);
+    assert_eq!(engine.eval("greeting\n").unwrap().to_string(), "'hi'");
+}
+
+#[test]
+fn returns_errors_as_diagnostics() {
+    let mut engine = Engine::new();
+    let errors = engine.eval("let amount = 1\nlog(amont)\n").unwrap_err();
+    assert_eq!(errors.0[0].code, "E0102");
+    assert_eq!(errors.0[0].spans[0].line_start, 2);
+
+    engine.eval("fun twice (f) {\n    return f() + f()\n}\n").unwrap();
+    let errors = engine.call_global("twice", &[Value::Int(1)]).unwrap_err();
+    assert_eq!(errors.0[0].code, "E0201");
+    assert_eq!(errors.0[0].spans[0].label.as_deref(), Some("in twice()"));
+
+    let errors = engine.call_global("thrice", &[]).unwrap_err();
+    assert_eq!(errors.0[0].message, "Undefined variable 'thrice'.");
+
+    // Fields declared in the body of a class, or of its superclass, are suggested too
+    engine
+        .eval("class Tally {\n    let count = 0\n}\nclass Score < Tally {}\n")
+        .unwrap();
+    let errors = engine.eval("Score().cout\n").unwrap_err();
+    assert_eq!(errors.0[0].code, "E0103");
+    assert_eq!(errors.0[0].suggestions, ["did you mean `count`?"]);
+    assert_eq!(engine.eval("1 + 1\n").unwrap(), Value::Int(2));
+
+    // As are the errors only found while compiling to bytecode
+    let errors = engine.eval("log('never')\nbreak\n").unwrap_err();
+    assert_eq!(errors.0[0].code, "E0003");
+    assert_eq!(errors.0[0].spans[0].line_start, 2);
+    let errors = engine.eval("fun f () {\n    let a = 1\n    let a = 2\n}\n").unwrap_err();
+    assert_eq!(errors.0[0].code, "E0104");
+    assert_eq!(errors.0[0].spans[0].line_start, 3);
+    assert_eq!(engine.eval("1 + 1\n").unwrap(), Value::Int(2));
+}
+
+#[test]
+fn runs_files_like_the_binary() {
+    let path = write_file("ok", "fun square (x) {\n    return x * x\n}\nlog(square(3))\n");
+    let mut engine = Engine::new();
+    assert_eq!(engine.run_file(&path).unwrap(), Value::Nil);
+    assert_eq!(engine.call_global("square", &[Value::Int(4)]).unwrap(), Value::Int(16));
+    let output = cupid_command().arg(&path).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "9\n");
+
+    let path = write_file("err", "fun f () {\n    f()\n}\nf()\n");
+    let errors = match Engine::new().run_file(&path) {
+        Err(FileError::Diagnostics(errors)) => errors,
+        result => panic!("expected a runtime error, got {:?}", result),
+    };
+    assert_eq!(errors.0[0].code, "E0302");
+    assert_eq!(errors.0[0].spans[0].file.as_deref(), Some(path.as_str()));
+    let output = cupid_command().arg(&path).output().unwrap();
+    assert_eq!(output.status.code(), Some(70));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.lines().next(), Some(errors.0[0].message.as_str()));
+
+    assert!(matches!(Engine::new().run_file("missing.cupid"), Err(FileError::Unreadable(_))));
+}
+
+#[test]
+fn calls_natives_with_captured_state() {
+    let calls = Rc::new(Cell::new(0));
+    let mut engine = Engine::new();
+    let counter = calls.clone();
+    engine.register_native("tick", 1, move |_, args| {
+        counter.set(counter.get() + 1);
+        match args[0] {
+            Value::Int(step) => Ok(Value::Int(counter.get() * step)),
+            _ => Ok(Value::Nil),
+        }
+    });
+    assert_eq!(engine.eval("tick(10)\ntick(10)\n").unwrap(), Value::Int(20));
+    assert_eq!(calls.get(), 2);
+    assert_eq!(engine.global("tick").unwrap().to_string(), "<native fun tick>");
+    let errors = engine.eval("tick()\n").unwrap_err();
+    assert_eq!(errors.0[0].code, "E0301");
+    assert_eq!(errors.0[0].message, "Expected 1 arguments but got 0.");
+}
+
+struct Account {
+    balance: i32,
+}
+
+#[test]
+fn wraps_host_values_in_foreign_objects() {
+    let closed = Rc::new(Cell::new(None));
+    let mut config = VmConfig::default();
+    config.gc.stress = true;
+    let mut engine = Engine::with_config(config);
+    let class = engine.foreign_class("Account");
+    engine.register_method(class, "deposit", 1, |_, args| match (args[0], args[1]) {
+        (Value::Foreign(mut account), Value::Int(amount)) => {
+            let account = account.downcast_mut::<Account>().unwrap();
+            account.balance += amount;
+            Ok(Value::Int(account.balance))
+        }
+        _ => Ok(Value::Nil),
+    });
+    let on_close = closed.clone();
+    let account = Foreign::new(class, Account { balance: 5 })
+        .with_finalizer(move |account: &mut Account| on_close.set(Some(account.balance)));
+    let account = engine.foreign(account);
+    engine.set_global("account", account);
+
+    assert_eq!(engine.eval("account.deposit(10)\n").unwrap(), Value::Int(15));
+    assert_eq!(engine.eval("account\n").unwrap().to_string(), "Account object");
+    let errors = engine.eval("account.withdraw(1)\n").unwrap_err();
+    assert_eq!(errors.0[0].message, "Undefined property 'withdraw'.");
+    assert_eq!(closed.get(), None);
+
+    engine.set_global("account", Value::Nil);
+    engine.eval("let collect = [1, 2, 3]\n").unwrap();
+    assert_eq!(closed.get(), Some(15));
+}
+
+#[test]
+fn calls_back_into_cupid_from_natives() {
+    let mut engine = Engine::new();
+    engine.register_native("apply_twice", 2, |vm, args| {
+        let once = vm.call(args[0], &[args[1]])?;
+        vm.call(args[0], &[once])
+    });
+    engine.eval("fun inc (x) {\n    return x + 1\n}\n").unwrap();
+    assert_eq!(engine.eval("apply_twice(inc, 1)\n").unwrap(), Value::Int(3));
+    let inc = engine.global("inc").unwrap();
+    assert_eq!(engine.vm().call(inc, &[Value::Int(41)]).unwrap(), Value::Int(42));
+
+    // Errors in the callback unwind through the native, with the script's frames in the trace
+    engine.eval("fun fail (x) {\n    return x()\n}\n").unwrap();
+    let errors = engine.eval("apply_twice(fail, 1)\n").unwrap_err();
+    assert_eq!(errors.0[0].code, "E0201");
+    let labels: Vec<_> = errors.0[0].spans.iter().map(|span| span.label.clone().unwrap()).collect();
+    assert_eq!(labels, ["in fail()", "in script"]);
+    assert_eq!(engine.vm().frames.count(), 0);
+    assert_eq!(engine.eval("apply_twice(inc, 5)\n").unwrap(), Value::Int(7));
+
+    // A try call gets the error back instead of reporting it
+    let fail = engine.global("fail").unwrap();
+    let thrown = engine.vm().try_call(fail, &[Value::Int(1)]).unwrap();
+    assert!(
+        matches!(thrown, Err(Thrown::Error { message, .. }) if message.starts_with("Can only call"))
+    );
+    assert_eq!(engine.vm().try_call(inc, &[Value::Int(1)]).unwrap().unwrap(), Value::Int(2));
+}
+
+#[test]
+fn redirects_the_streams_of_the_vm() {
+    let (stdout, stderr) = (Buffer::new(), Buffer::new());
+    let mut engine = Engine::new();
+    engine.vm().set_stdout(stdout.clone());
+    engine.vm().set_stderr(stderr.clone());
+    engine.vm().set_stdin(Cursor::new("Ada\r\nGrace"));
+    engine.eval("log(read_line())\nlog(read_line())\nlog(read_line())\n").unwrap();
+    assert_eq!(stdout.take(), "'Ada'\n'Grace'\nnone\n");
+
+    // Without collected diagnostics, runtime errors are reported to the vm's stderr
+    engine.vm().diagnostics = None;
+    engine.eval("log('before')\nfun f () { f() }\nf()\n").unwrap_err();
+    assert_eq!(stdout.take(), "'before'\n");
+    let report = stderr.take();
+    assert!(report.starts_with("Stack overflow."), "{}", report);
+    assert!(report.ends_with("try `cupid explain E0302`.\n"), "{}", report);
+}
+
+#[test]
+fn stops_runs_over_their_budget() {
+    let config = VmConfig {
+        gc: GcConfig {
+            max_heap: Some(256 * 1024),
+            ..GcConfig::default()
+        },
+        fuel: Some(10_000),
+        ..VmConfig::default()
+    };
+    let mut engine = Engine::with_config(config);
+
+    let errors = engine.eval("while true {}\n").unwrap_err();
+    assert_eq!(errors.0[0].code, "E0305");
+    assert_eq!(engine.eval("fun f () {}\nf()\n").unwrap_err().0[0].code, "E0305");
+    engine.vm().fuel = Some(1_000_000);
+    assert_eq!(engine.eval("1 + 1\n").unwrap(), Value::Int(2));
+
+    // Garbage doesn't count towards the limit, only what is still reachable
+    engine
+        .eval("fun garbage () {\n    let a = [1, 2, 3, 4, 5, 6, 7, 8]\n}\n")
+        .unwrap();
+    engine
+        .eval("let i = 0\nwhile i < 2000 {\n    garbage()\n    i = i + 1\n}\n")
+        .unwrap();
+    let errors = engine.eval("let items = []\nwhile true {\n    push(items, [1, 2, 3])\n}\n");
+    assert_eq!(errors.unwrap_err().0[0].code, "E0306");
+    engine.set_global("items", Value::Nil);
+    assert_eq!(engine.eval("2 + 2\n").unwrap(), Value::Int(4));
+}
+
+#[test]
+fn checks_the_heap_limit_at_calls_and_loops() {
+    let config = VmConfig {
+        gc: GcConfig {
+            max_heap: Some(256 * 1024),
+            ..GcConfig::default()
+        },
+        ..VmConfig::default()
+    };
+    let mut engine = Engine::with_config(config);
+
+    // The limit is a soft one, which code that neither calls nor loops can go over
+    let code = format!("let big = '{}'\n1 + 1\n", "x".repeat(512 * 1024));
+    assert_eq!(engine.eval(&code).unwrap(), Value::Int(2));
+    let errors = engine.eval("fun f () {}\nf()\n").unwrap_err();
+    assert_eq!(errors.0[0].code, "E0306");
+    engine.set_global("big", Value::Nil);
+    assert_eq!(engine.eval("f()\n").unwrap(), Value::Nil);
+}
+
+#[test]
+fn interrupts_runs_from_another_thread() {
+    let mut engine = Engine::new();
+    let interrupt = engine.vm().interrupt.clone();
+    let thread = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        interrupt.interrupt();
+    });
+    let errors = engine.eval("while true {}\n").unwrap_err();
+    thread.join().unwrap();
+    assert_eq!(errors.0[0].code, "E0307");
+    assert_eq!(errors.0[0].message, "Interrupted.");
+    assert_eq!(engine.eval("1 + 1\n").unwrap(), Value::Int(2));
+}
@@ -9,6 +9,8 @@ use cupid_fmt::color;
 use regex::Regex;
 use test_generator::test_resources;
 
+/// Runs the freshly built binary. The child inherits the environment, so the whole suite can be run
+/// with the collector in stress mode: `CUPID_GC_STRESS=1 cargo test --test integration`
 fn cupid_command() -> Command {
     // Create full path to binary
     let mut path = env::current_exe()
@@ -39,6 +41,7 @@ fn parse_comments(path: &PathBuf) -> Expected {
     let output_re = Regex::new(r"-->? expect: ?(.*)").unwrap();
     let error_re = Regex::new(r"-->? (Error.*)").unwrap();
     let error_line_re = Regex::new(r"-->? \[(?:c )?line (\d+)\] (Error.*)").unwrap();
+    let diagnostic_re = Regex::new(r"-->? (error\[E\d+\]: .*)").unwrap();
     let runtime_error_re = Regex::new(r"-->? expect runtime error: (.+)").unwrap();
 
     let mut expected = Expected {
@@ -65,6 +68,11 @@ fn parse_comments(path: &PathBuf) -> Expected {
             let s = format!("[line {}] {}", i + 1, msg);
             expected.compile_err.push(s);
         }
+        if let Some(m) = diagnostic_re.captures(line) {
+            let msg = m.get(1).unwrap().as_str();
+            let s = format!("[line {}] {}", i + 1, msg);
+            expected.compile_err.push(s);
+        }
         if let Some(m) = runtime_error_re.captures(line) {
             let message = m.get(1).unwrap().as_str().to_owned();
             let line_prefix = format!("[line {}]", i + 1);
@@ -77,6 +85,38 @@ fn parse_comments(path: &PathBuf) -> Expected {
     expected
 }
 
+/// Cupid reports compile errors as diagnostics, a colored `type error[E0205]: msg` header with
+/// the location on a `  --> path:line:col` line below it. Each one is folded into the
+/// `[line N] error[E0205]: msg` form the comments use; other lines are kept as they are.
+fn compile_errors(err: &[String]) -> Vec<String> {
+    let ansi_re = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+    let header_re = Regex::new(r"^[a-z ]*(error\[E\d+\]: .*)$").unwrap();
+    let location_re = Regex::new(r"^\s*--> .*:(\d+):\d+$").unwrap();
+
+    let lines: Vec<String> = err
+        .iter()
+        .map(|line| ansi_re.replace_all(line, "").into_owned())
+        .collect();
+    if !lines.iter().any(|line| header_re.is_match(line)) {
+        return err.to_vec();
+    }
+    let mut errors = vec![];
+    for (i, line) in lines.iter().enumerate() {
+        if let Some(m) = header_re.captures(line) {
+            let msg = m.get(1).unwrap().as_str();
+            let location = lines[i + 1..]
+                .iter()
+                .take_while(|line| !header_re.is_match(line))
+                .find_map(|line| location_re.captures(line));
+            match location {
+                Some(location) => errors.push(format!("[line {}] {}", &location[1], msg)),
+                None => errors.push(msg.to_owned()),
+            }
+        }
+    }
+    errors
+}
+
 fn fmt_test_problem(msg: &str, expected: &Expected, errors: &[String]) -> String {
     let expected_compile_err = !expected.compile_err.is_empty();
     let expected_runtime_err = expected.runtime_err.is_some();
@@ -169,7 +209,7 @@ fn run_file_test(filename: &str) {
         }
         assert_eq!(
             expected.compile_err,
-            err,
+            compile_errors(&err),
             "{}",
             formatted("Compile error should match")
         );
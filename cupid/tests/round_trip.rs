@@ -0,0 +1,23 @@
+use std::fs;
+
+extern crate cupid;
+extern crate test_generator;
+
+use cupid::{gc::Gc, parse::parser::Parser};
+use test_generator::test_resources;
+
+/// Prints the syntax tree of the code back, along with the code that doesn't parse.
+fn print(code: &str) -> String {
+    let mut gc = Gc::default();
+    let mut parser = Parser::new(code);
+    let (file, _) = parser.parse_lossless(&mut gc);
+    file.print(&parser.arena)
+}
+
+/// Printing the syntax tree of a file gives back its exact code, comments and blank lines
+/// included, even when some of the code is invalid.
+#[test_resources("tests/integration/**/*.cupid")]
+fn round_trip(path: &str) {
+    let code = fs::read_to_string(path).unwrap();
+    assert_eq!(print(&code), code);
+}